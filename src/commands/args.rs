@@ -1,12 +1,22 @@
-use std::{env, ffi::OsString};
+use std::{env, ffi::OsString, path::PathBuf, sync::Arc, time::Duration};
 
-use crate::errors::Error;
+use crate::{
+    engine::{synchronous::Synchronous, vfs::MemoryVfs},
+    errors::Error,
+};
+
+use super::storage::{Layout, Storage};
 
 #[derive(Debug)]
 pub struct Config {
     pub tcp: bool,
     pub address: OsString,
     pub port: OsString,
+    pub busy_timeout: Duration,
+    pub synchronous: Synchronous,
+    /// Maximum number of pages kept in the page cache.
+    pub cache_size: usize,
+    pub storage: Storage,
 }
 
 impl Default for Config {
@@ -15,13 +25,22 @@ impl Default for Config {
             tcp: true,
             address: OsString::from("127.0.0.1"),
             port: OsString::from("80"),
+            busy_timeout: Duration::from_millis(5000),
+            synchronous: Synchronous::default(),
+            cache_size: 2000,
+            storage: Storage::default(),
         }
     }
 }
 
 pub fn parse_args() -> Result<Config, Error> {
+    parse(env::args_os())
+}
+
+fn parse(mut args: impl Iterator<Item = OsString>) -> Result<Config, Error> {
     let mut config = Config::default();
-    let mut args = env::args_os();
+    // a database is either a directory or a single file, never both.
+    let mut layout_flag: Option<&'static str> = None;
 
     while let Some(arg) = args.next() {
         match arg.as_os_str().to_str().expect("Failed to parse argument") {
@@ -33,6 +52,38 @@ pub fn parse_args() -> Result<Config, Error> {
                 let value = args.next().ok_or_else(|| Error::Argument)?;
                 config.port = value;
             }
+            "--busy-timeout" => {
+                let value = args.next().ok_or(Error::Argument)?;
+                let millis = value
+                    .to_str()
+                    .and_then(|x| x.parse::<u64>().ok())
+                    .ok_or(Error::Argument)?;
+                config.busy_timeout = Duration::from_millis(millis);
+            }
+            "--synchronous" => {
+                let value = args.next().ok_or(Error::Argument)?;
+                config.synchronous = value.to_str().ok_or(Error::Argument)?.parse()?;
+            }
+            "--cache-size" => {
+                let value = args.next().ok_or(Error::Argument)?;
+                config.cache_size = value
+                    .to_str()
+                    .and_then(|x| x.parse::<usize>().ok())
+                    .ok_or(Error::Argument)?;
+            }
+            "--data-dir" => {
+                pick_layout(&mut layout_flag, "--data-dir")?;
+                let value = args.next().ok_or(Error::Argument)?;
+                config.storage.layout = Layout::Directory(PathBuf::from(value));
+            }
+            "--file" => {
+                pick_layout(&mut layout_flag, "--file")?;
+                let value = args.next().ok_or(Error::Argument)?;
+                config.storage.layout = Layout::SingleFile(PathBuf::from(value));
+            }
+            "--memory" => {
+                config.storage.vfs = Arc::new(MemoryVfs::new());
+            }
             "--repl" => {
                 config.tcp = false;
             }
@@ -42,3 +93,49 @@ pub fn parse_args() -> Result<Config, Error> {
 
     Ok(config)
 }
+
+/// Remember the flag that picked the layout, failing when the other one already did.
+fn pick_layout(picked: &mut Option<&'static str>, flag: &'static str) -> Result<(), Error> {
+    match picked.replace(flag) {
+        Some(other) if other != flag => Err(Error::ArgumentConflict(other, flag)),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse;
+    use crate::{commands::storage::Layout, errors::Error};
+    use std::{ffi::OsString, path::Path};
+
+    fn parse_layout(args: &[&str]) -> Result<Layout, Error> {
+        let args = ["rust_database"].iter().chain(args).map(OsString::from);
+        Ok(parse(args)?.storage.layout)
+    }
+
+    #[test]
+    fn test_layout() {
+        assert!(matches!(
+            parse_layout(&[]),
+            Ok(Layout::Directory(dir)) if dir == Path::new("./db")
+        ));
+        assert!(matches!(
+            parse_layout(&["--data-dir", "/var/db"]),
+            Ok(Layout::Directory(dir)) if dir == Path::new("/var/db")
+        ));
+        assert!(matches!(
+            parse_layout(&["--file", "app.db", "--repl"]),
+            Ok(Layout::SingleFile(file)) if file == Path::new("app.db")
+        ));
+        assert!(matches!(parse_layout(&["--file"]), Err(Error::Argument)));
+
+        assert!(matches!(
+            parse_layout(&["--file", "app.db", "--data-dir", "/var/db"]),
+            Err(Error::ArgumentConflict("--file", "--data-dir"))
+        ));
+        assert!(matches!(
+            parse_layout(&["--data-dir", "/var/db", "--file", "app.db"]),
+            Err(Error::ArgumentConflict("--data-dir", "--file"))
+        ));
+    }
+}
@@ -1,78 +1,568 @@
-use crate::engine::structure::Record;
-use crate::engine::{btree::BTreeBuilder, node_type::Schema};
+use crate::commands::session::Session;
+use crate::commands::storage::{Layout, Storage};
+use crate::engine::catalog::{Catalog, CATALOG_TABLE};
+use crate::engine::error::Error as EngineError;
+use crate::engine::file_lock::LockMode;
+use crate::engine::page_cache::{lock_cache, PageCache, SharedPageCache};
+use crate::engine::structure::{Record, Value};
+use crate::engine::synchronous::Synchronous;
+use crate::engine::{
+    btree::{BTree, BTreeBuilder},
+    index::Index,
+    node_type::Schema,
+    planner::Plan,
+    stats::TableStats,
+    table::Table,
+};
 use crate::errors::Error;
-use crate::sql::Statement;
+use crate::sql::{ColumnExpr, Condition, Statement};
 use log::info;
 use std::collections::HashMap;
-use std::fs;
 use std::path::PathBuf;
-use std::sync::{Arc, RwLock};
+use std::sync::{Arc, Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard, TryLockError};
+use std::thread;
+use std::time::{Duration, Instant};
 
 pub struct LockTable {
     locks: std::collections::HashMap<String, RwLock<()>>,
+    storage: Storage,
+    cache: SharedPageCache,
+    /// Idle open tables, reused by later statements instead of opening the files again.
+    handles: Mutex<HashMap<String, Vec<BTree>>>,
 }
 
+/// Number of idle handles kept open per table, extra handles are closed when returned.
+const MAX_IDLE_HANDLES: usize = 8;
+
+/// Guard for a single table lock, the lock is released when it is dropped once the statement
+/// has finished.
+pub enum TableGuard<'a> {
+    Read { _guard: RwLockReadGuard<'a, ()> },
+    Write { _guard: RwLockWriteGuard<'a, ()> },
+}
+
+/// Wait up to `timeout` for a read lock, returning `Error::Busy` when it could not be taken in time.
+pub fn read_with_timeout<T>(
+    lock: &RwLock<T>,
+    timeout: Duration,
+) -> Result<RwLockReadGuard<'_, T>, Error> {
+    let started = Instant::now();
+
+    loop {
+        match lock.try_read() {
+            Ok(guard) => return Ok(guard),
+            Err(TryLockError::Poisoned(err)) => return Err(Error::Lock(err.to_string())),
+            Err(TryLockError::WouldBlock) => wait_for_lock(started, timeout)?,
+        }
+    }
+}
+
+/// Wait up to `timeout` for a write lock, returning `Error::Busy` when it could not be taken in time.
+pub fn write_with_timeout<T>(
+    lock: &RwLock<T>,
+    timeout: Duration,
+) -> Result<RwLockWriteGuard<'_, T>, Error> {
+    let started = Instant::now();
+
+    loop {
+        match lock.try_write() {
+            Ok(guard) => return Ok(guard),
+            Err(TryLockError::Poisoned(err)) => return Err(Error::Lock(err.to_string())),
+            Err(TryLockError::WouldBlock) => wait_for_lock(started, timeout)?,
+        }
+    }
+}
+
+fn wait_for_lock(started: Instant, timeout: Duration) -> Result<(), Error> {
+    let elapsed = started.elapsed();
+    if elapsed >= timeout {
+        return Err(Error::Busy(format!(
+            "Timed out after {}ms waiting for a lock.",
+            timeout.as_millis()
+        )));
+    }
+
+    thread::sleep(LOCK_RETRY_INTERVAL.min(timeout - elapsed));
+    Ok(())
+}
+
+const LOCK_RETRY_INTERVAL: Duration = Duration::from_millis(5);
+
 impl LockTable {
-    pub fn new(locks: HashMap<String, RwLock<()>>) -> Self {
-        Self { locks }
+    /// Ephemeral database kept in memory, for tests of code using the database.
+    #[allow(dead_code)]
+    pub fn in_memory(cache_size: usize) -> Self {
+        Self::new(
+            HashMap::new(),
+            Storage::memory(),
+            PageCache::shared(cache_size),
+        )
+    }
+
+    pub fn new(
+        locks: HashMap<String, RwLock<()>>,
+        storage: Storage,
+        cache: SharedPageCache,
+    ) -> Self {
+        Self {
+            locks,
+            storage,
+            cache,
+            handles: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Get an open handle for the table, reusing an idle one when there is one.
+    /// Hand it back with `return_handle` once the statement succeeded, a handle
+    /// that is dropped instead is simply closed.
+    pub fn take_handle(&self, table: &String, session: &Session) -> Result<BTree, Error> {
+        let mut db = match self.take_idle(table)? {
+            Some(db) => db,
+            None => self.builder(table, self.get_path(table)?).build()?,
+        };
+
+        db.set_synchronous(session.synchronous);
+
+        Ok(db)
+    }
+
+    fn take_idle(&self, key: &String) -> Result<Option<BTree>, Error> {
+        Ok(self
+            .handles
+            .lock()
+            .map_err(|e| Error::Lock(e.to_string()))?
+            .get_mut(key)
+            .and_then(|x| x.pop()))
+    }
+
+    /// Like `take_handle`, for an index of `table`.
+    fn take_index_handle(
+        &self,
+        table: &String,
+        index: &String,
+        session: &Session,
+    ) -> Result<BTree, Error> {
+        let mut db = match self.take_idle(&index_handle_key(table, index))? {
+            Some(db) => db,
+            None => self.index_builder(table, index).build()?,
+        };
+
+        db.set_synchronous(session.synchronous);
+
+        Ok(db)
+    }
+
+    pub fn return_handle(&self, table: &String, db: BTree) -> Result<(), Error> {
+        let mut handles = self
+            .handles
+            .lock()
+            .map_err(|e| Error::Lock(e.to_string()))?;
+
+        let idle = handles.entry(table.to_owned()).or_default();
+        if idle.len() < MAX_IDLE_HANDLES {
+            idle.push(db);
+        }
+
+        Ok(())
+    }
+
+    /// Close every idle handle of a table and its indexes.
+    fn close_handles(&self, table: &String) -> Result<(), Error> {
+        let prefix = index_handle_key(table, &String::new());
+
+        self.handles
+            .lock()
+            .map_err(|e| Error::Lock(e.to_string()))?
+            .retain(|key, _| key != table && !key.starts_with(&prefix));
+
+        Ok(())
+    }
+
+    /// Builder for a handle on `table`, stored at `path`.
+    fn builder(&self, table: &String, path: PathBuf) -> BTreeBuilder {
+        let builder = BTreeBuilder::new()
+            .b_parameter(10)
+            .cursor_offset(256)
+            .cache(self.cache())
+            .vfs(self.storage.vfs.clone())
+            .path(path);
+
+        match self.storage.layout {
+            Layout::SingleFile(_) => builder.table(table.to_owned()),
+            Layout::Directory(_) => builder,
+        }
+    }
+
+    /// Builder for a handle on `index` of `table`.
+    fn index_builder(&self, table: &String, index: &String) -> BTreeBuilder {
+        self.builder(index, self.storage.index_path(table, index))
+            .index(table.to_owned())
+    }
+
+    pub fn storage(&self) -> &Storage {
+        &self.storage
+    }
+
+    /// Page cache shared by every table.
+    pub fn cache(&self) -> SharedPageCache {
+        self.cache.clone()
     }
 
-    pub fn get_lock(&self, table: &String) -> Result<(&RwLock<()>, PathBuf), Error> {
-        let table_name = table.to_lowercase().replace(" ", "_");
-        let table_path = PathBuf::from(format!("./db/{}/table", table_name));
+    /// Cache statistics as rows of (name, value), returned by `PRAGMA cache_stats;`.
+    pub fn cache_stats(&self) -> Result<Vec<Record>, Error> {
+        let stats = lock_cache(&self.cache)?.stats();
 
-        if !table_path.exists() {
+        Ok([
+            ("hits", stats.hits),
+            ("misses", stats.misses),
+            ("evictions", stats.evictions),
+            ("write_backs", stats.write_backs),
+            ("pages", stats.pages as u64),
+            ("capacity", stats.capacity as u64),
+        ]
+        .into_iter()
+        .map(|(name, value)| Record(vec![Value::String(name.into()), Value::U64(value)]))
+        .collect())
+    }
+
+    pub fn get_path(&self, table: &String) -> Result<PathBuf, Error> {
+        let table_path = self.storage.table_path(table);
+
+        if !self.storage.exists(&table_path) {
             return Err(Error::Unexpexted("Table does not exists"));
         }
 
-        match self.locks.get(table) {
-            Some(lock) => Ok((lock, table_path)),
-            None => Err(Error::Unexpexted("No table was found.")),
+        match self.locks.contains_key(table) {
+            true => Ok(table_path),
+            false => Err(Error::Unexpexted("No table was found.")),
         }
     }
 
-    pub fn add_lock(&mut self, table: String) -> Result<(&RwLock<()>, PathBuf), Error> {
-        let table_name = table.to_lowercase().replace(" ", "_");
-        let table_path = PathBuf::from(format!("./db/{}/table", table_name));
+    /// Lock every table a statement touches.
+    /// Tables are always locked in name order, so two statements can never end up
+    /// waiting on each other. A table listed more then once is locked once, using the strongest mode.
+    pub fn lock_tables(
+        &self,
+        tables: &[(&String, LockMode)],
+        timeout: Duration,
+    ) -> Result<Vec<TableGuard<'_>>, Error> {
+        let mut ordered = tables.to_vec();
+        ordered.sort_by(|a, b| a.0.cmp(b.0));
+        ordered.dedup_by(|next, prev| {
+            if next.0 != prev.0 {
+                return false;
+            }
+            if next.1 == LockMode::Exclusive {
+                prev.1 = LockMode::Exclusive;
+            }
+            true
+        });
 
-        let key_exists = self.locks.contains_key(&table_name);
-        let file_exists = table_path.exists();
+        let mut guards = vec![];
+        for (table, mode) in ordered {
+            let lock = self
+                .locks
+                .get(table)
+                .ok_or(Error::Unexpexted("No table was found."))?;
 
-        if !key_exists {
-            self.locks.insert(table.clone(), RwLock::new(()));
+            guards.push(match mode {
+                LockMode::Shared => TableGuard::Read {
+                    _guard: read_with_timeout(lock, timeout)?,
+                },
+                LockMode::Exclusive => TableGuard::Write {
+                    _guard: write_with_timeout(lock, timeout)?,
+                },
+            });
         }
 
-        if !file_exists {
-            fs::create_dir_all(
-                &table_path
-                    .parent()
-                    .ok_or_else(|| Error::Unexpexted("Failed to get parent path."))?,
-            )?;
+        Ok(guards)
+    }
+
+    /// Rows of the catalog of a single file database, returned by `SELECT ... FROM sqlite_master;`.
+    pub fn catalog(
+        &self,
+        columns: &Vec<ColumnExpr>,
+        target: &Option<Vec<Condition>>,
+        timeout: Duration,
+    ) -> Result<Vec<Record>, Error> {
+        let path = match &self.storage.layout {
+            Layout::SingleFile(path) => path,
+            Layout::Directory(_) => return Err(Error::Unexpexted("No table was found.")),
+        };
+
+        // no table was created yet.
+        if !self.storage.exists(path) {
+            return Ok(vec![]);
         }
 
-        match self.locks.get(&table_name) {
-            Some(lock) => Ok((lock, table_path)),
-            None => Err(Error::Unexpexted("Table does not exists.")),
+        let mut file = self.storage.vfs.open(path)?;
+
+        file.lock(LockMode::Shared, timeout)?;
+        let catalog = Catalog::read(file.as_mut());
+        file.unlock()?;
+
+        Ok(catalog?.select(columns, target)?)
+    }
+
+    /// Names of every table, in name order.
+    pub fn table_names(&self) -> Vec<String> {
+        let mut tables: Vec<String> = self.locks.keys().cloned().collect();
+        tables.sort();
+        tables
+    }
+
+    /// Schema of every table with its statistics, if it was analyzed, in name order.
+    pub fn schemas(&self, session: &Session) -> Result<Vec<(Schema, Option<TableStats>)>, Error> {
+        let mut schemas = vec![];
+        for table in self.table_names() {
+            let mut db = self.take_handle(&table, session)?;
+
+            schemas.push(with_file_lock(
+                &mut db,
+                LockMode::Shared,
+                session.busy_timeout,
+                |db| Ok((db.get_table()?, db.get_stats()?)),
+            )?);
+
+            self.return_handle(&table, db)?;
         }
+
+        Ok(schemas)
     }
 
-    pub fn remove_lock(&mut self, table: String) -> Result<(), Error> {
-        let table_name = table.to_lowercase().replace(" ", "_");
-        let table_path = PathBuf::from(format!("./db/{}/table", table_name));
+    /// Run `action` on the table and its indexes while holding the file locks on all
+    /// of them. Like `with_file_lock`, the handles are only reused when the action succeeded
+    /// and the pages of a failed action are thrown away.
+    pub fn with_table<T>(
+        &self,
+        table: &String,
+        mode: LockMode,
+        session: &Session,
+        action: impl FnOnce(&mut Table) -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        let mut tree = self.take_handle(table, session)?;
+        tree.lock(mode, session.busy_timeout)?;
+
+        // the indexes are looked up under the lock, so none can be added meanwhile.
+        let indexes = match self.open_indexes(table, &mut tree, mode, session) {
+            Ok(indexes) => indexes,
+            Err(err) => {
+                tree.unlock()?;
+                return Err(err);
+            }
+        };
+
+        let mut handle = Table::new(tree, indexes);
+        let result = action(&mut handle);
+        let (mut tree, indexes) = handle.into_parts();
+
+        let mut trees = vec![];
+        for mut index in indexes {
+            match result {
+                Ok(_) => index.tree.unlock()?,
+                Err(_) => index.tree.abort()?,
+            }
+            trees.push((index_handle_key(table, &index.name), index.tree));
+        }
+        match result {
+            Ok(_) => tree.unlock()?,
+            Err(_) => tree.abort()?,
+        }
+
+        let value = result?;
+
+        self.return_handle(table, tree)?;
+        for (key, tree) in trees {
+            self.return_handle(&key, tree)?;
+        }
+
+        Ok(value)
+    }
+
+    /// Open and lock every index of `table`, whose tree is already locked.
+    fn open_indexes(
+        &self,
+        table: &String,
+        tree: &mut BTree,
+        mode: LockMode,
+        session: &Session,
+    ) -> Result<Vec<Index>, Error> {
+        let names = self.storage.index_names(table)?;
+        if names.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let schema = tree.get_table()?;
+
+        let mut indexes: Vec<Index> = vec![];
+        for name in names {
+            let mut index_tree = self.take_index_handle(table, &name, session)?;
+
+            let opened = index_tree
+                .lock(mode, session.busy_timeout)
+                .and_then(|_| Index::open(index_tree, &schema));
+
+            match opened {
+                Ok(index) => indexes.push(index),
+                Err(err) => {
+                    for index in indexes.iter_mut() {
+                        index.tree.unlock()?;
+                    }
+                    return Err(err.into());
+                }
+            }
+        }
+
+        Ok(indexes)
+    }
+
+    /// Table the index named `name` belongs to.
+    pub fn index_owner(&self, name: &String) -> Result<Option<String>, Error> {
+        for table in self.table_names() {
+            if self.storage.index_names(&table)?.contains(name) {
+                return Ok(Some(table));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Create the index `name` on `columns` of `table` and fill it with the rows of the table.
+    pub fn create_index(
+        &self,
+        table: &String,
+        name: &String,
+        columns: &[String],
+        unique: bool,
+        session: &Session,
+    ) -> Result<(), Error> {
+        if self.locks.contains_key(name) || self.index_owner(name)?.is_some() {
+            return Err(EngineError::Validate(format!(
+                "There is already a table or index named '{}'.",
+                name
+            ))
+            .into());
+        }
+
+        let path = self.storage.index_path(table, name);
+
+        self.with_table(table, LockMode::Exclusive, session, |handle| {
+            self.storage.create_parent(&path)?;
+
+            let mut tree = self.index_builder(table, name).build()?;
+            tree.set_synchronous(session.synchronous);
+            tree.lock(LockMode::Exclusive, session.busy_timeout)?;
+
+            let result = handle.create_index(tree, name, columns, unique);
+
+            // do not leave the files of an index that could not be built behind.
+            if result.is_err() && !self.storage.is_single_file() {
+                self.storage.remove_table(&path)?;
+                lock_cache(&self.cache)?.invalidate(&path);
+            }
+
+            Ok(result?)
+        })?;
+
+        if session.synchronous == Synchronous::Full {
+            self.storage.sync_parents(&path)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn drop_index(
+        &self,
+        table: &String,
+        name: &String,
+        session: &Session,
+    ) -> Result<(), Error> {
+        let path = self.storage.index_path(table, name);
+        self.close_handles(&index_handle_key(table, name))?;
+
+        self.with_table(table, LockMode::Exclusive, session, |handle| {
+            let idx = handle
+                .indexes
+                .iter()
+                .position(|x| &x.name == name)
+                .ok_or(Error::Unexpexted("No index was found."))?;
+            let mut index = handle.indexes.remove(idx);
+
+            if self.storage.is_single_file() {
+                return Ok(index.tree.drop_table()?);
+            }
+
+            index.tree.unlock()?;
+            drop(index);
+
+            self.storage.remove_table(&path)?;
+            lock_cache(&self.cache)?.invalidate(&path);
+
+            if session.synchronous == Synchronous::Full {
+                self.storage.sync_parents(&path)?;
+            }
+
+            Ok(())
+        })
+    }
+
+    pub fn add_lock(&mut self, table: String) -> Result<PathBuf, Error> {
+        let table_path = self.storage.table_path(&table);
+
+        if !self.storage.exists(&table_path) {
+            self.storage.create_parent(&table_path)?;
+        }
+
+        self.locks.entry(table).or_insert_with(|| RwLock::new(()));
+
+        Ok(table_path)
+    }
+
+    pub fn remove_lock(&mut self, table_name: String, session: &Session) -> Result<(), Error> {
+        let table_path = self.storage.table_path(&table_name);
 
         let key_exists = self.locks.contains_key(&table_name);
-        let file_exists = table_path.exists();
+        let file_exists = self.storage.exists(&table_path);
+
+        if self.storage.is_single_file() {
+            if key_exists && file_exists {
+                let mut db = self.builder(&table_name, table_path).build()?;
+                db.set_synchronous(session.synchronous);
+
+                with_file_lock(&mut db, LockMode::Exclusive, session.busy_timeout, |db| {
+                    Ok(db.drop_table()?)
+                })?;
+            }
 
-        if key_exists {
             self.locks.remove(&table_name);
+            self.close_handles(&table_name)?;
+
+            return Ok(());
         }
 
         if file_exists {
-            fs::remove_dir_all(
-                table_path
-                    .parent()
-                    .ok_or_else(|| Error::Unexpexted("Failed to get path parent"))?,
-            )?;
+            // wait for other processes to finish with the table before removing it.
+            let mut db = self.builder(&table_name, table_path.clone()).build()?;
+            db.lock(LockMode::Exclusive, session.busy_timeout)?;
+            drop(db);
+
+            self.locks.remove(&table_name);
+            self.close_handles(&table_name)?;
+
+            let indexes = self.storage.index_names(&table_name)?;
+
+            self.storage.remove_table(&table_path)?;
+            lock_cache(&self.cache)?.invalidate(&table_path);
+            for index in indexes {
+                lock_cache(&self.cache)?.invalidate(&self.storage.index_path(&table_name, &index));
+            }
+
+            if session.synchronous == Synchronous::Full {
+                self.storage.sync_parents(&table_path)?;
+            }
+        } else {
+            self.locks.remove(&table_name);
+            self.close_handles(&table_name)?;
         }
 
         Ok(())
@@ -81,99 +571,143 @@ impl LockTable {
 
 pub type AccessLockTable = Arc<RwLock<LockTable>>;
 
+/// Idle handles of an index are kept under the name of its table and the index.
+fn index_handle_key(table: &String, index: &String) -> String {
+    format!("{}.{}", table, index)
+}
+
+/// Run `action` while holding the OS level lock on the table files,
+/// the lock is released even when the action fails, without writing its pages.
+pub fn with_file_lock<T>(
+    db: &mut BTree,
+    mode: LockMode,
+    timeout: Duration,
+    action: impl FnOnce(&mut BTree) -> Result<T, Error>,
+) -> Result<T, Error> {
+    db.lock(mode, timeout)?;
+
+    let result = action(db);
+
+    match result {
+        Ok(_) => db.unlock()?,
+        Err(_) => db.abort()?,
+    }
+
+    result
+}
+
 pub fn execute_statement(
     statement: &Statement,
     lock_table: AccessLockTable,
+    session: &mut Session,
 ) -> Result<Option<Vec<Record>>, Error> {
+    let busy_timeout = session.busy_timeout;
+
     match statement {
         Statement::Insert { cols, data, table } => {
-            let table_lock = lock_table.read().map_err(|e| Error::Lock(e.to_string()))?;
-
-            let (lock, table_path) = table_lock.get_lock(table)?;
+            let table_lock = read_with_timeout(&lock_table, busy_timeout)?;
 
-            let mut db = BTreeBuilder::new()
-                .b_parameter(10)
-                .cursor_offset(256)
-                .path(PathBuf::from(table_path))
-                .build()?;
+            let _guards = table_lock.lock_tables(&[(table, LockMode::Exclusive)], busy_timeout)?;
 
-            if let Ok(_) = lock.write() {
-                let schema = db.get_table()?;
+            table_lock.with_table(table, LockMode::Exclusive, session, |db| {
+                let schema = db.tree.get_table()?;
 
-                let value = Record::create_from(cols, data, &schema)?;
+                let value = Record::create_from(cols, data, &schema, session.rounding)?;
 
                 db.insert(value)?;
 
-                return Ok(None);
-            }
+                Ok(())
+            })?;
 
-            Err(Error::Lock("Failed to lock.".into()))
+            Ok(None)
+        }
+        Statement::Select {
+            table,
+            columns,
+            target,
+        } if table == CATALOG_TABLE => {
+            let table_lock = read_with_timeout(&lock_table, busy_timeout)?;
+            Ok(Some(table_lock.catalog(columns, target, busy_timeout)?))
         }
         Statement::Select {
             table,
             columns,
             target,
         } => {
-            let table_lock = lock_table.read().map_err(|e| Error::Lock(e.to_string()))?;
+            let table_lock = read_with_timeout(&lock_table, busy_timeout)?;
 
-            let (lock, table_path) = table_lock.get_lock(table)?;
+            let _guards = table_lock.lock_tables(&[(table, LockMode::Shared)], busy_timeout)?;
 
-            let mut db = BTreeBuilder::new()
-                .b_parameter(10)
-                .cursor_offset(256)
-                .path(PathBuf::from(table_path))
-                .build()?;
+            let results = table_lock.with_table(table, LockMode::Shared, session, |db| {
+                Ok(db.select(columns, target)?)
+            })?;
 
-            if let Ok(_) = lock.read() {
-                let results = db.select(columns, target, None)?;
-                return Ok(Some(results));
-            }
-
-            Ok(None)
+            Ok(Some(results))
         }
         Statement::Create {
             table,
             cols,
             primary_key,
         } => {
-            if let Ok(mut handler) = lock_table.write() {
-                if let Ok((lock, table_path)) = handler.add_lock(table.to_string()) {
-                    let mut db = BTreeBuilder::new()
-                        .b_parameter(10)
-                        .path(table_path)
+            let mut handler = write_with_timeout(&lock_table, busy_timeout)?;
+
+            if handler.index_owner(table)?.is_some() {
+                return Err(EngineError::Validate(format!(
+                    "There is already an index named '{}'.",
+                    table
+                ))
+                .into());
+            }
+
+            let existed = handler.table_names().contains(table);
+            let table_path = handler.add_lock(table.to_string())?;
+
+            let created = handler
+                .lock_tables(&[(table, LockMode::Exclusive)], busy_timeout)
+                .and_then(|_guards| {
+                    let mut db = handler
+                        .builder(table, table_path.clone())
+                        .synchronous(session.synchronous)
                         .build()?;
 
-                    if let Ok(_) = lock.write() {
-                        let schema = Schema::new(
-                            table.to_owned(),
-                            primary_key.to_owned(),
-                            cols.to_owned(),
-                            None,
-                        );
+                    let schema = Schema::new(table.to_owned(), 0, cols.to_owned(), None)
+                        .with_primary_key(primary_key.to_owned());
+
+                    with_file_lock(&mut db, LockMode::Exclusive, busy_timeout, |db| {
+                        Ok(db.create_table(schema)?)
+                    })?;
 
-                        db.create_table(schema)?;
+                    Ok(db)
+                });
 
-                        return Ok(None);
+            // a table that could not be created, like when the catalog is full, is not left behind.
+            let db = match created {
+                Ok(db) => db,
+                Err(err) => {
+                    if !existed {
+                        handler.locks.remove(table);
                     }
+                    return Err(err);
                 }
+            };
+
+            handler.return_handle(table, db)?;
+
+            // make sure the new table directory and its files are not lost on power loss.
+            if session.synchronous == Synchronous::Full {
+                handler.storage().sync_parents(&table_path)?;
             }
 
-            Err(Error::Unexpexted("Failed to create table."))
+            Ok(None)
         }
         Statement::Delete { table, target } => {
-            let table_lock = lock_table.read().map_err(|e| Error::Lock(e.to_string()))?;
-
-            let (lock, table_path) = table_lock.get_lock(table)?;
+            let table_lock = read_with_timeout(&lock_table, busy_timeout)?;
 
-            let mut db = BTreeBuilder::new()
-                .b_parameter(10)
-                .cursor_offset(256)
-                .path(PathBuf::from(table_path))
-                .build()?;
+            let _guards = table_lock.lock_tables(&[(table, LockMode::Exclusive)], busy_timeout)?;
 
-            if let Ok(_) = lock.write() {
-                db.delete(Some(target))?;
-            }
+            table_lock.with_table(table, LockMode::Exclusive, session, |db| {
+                Ok(db.delete(target)?)
+            })?;
 
             Ok(None)
         }
@@ -182,30 +716,1122 @@ pub fn execute_statement(
             columns,
             target,
         } => {
-            let table_lock = lock_table.read().map_err(|e| Error::Lock(e.to_string()))?;
+            let table_lock = read_with_timeout(&lock_table, busy_timeout)?;
+
+            let _guards = table_lock.lock_tables(&[(table, LockMode::Exclusive)], busy_timeout)?;
+
+            table_lock.with_table(table, LockMode::Exclusive, session, |db| {
+                Ok(db.update(columns, target, session.rounding)?)
+            })?;
+
+            Ok(None)
+        }
+        Statement::DropTable { table } => {
+            let mut handler = write_with_timeout(&lock_table, busy_timeout)?;
+
+            handler.remove_lock(table.to_owned(), session)?;
+            info!("Dropped Table '{}'", table);
+
+            Ok(None)
+        }
+        Statement::CreateIndex {
+            name,
+            table,
+            columns,
+            unique,
+        } => {
+            let table_lock = read_with_timeout(&lock_table, busy_timeout)?;
+
+            let _guards = table_lock.lock_tables(&[(table, LockMode::Exclusive)], busy_timeout)?;
+
+            table_lock.create_index(table, name, columns, *unique, session)?;
+            info!("Created Index '{}' on '{}'", name, table);
+
+            Ok(None)
+        }
+        Statement::DropIndex { name } => {
+            let table_lock = read_with_timeout(&lock_table, busy_timeout)?;
+
+            let table = table_lock
+                .index_owner(name)?
+                .ok_or(Error::Unexpexted("No index was found."))?;
+
+            let _guards = table_lock.lock_tables(&[(&table, LockMode::Exclusive)], busy_timeout)?;
+
+            table_lock.drop_index(&table, name, session)?;
+            info!("Dropped Index '{}'", name);
+
+            Ok(None)
+        }
+        Statement::Pragma { name, value } if name == "cache_stats" && value.is_none() => {
+            let table_lock = read_with_timeout(&lock_table, busy_timeout)?;
+            Ok(Some(table_lock.cache_stats()?))
+        }
+        Statement::Pragma { name, value } => session.pragma(name, value),
+        Statement::Analyze { table } => {
+            let table_lock = read_with_timeout(&lock_table, busy_timeout)?;
 
-            let (lock, table_path) = table_lock.get_lock(table)?;
+            let tables = match table {
+                Some(table) => vec![table.to_owned()],
+                None => table_lock.table_names(),
+            };
 
-            let mut db = BTreeBuilder::new()
-                .b_parameter(10)
-                .cursor_offset(256)
-                .path(PathBuf::from(table_path))
-                .build()?;
+            for table in tables {
+                let _guards =
+                    table_lock.lock_tables(&[(&table, LockMode::Exclusive)], busy_timeout)?;
 
-            if let Ok(_) = lock.write() {
-                db.update(columns, target)?;
+                let rows = table_lock.with_table(&table, LockMode::Exclusive, session, |db| {
+                    let stats = db.tree.analyze()?;
+                    db.tree.set_stats(&stats)?;
+                    Ok(stats.rows)
+                })?;
+
+                info!("Analyzed table '{}' ({} rows)", table, rows);
             }
 
             Ok(None)
         }
-        Statement::DropTable { table } => {
-            if let Ok(mut lock) = lock_table.write() {
-                lock.remove_lock(table.to_owned())?;
-                info!("Dropped Table '{}'", table);
-                return Ok(None);
+        Statement::Explain(statement) => Ok(Some(explain(statement, lock_table, session)?)),
+    }
+}
+
+/// Run `statement` like `execute_statement`, but hand the rows it returns to `output` one
+/// at a time. The rows of a select are handed over while the table is read, so a large
+/// table is never collected in memory. The table stays locked until every row was handed over.
+pub fn stream_statement(
+    statement: &Statement,
+    lock_table: AccessLockTable,
+    session: &mut Session,
+    output: &mut dyn FnMut(Record) -> Result<(), Error>,
+) -> Result<(), Error> {
+    match statement {
+        Statement::Select {
+            table,
+            columns,
+            target,
+        } if table != CATALOG_TABLE => {
+            let busy_timeout = session.busy_timeout;
+            let table_lock = read_with_timeout(&lock_table, busy_timeout)?;
+
+            let _guards = table_lock.lock_tables(&[(table, LockMode::Shared)], busy_timeout)?;
+
+            table_lock.with_table(table, LockMode::Shared, session, |db| {
+                db.select_each(columns, target, &mut *output)
+            })
+        }
+        _ => {
+            if let Some(rows) = execute_statement(statement, lock_table, session)? {
+                for row in rows {
+                    output(row)?;
+                }
+            }
+
+            Ok(())
+        }
+    }
+}
+
+/// Rows of `EXPLAIN QUERY PLAN`, describing how `statement` would read its table.
+/// Statements that read no rows have no plan.
+fn explain(
+    statement: &Statement,
+    lock_table: AccessLockTable,
+    session: &Session,
+) -> Result<Vec<Record>, Error> {
+    let (table, target) = match statement {
+        Statement::Select { table, target, .. } | Statement::Update { table, target, .. } => {
+            (table, target.to_owned())
+        }
+        Statement::Delete { table, target } => (table, Some(target.to_owned())),
+        _ => return Ok(vec![]),
+    };
+
+    if table == CATALOG_TABLE {
+        return Ok(vec![Plan::scan(CATALOG_TABLE, 1.0).row(2, 0)]);
+    }
+
+    let busy_timeout = session.busy_timeout;
+    let table_lock = read_with_timeout(&lock_table, busy_timeout)?;
+
+    let _guards = table_lock.lock_tables(&[(table, LockMode::Shared)], busy_timeout)?;
+
+    let plan =
+        table_lock.with_table(table, LockMode::Shared, session, |db| Ok(db.plan(&target)?))?;
+
+    Ok(vec![plan.row(2, 0)])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        execute_statement, stream_statement, AccessLockTable, LockTable, TableGuard,
+        MAX_IDLE_HANDLES,
+    };
+    use crate::{
+        commands::{
+            args::Config,
+            prepare::prepare_statement,
+            session::Session,
+            storage::{Layout, Storage},
+        },
+        engine::{
+            file_lock::LockMode,
+            page_cache::PageCache,
+            structure::{Record, Value},
+        },
+        errors::Error,
+    };
+    use std::{
+        collections::HashMap,
+        sync::{Arc, RwLock},
+        time::Duration,
+    };
+
+    /// A database with one connection to it, in memory unless it is given a lock table.
+    struct TestDb {
+        lock_table: AccessLockTable,
+        session: Session,
+    }
+
+    impl TestDb {
+        fn new() -> Self {
+            Self::with_lock_table(LockTable::in_memory(16))
+        }
+
+        fn with_lock_table(lock_table: LockTable) -> Self {
+            Self {
+                lock_table: Arc::new(RwLock::new(lock_table)),
+                session: Session::from(&Config::default()),
+            }
+        }
+
+        fn table_names(&self) -> Vec<String> {
+            self.lock_table
+                .read()
+                .expect("Failed to lock")
+                .table_names()
+        }
+
+        fn try_run(&mut self, query: &str) -> Result<Option<Vec<Record>>, Error> {
+            let statement = prepare_statement(&query.to_string()).expect("Failed to prepare");
+            execute_statement(&statement, self.lock_table.clone(), &mut self.session)
+        }
+
+        fn run(&mut self, query: &str) -> Option<Vec<Record>> {
+            self.try_run(query)
+                .unwrap_or_else(|err| panic!("Failed to execute '{}': {}", query, err))
+        }
+
+        /// Rows of `query` as the JSON a client gets.
+        fn json(&mut self, query: &str) -> String {
+            serde_json::to_string(&self.run(query).unwrap_or_default())
+                .expect("Failed to serialize")
+        }
+
+        fn stream(
+            &mut self,
+            query: &str,
+            output: &mut dyn FnMut(Record) -> Result<(), Error>,
+        ) -> Result<(), Error> {
+            let statement = prepare_statement(&query.to_string()).expect("Failed to prepare");
+            stream_statement(
+                &statement,
+                self.lock_table.clone(),
+                &mut self.session,
+                output,
+            )
+        }
+    }
+
+    fn get_lock_table() -> LockTable {
+        let mut locks = HashMap::new();
+        locks.insert("users".to_string(), RwLock::new(()));
+        locks.insert("posts".to_string(), RwLock::new(()));
+        LockTable::new(locks, Storage::default(), PageCache::shared(16))
+    }
+
+    #[test]
+    fn test_lock_tables_in_order() {
+        let table = get_lock_table();
+        let (users, posts) = ("users".to_string(), "posts".to_string());
+
+        let guards = match table.lock_tables(
+            &[
+                (&users, LockMode::Shared),
+                (&posts, LockMode::Shared),
+                (&users, LockMode::Exclusive),
+            ],
+            Duration::ZERO,
+        ) {
+            Ok(value) => value,
+            Err(err) => panic!("{}", err),
+        };
+
+        assert_eq!(guards.len(), 2);
+        assert!(matches!(guards[0], TableGuard::Read { .. }));
+        assert!(matches!(guards[1], TableGuard::Write { .. }));
+    }
+
+    #[test]
+    fn test_lock_tables_timeout() {
+        let table = get_lock_table();
+        let users = "users".to_string();
+
+        let _guards = table
+            .lock_tables(&[(&users, LockMode::Shared)], Duration::ZERO)
+            .expect("Failed to lock table");
+
+        match table.lock_tables(&[(&users, LockMode::Exclusive)], Duration::from_millis(20)) {
+            Err(err) => assert!(matches!(err, Error::Busy(_))),
+            Ok(_) => panic!("Expected the table to be busy"),
+        };
+    }
+
+    #[test]
+    fn test_in_memory_database() {
+        let mut db = TestDb::new();
+
+        db.run("CREATE TABLE memory_users (id uint PRIMARY KEY, name string);");
+        db.run("INSERT INTO memory_users (id,name) VALUES (1,\"a\");");
+        db.run("INSERT INTO memory_users (id,name) VALUES (2,\"b\");");
+        db.run("UPDATE memory_users SET name = \"c\" WHERE id = 2;");
+
+        let rows = db.run("SELECT * FROM memory_users WHERE id = 2;");
+        assert_eq!(
+            rows,
+            Some(vec![Record(vec![
+                Value::UInt(2),
+                Value::String("c".into())
+            ])])
+        );
+
+        // nothing was written to disk.
+        assert!(!std::path::Path::new("./db/memory_users").exists());
+
+        db.run("DROP TABLE memory_users;");
+        assert!(db.try_run("SELECT * FROM memory_users;").is_err());
+    }
+
+    #[test]
+    fn test_handle_pool() {
+        let mut db = TestDb::new();
+        let idle = |db: &TestDb, key: &str| {
+            db.lock_table
+                .read()
+                .expect("Failed to lock")
+                .handles
+                .lock()
+                .expect("Failed to lock handles")
+                .get(key)
+                .map_or(0, Vec::len)
+        };
+
+        db.run("CREATE TABLE pooled (id uint PRIMARY KEY, name string);");
+        db.run("CREATE INDEX pooled_name ON pooled (name);");
+        db.run("INSERT INTO pooled (id,name) VALUES (1,\"a\");");
+        db.run("SELECT * FROM pooled;");
+        db.run("SELECT * FROM pooled;");
+
+        // statements run one after another share one handle.
+        assert_eq!(idle(&db, "pooled"), 1);
+        assert_eq!(idle(&db, "pooled.pooled_name"), 1);
+
+        {
+            let lock_table = db.lock_table.read().expect("Failed to lock");
+            let table = "pooled".to_string();
+            let handles = (0..MAX_IDLE_HANDLES + 2)
+                .map(|_| lock_table.take_handle(&table, &db.session))
+                .collect::<Result<Vec<_>, _>>()
+                .expect("Failed to open handles");
+            for handle in handles {
+                lock_table
+                    .return_handle(&table, handle)
+                    .expect("Failed to return handle");
             }
+        }
+        assert_eq!(idle(&db, "pooled"), MAX_IDLE_HANDLES);
 
-            Err(Error::Unexpexted("Failed to lock."))
+        db.run("DROP TABLE pooled;");
+        assert_eq!(idle(&db, "pooled"), 0);
+        assert_eq!(idle(&db, "pooled.pooled_name"), 0);
+    }
+
+    #[test]
+    fn test_split_tree() {
+        let mut db = TestDb::new();
+
+        // inserted in key order, so every insert goes into the last child of the root.
+        db.run("CREATE TABLE split (id uint PRIMARY KEY, name string);");
+        for id in 0..60 {
+            db.run(&format!(
+                "INSERT INTO split (id,name) VALUES ({},\"a\");",
+                id
+            ));
         }
+        assert_eq!(
+            db.json("SELECT (id) FROM split WHERE id >= 57;"),
+            "[[57],[58],[59]]"
+        );
+
+        // every leaf is written back in its own place, not over the root.
+        db.run("UPDATE split SET name = \"b\";");
+        let rows = db.run("SELECT * FROM split;").unwrap_or_default();
+        assert_eq!(rows.len(), 60);
+        assert!(rows
+            .iter()
+            .enumerate()
+            .all(|(id, row)| row.0 == vec![Value::UInt(id), Value::String("b".into())]));
+    }
+
+    #[test]
+    fn test_table_name_case() {
+        let mut db = TestDb::new();
+
+        db.run("CREATE TABLE Users (id uint PRIMARY KEY);");
+        db.run("INSERT INTO USERS VALUES (1);");
+        assert_eq!(db.json("SELECT * FROM users;"), "[[1]]");
+        assert_eq!(db.table_names(), vec!["users".to_string()]);
+
+        db.run("DROP TABLE uSeRs;");
+        assert!(db.table_names().is_empty());
+    }
+
+    #[test]
+    fn test_catalog_full() {
+        let storage = Storage {
+            layout: Layout::SingleFile("/memory.db".into()),
+            ..Storage::memory()
+        };
+        let mut db = TestDb::with_lock_table(LockTable::new(
+            HashMap::new(),
+            storage,
+            PageCache::shared(16),
+        ));
+
+        // long names fill the one page of the catalog after a few tables.
+        let name = |x: usize| format!("t{}_{}", x, "x".repeat(200));
+        let (full, err) = (0..100)
+            .find_map(|x| {
+                db.try_run(&format!("CREATE TABLE {} (id uint PRIMARY KEY);", name(x)))
+                    .err()
+                    .map(|err| (x, err))
+            })
+            .expect("The catalog never filled up");
+
+        assert!(err.to_string().contains("Catalog is full"));
+        assert!(db
+            .try_run(&format!("SELECT * FROM {};", name(full)))
+            .is_err());
+        assert_eq!(db.table_names().len(), full);
+
+        db.run(&format!("INSERT INTO {} VALUES (1);", name(0)));
+        assert_eq!(db.json(&format!("SELECT * FROM {};", name(0))), "[[1]]");
+    }
+
+    #[test]
+    fn test_indexes() {
+        let mut db = TestDb::new();
+
+        db.run("CREATE TABLE indexed_users (id uint PRIMARY KEY, name string, age uint);");
+        db.run("INSERT INTO indexed_users (id,name,age) VALUES (1,\"a\",30);");
+        db.run("INSERT INTO indexed_users (id,name,age) VALUES (2,\"b\",20);");
+        db.run("CREATE UNIQUE INDEX indexed_users_name ON indexed_users (name);");
+        db.run("CREATE INDEX indexed_users_age ON indexed_users (age);");
+        db.run("INSERT INTO indexed_users (id,name,age) VALUES (3,\"c\",20);");
+
+        assert!(db
+            .try_run("INSERT INTO indexed_users (id,name,age) VALUES (4,\"a\",10);")
+            .is_err());
+        assert!(db
+            .try_run("INSERT INTO indexed_users (id,name,age) VALUES (3,\"d\",10);")
+            .is_err());
+        assert!(db
+            .try_run("UPDATE indexed_users SET name = \"a\" WHERE id = 2;")
+            .is_err());
+
+        let row = |id: usize, name: &str, age: usize| {
+            Record(vec![
+                Value::UInt(id),
+                Value::String(name.into()),
+                Value::UInt(age),
+            ])
+        };
+
+        assert_eq!(
+            db.run("SELECT * FROM indexed_users WHERE age = 20;"),
+            Some(vec![row(2, "b", 20), row(3, "c", 20)])
+        );
+        assert_eq!(
+            db.run("SELECT * FROM indexed_users WHERE name = \"a\";"),
+            Some(vec![row(1, "a", 30)])
+        );
+
+        db.run("UPDATE indexed_users SET age = 40 WHERE name = \"b\";");
+        assert_eq!(
+            db.run("SELECT * FROM indexed_users WHERE age > 30;"),
+            Some(vec![row(2, "b", 40)])
+        );
+
+        db.run("DELETE FROM indexed_users WHERE age = 20;");
+        assert_eq!(
+            db.run("SELECT * FROM indexed_users WHERE age < 100;"),
+            Some(vec![row(1, "a", 30), row(2, "b", 40)])
+        );
+
+        let plan = |detail: &str| {
+            Some(vec![Record(vec![
+                Value::UInt(2),
+                Value::UInt(0),
+                Value::UInt(0),
+                Value::String(detail.into()),
+            ])])
+        };
+        assert_eq!(
+            db.run("EXPLAIN QUERY PLAN SELECT * FROM indexed_users WHERE name = \"a\";"),
+            plan("SEARCH indexed_users USING INDEX indexed_users_name (name=?)")
+        );
+        assert_eq!(
+            db.run("EXPLAIN QUERY PLAN DELETE FROM indexed_users WHERE id = 1;"),
+            plan("SCAN indexed_users")
+        );
+        assert_eq!(
+            db.run(
+                "EXPLAIN QUERY PLAN INSERT INTO indexed_users (id,name,age) VALUES (9,\"z\",1);"
+            ),
+            Some(vec![])
+        );
+
+        db.run("DROP INDEX indexed_users_name;");
+        db.run("INSERT INTO indexed_users (id,name,age) VALUES (5,\"a\",50);");
+        assert!(db.try_run("DROP INDEX indexed_users_name;").is_err());
+
+        db.run("DROP TABLE indexed_users;");
+    }
+
+    #[test]
+    fn test_composite_primary_key() {
+        let mut db = TestDb::new();
+
+        db.run("CREATE TABLE members (team uint, id uint, name string, PRIMARY KEY (team, id));");
+        db.run("INSERT INTO members (team,id,name) VALUES (2,1,\"c\");");
+        db.run("INSERT INTO members (team,id,name) VALUES (1,2,\"b\");");
+        db.run("INSERT INTO members (team,id,name) VALUES (1,1,\"a\");");
+
+        // only the pair has to be unique.
+        assert!(db
+            .try_run("INSERT INTO members (team,id,name) VALUES (1,2,\"d\");")
+            .is_err());
+        assert!(db
+            .try_run("INSERT INTO members (id,name) VALUES (3,\"d\");")
+            .is_err());
+
+        let row = |team: usize, id: usize, name: &str| {
+            Record(vec![
+                Value::UInt(team),
+                Value::UInt(id),
+                Value::String(name.into()),
+            ])
+        };
+
+        // rows are kept in key order.
+        assert_eq!(
+            db.run("SELECT * FROM members;"),
+            Some(vec![row(1, 1, "a"), row(1, 2, "b"), row(2, 1, "c")])
+        );
+
+        db.run("CREATE INDEX members_name ON members (name);");
+        assert!(db
+            .try_run("UPDATE members SET team = 1 WHERE name = \"c\";")
+            .is_err());
+
+        db.run("UPDATE members SET team = 3 WHERE name = \"c\";");
+        assert_eq!(
+            db.run("SELECT * FROM members WHERE name = \"c\";"),
+            Some(vec![row(3, 1, "c")])
+        );
+
+        db.run("DELETE FROM members WHERE team = 1 AND id = 2;");
+        assert_eq!(
+            db.run("SELECT * FROM members;"),
+            Some(vec![row(1, 1, "a"), row(3, 1, "c")])
+        );
+    }
+
+    #[test]
+    fn test_decimal() {
+        let mut db = TestDb::new();
+
+        db.run("CREATE TABLE accounts (id uint PRIMARY KEY, balance decimal(10, 2));");
+        db.run("INSERT INTO accounts VALUES (1,100.005);");
+        db.run("PRAGMA rounding = half_even;");
+        db.run("INSERT INTO accounts VALUES (2,0.125);");
+        db.run("INSERT INTO accounts VALUES (3,99999999.99);");
+        assert!(db
+            .try_run("INSERT INTO accounts VALUES (4,100000000);")
+            .is_err());
+        assert!(db
+            .try_run("INSERT INTO accounts VALUES (4,1.5e400);")
+            .is_err());
+        assert_eq!(
+            db.json("SELECT * FROM accounts;"),
+            r#"[[1,"100.01"],[2,"0.12"],[3,"99999999.99"]]"#
+        );
+
+        // 0.1 is exact, three times it is 0.30 and not 0.30000000000000004.
+        db.run("UPDATE accounts SET balance = balance + 0.1 WHERE id < 3;");
+        db.run("UPDATE accounts SET balance = balance + 0.1 WHERE id < 3;");
+        db.run("UPDATE accounts SET balance = balance-0.1 WHERE id = 2;");
+        db.run("UPDATE accounts SET balance = balance / 3 WHERE id = 1;");
+        assert!(db
+            .try_run("UPDATE accounts SET balance = balance * 10 WHERE id = 3;")
+            .is_err());
+        assert!(db
+            .try_run("UPDATE accounts SET balance = balance / 0 WHERE id = 3;")
+            .is_err());
+        assert_eq!(
+            db.json("SELECT * FROM accounts;"),
+            r#"[[1,"33.40"],[2,"0.22"],[3,"99999999.99"]]"#
+        );
+        assert_eq!(
+            db.json("SELECT (id) FROM accounts WHERE balance = 0.220;"),
+            "[[2]]"
+        );
+        assert_eq!(
+            db.json("SELECT (id) FROM accounts WHERE balance = 0.225;"),
+            "[]"
+        );
+
+        db.run("CREATE TABLE prices (price decimal(6, 3) PRIMARY KEY, name string);");
+        for price in ["10", "-1.5", "0.25", "-0.001", "2"] {
+            db.run(&format!("INSERT INTO prices VALUES ({},\"p\");", price));
+        }
+        assert!(db
+            .try_run("INSERT INTO prices VALUES (2.000,\"p\");")
+            .is_err());
+        assert_eq!(
+            db.json("SELECT (price) FROM prices WHERE price > -1;"),
+            r#"[["-0.001"],["0.250"],["2.000"],["10.000"]]"#
+        );
+    }
+
+    #[test]
+    fn test_json() {
+        let mut db = TestDb::new();
+
+        db.run("CREATE TABLE users (id uint PRIMARY KEY, attrs json);");
+        db.run(
+            r#"INSERT INTO users VALUES (1,'{ "name": "Ada", "tags": ["a", "b"], "age": 36 }');"#,
+        );
+        db.run(r#"INSERT INTO users VALUES (2,'{"name": "Bob", "tags": []}');"#);
+        db.run("INSERT INTO users VALUES (3,null);");
+        assert!(db
+            .try_run("INSERT INTO users VALUES (4,'{\"name\":');")
+            .is_err());
+
+        // documents are sent as JSON, not as strings holding it.
+        assert_eq!(
+            db.json("SELECT * FROM users WHERE id = 2;"),
+            r#"[[2,{"name":"Bob","tags":[]}]]"#
+        );
+        assert_eq!(
+            db.json(
+                "SELECT (id, json_extract(attrs, '$.name'), json_array_length(attrs, '$.tags')) FROM users;"
+            ),
+            r#"[[1,"Ada",2],[2,"Bob",0],[3,null,null]]"#
+        );
+        assert_eq!(
+            db.json(
+                "SELECT (id) FROM users WHERE json_extract(attrs, '$.age') BETWEEN 30 AND 50 OR json_extract(attrs, '$.name') = 'Bob';"
+            ),
+            "[[1],[2]]"
+        );
+        assert!(db
+            .try_run("SELECT (id) FROM users WHERE json_extract(id, '$.a') = 1;")
+            .is_err());
+        assert!(db
+            .try_run("SELECT (id) FROM users WHERE upper(attrs) = 1;")
+            .is_err());
+
+        db.run(
+            "UPDATE users SET attrs = json_set(attrs, '$.age', 41, '$.tags[#]', 'c') WHERE id = 2;",
+        );
+        assert_eq!(
+            db.json("SELECT (json_extract(attrs, '$.age', '$.tags')) FROM users WHERE id = 2;"),
+            r#"[[[41,["c"]]]]"#
+        );
+    }
+
+    #[test]
+    fn test_uuid() {
+        let mut db = TestDb::new();
+
+        db.run("CREATE TABLE users (id uuid PRIMARY KEY DEFAULT uuid_v7(), name string, created timestamp DEFAULT now());");
+
+        // every row gets its own id, and v7 ids keep the rows in the order they were added.
+        let names: Vec<String> = (0..25).map(|x| format!("user {}", x)).collect();
+        for name in &names {
+            db.run(&format!("INSERT INTO users (name) VALUES (\"{}\");", name));
+        }
+        let rows = db.run("SELECT * FROM users;").unwrap_or_default();
+        assert_eq!(
+            rows.iter()
+                .map(|x| x.0[1].to_string())
+                .collect::<Vec<String>>(),
+            names
+        );
+        assert!(rows.iter().all(|x| x.0[2] != Value::Null));
+
+        db.run("INSERT INTO users (id, name) VALUES (\"00000000-0000-4000-8000-00000000000A\",\"first\");");
+        assert!(db
+            .try_run("INSERT INTO users (id, name) VALUES (\"0000-0000\",\"bad\");")
+            .is_err());
+        assert_eq!(
+            db.json(
+                "SELECT (id, name) FROM users WHERE id = \"0000000000004000800000000000000a\";"
+            ),
+            r#"[["00000000-0000-4000-8000-00000000000a","first"]]"#
+        );
+        assert_eq!(
+            db.json(
+                "SELECT (name) FROM users WHERE id < \"00000000-0000-4000-8000-00000000000b\";"
+            ),
+            r#"[["first"]]"#
+        );
+    }
+
+    #[test]
+    fn test_cast() {
+        let mut db = TestDb::new();
+
+        db.run("CREATE TABLE items (id uint PRIMARY KEY, code string, qty int);");
+
+        // a number in a string column is kept as its text and a whole number as text is taken
+        // by an integer column, a fraction is not.
+        db.run("INSERT INTO items VALUES (\"1\", 12, \"3.0\");");
+        db.run("INSERT INTO items VALUES (2, \"7b\", CAST(\"5 boxes\" AS int));");
+        assert!(db
+            .try_run("INSERT INTO items VALUES (3, \"x\", \"2.5\");")
+            .is_err());
+        assert!(db
+            .try_run("INSERT INTO items VALUES (3, \"x\", \"two\");")
+            .is_err());
+        assert_eq!(
+            db.json("SELECT * FROM items;"),
+            r#"[[1,"12",3],[2,"7b",5]]"#
+        );
+
+        // text that is not a number is never equal to, larger or smaller than one.
+        assert_eq!(db.json("SELECT (id) FROM items WHERE qty < \"abc\";"), "[]");
+        assert_eq!(db.json("SELECT (id) FROM items WHERE qty = 3.0;"), "[[1]]");
+        assert_eq!(
+            db.json("SELECT (id, CAST(code AS int)) FROM items WHERE CAST(code AS int) > 8;"),
+            "[[1,12]]"
+        );
+        assert_eq!(
+            db.json("SELECT (CAST(qty AS string)) FROM items WHERE CAST(qty AS string) = \"5\";"),
+            r#"[["5"]]"#
+        );
+
+        db.run("UPDATE items SET code = CAST(qty AS string) WHERE id = 2;");
+        assert_eq!(
+            db.json("SELECT (code) FROM items WHERE id = 2;"),
+            r#"[["5"]]"#
+        );
+    }
+
+    #[test]
+    fn test_timestamp() {
+        let mut db = TestDb::new();
+
+        let strings = |rows: Option<Vec<Record>>| {
+            rows.unwrap_or_default()
+                .into_iter()
+                .map(|x| x.0.iter().map(|x| x.to_string()).collect::<Vec<String>>())
+                .collect::<Vec<Vec<String>>>()
+        };
+
+        db.run("CREATE TABLE logins (at timestamp PRIMARY KEY, day date, start time);");
+        db.run(
+            "INSERT INTO logins VALUES (\"2024-01-31T15:45:00+02:00\",\"2024-01-31\",\"13:45\");",
+        );
+        // seconds since 1970, like the u64 columns they replace.
+        db.run("INSERT INTO logins VALUES (1704067200,\"2024-01-01\",\"00:00:00.25\");");
+        db.run("INSERT INTO logins VALUES (datetime(\"2024-01-31 13:45\", \"+1 month\"),date(\"2024-01-31\", \"+1 month\"),time(\"2024-02-29 08:00:00\"));");
+        assert!(db
+            .try_run("INSERT INTO logins VALUES (\"2024-02-30 10:00\",\"2024-02-30\",\"10:00\");")
+            .is_err());
+        assert!(db
+            .try_run("INSERT INTO logins VALUES (\"2024-02-20 10:00\",\"yesterday\",\"10:00\");")
+            .is_err());
+
+        assert_eq!(
+            strings(db.run("SELECT * FROM logins;")),
+            vec![
+                vec!["2024-01-01T00:00:00Z", "2024-01-01", "00:00:00.25"],
+                vec!["2024-01-31T13:45:00Z", "2024-01-31", "13:45:00"],
+                vec!["2024-02-29T13:45:00Z", "2024-02-29", "08:00:00"],
+            ]
+        );
+        assert_eq!(
+            strings(db.run("SELECT (day) FROM logins WHERE at BETWEEN \"2024-01-15\" AND now();")),
+            vec![vec!["2024-01-31"], vec!["2024-02-29"]]
+        );
+        assert_eq!(
+            strings(db.run(
+                "SELECT (day) FROM logins WHERE start < \"12:00\" AND day >= \"2024-02-01\";"
+            )),
+            vec![vec!["2024-02-29"]]
+        );
+    }
+
+    #[test]
+    fn test_blob() {
+        let mut db = TestDb::new();
+
+        let hex = |blob: &[u8]| {
+            blob.iter()
+                .map(|x| format!("{:02x}", x))
+                .collect::<String>()
+        };
+        let blob = |id: usize| (0..500).map(|x| (x * id % 256) as u8).collect::<Vec<u8>>();
+
+        db.run("CREATE TABLE files (id uint PRIMARY KEY, data blob);");
+        // far more than fits in a leaf without overflow pages.
+        for id in 0..30 {
+            db.run(&format!(
+                "INSERT INTO files (id,data) VALUES ({},X'{}');",
+                id,
+                hex(&blob(id))
+            ));
+        }
+        db.run("INSERT INTO files (id,data) VALUES (30,X'DEADBEEF');");
+
+        let rows = db.run("SELECT * FROM files;").unwrap_or_default();
+        assert_eq!(rows.len(), 31);
+        for (id, row) in rows.iter().take(30).enumerate() {
+            assert_eq!(row.0[1], Value::Blob(blob(id)));
+        }
+
+        db.run(&format!(
+            "UPDATE files SET data = X'{}' WHERE data = X'DEADBEEF';",
+            hex(&blob(31))
+        ));
+        assert_eq!(
+            db.run(&format!(
+                "SELECT (id) FROM files WHERE data = X'{}';",
+                hex(&blob(31))
+            )),
+            Some(vec![Record(vec![Value::UInt(30)])])
+        );
+
+        db.run(&format!(
+            "DELETE FROM files WHERE data = X'{}';",
+            hex(&blob(7))
+        ));
+        assert_eq!(db.run("SELECT * FROM files;").unwrap_or_default().len(), 30);
+    }
+
+    #[test]
+    fn test_update_key() {
+        let mut db = TestDb::new();
+
+        db.run("CREATE TABLE teams (team uint, id uint, name string, PRIMARY KEY (team, id));");
+        db.run("INSERT INTO teams (team,id,name) VALUES (1,1,\"a\");");
+        db.run("INSERT INTO teams (team,id,name) VALUES (2,1,\"c\");");
+        db.run("INSERT INTO teams (team,id,name) VALUES (1,2,\"b\");");
+
+        let err = db
+            .try_run("UPDATE teams SET team = 2, id = 1 WHERE name = \"b\";")
+            .expect_err("A primary key was used twice");
+        assert!(err.to_string().contains("UNIQUE constraint failed"));
+
+        // the row moves to its new place in the order of the key.
+        db.run("UPDATE teams SET team = 3 WHERE name = \"a\";");
+        assert_eq!(
+            db.json("SELECT * FROM teams;"),
+            r#"[[1,2,"b"],[2,1,"c"],[3,1,"a"]]"#
+        );
+        assert_eq!(
+            db.json("SELECT (name) FROM teams WHERE team = 3 AND id = 1;"),
+            r#"[["a"]]"#
+        );
+
+        db.run("CREATE TABLE ranked (id uint PRIMARY KEY DESC, name string);");
+        db.run("INSERT INTO ranked (id,name) VALUES (1,\"a\");");
+        db.run("INSERT INTO ranked (id,name) VALUES (2,\"b\");");
+        db.run("UPDATE ranked SET id = 5 WHERE id = 1;");
+        assert_eq!(db.json("SELECT (id) FROM ranked;"), "[[5],[2]]");
+    }
+
+    #[test]
+    fn test_failed_update() {
+        let mut db = TestDb::new();
+
+        db.run("CREATE TABLE named (name string PRIMARY KEY, id uint);");
+        db.run("INSERT INTO named (name,id) VALUES (\"k1\",1);");
+        let err = db
+            .try_run(&format!(
+                "UPDATE named SET name = \"{}\" WHERE id = 1;",
+                "k".repeat(400)
+            ))
+            .expect_err("A large key was set");
+        assert!(err.to_string().contains("Primary key is too large"));
+        assert_eq!(db.json("SELECT * FROM named;"), r#"[["k1",1]]"#);
+
+        db.run("CREATE TABLE files (id uint PRIMARY KEY, data blob);");
+        db.run("CREATE INDEX files_data ON files (data);");
+        db.run("INSERT INTO files (id,data) VALUES (1,X'0102');");
+        let err = db
+            .try_run(&format!(
+                "UPDATE files SET data = X'{}' WHERE id = 1;",
+                "ab".repeat(300)
+            ))
+            .expect_err("A large indexed value was set");
+        assert!(err.to_string().contains("Indexed value is too large"));
+
+        // the table and the index still agree.
+        assert_eq!(db.json("SELECT * FROM files;"), r#"[[1,"AQI="]]"#);
+        assert_eq!(
+            db.json("SELECT (id) FROM files WHERE data = X'0102';"),
+            "[[1]]"
+        );
+
+        // a row too large for a page is only found out while it is written.
+        db.run("CREATE TABLE notes (id uint PRIMARY KEY, tag uint, note string);");
+        db.run("CREATE INDEX notes_tag ON notes (tag);");
+        db.run("INSERT INTO notes (id,tag,note) VALUES (1,7,\"a\");");
+        assert!(db
+            .try_run(&format!(
+                "UPDATE notes SET tag = 8, note = \"{}\" WHERE id = 1;",
+                "n".repeat(5000)
+            ))
+            .is_err());
+        assert_eq!(db.json("SELECT * FROM notes;"), r#"[[1,7,"a"]]"#);
+        assert_eq!(db.json("SELECT (id) FROM notes WHERE tag = 7;"), "[[1]]");
+        assert_eq!(db.json("SELECT (id) FROM notes WHERE tag = 8;"), "[]");
+    }
+
+    #[test]
+    fn test_drop_busy_table() {
+        let mut db = TestDb::new();
+
+        db.run("CREATE TABLE busy (id uint PRIMARY KEY);");
+        db.run("INSERT INTO busy (id) VALUES (1);");
+
+        // another process holding the table file.
+        let mut other = {
+            let lock_table = db.lock_table.read().expect("Failed to lock");
+            let table = "busy".to_string();
+            let path = lock_table.storage().table_path(&table);
+            lock_table
+                .builder(&table, path)
+                .build()
+                .expect("Failed to build")
+        };
+        other
+            .lock(LockMode::Shared, Duration::ZERO)
+            .expect("Failed to lock");
+
+        db.session.busy_timeout = Duration::from_millis(20);
+        match db.try_run("DROP TABLE busy;") {
+            Err(err) => assert!(err.is_busy()),
+            Ok(_) => panic!("Expected the table to be busy"),
+        };
+        drop(other);
+
+        assert_eq!(db.table_names(), vec!["busy".to_string()]);
+        assert_eq!(db.json("SELECT (id) FROM busy;"), "[[1]]");
+
+        db.run("DROP TABLE busy;");
+        assert!(db.table_names().is_empty());
+    }
+
+    #[test]
+    fn test_large_keys() {
+        let mut db = TestDb::new();
+        let hex = |byte: usize| format!("{:02x}", byte).repeat(300);
+
+        // keys are not moved to overflow pages, so they are limited in size.
+        db.run("CREATE TABLE blob_keys (data blob PRIMARY KEY, id uint);");
+        db.run("INSERT INTO blob_keys (data,id) VALUES (X'0102',1);");
+        for id in 0..20 {
+            let err = db
+                .try_run(&format!(
+                    "INSERT INTO blob_keys (data,id) VALUES (X'{}',{});",
+                    hex(id),
+                    id
+                ))
+                .expect_err("A large key was inserted");
+            assert!(err.to_string().contains("Primary key is too large"));
+        }
+        assert_eq!(db.json("SELECT (id) FROM blob_keys;"), "[[1]]");
+
+        // the same for an indexed blob, even though it is kept in overflow pages in the table.
+        db.run("CREATE TABLE blob_values (id uint PRIMARY KEY, data blob);");
+        db.run("CREATE INDEX blob_values_data ON blob_values (data);");
+        db.run("INSERT INTO blob_values (id,data) VALUES (1,X'0102');");
+        for id in 2..20 {
+            let err = db
+                .try_run(&format!(
+                    "INSERT INTO blob_values (id,data) VALUES ({},X'{}');",
+                    id,
+                    hex(id)
+                ))
+                .expect_err("A large indexed value was inserted");
+            assert!(err.to_string().contains("Indexed value is too large"));
+        }
+        assert_eq!(
+            db.json("SELECT (id) FROM blob_values WHERE data = X'0102';"),
+            "[[1]]"
+        );
+        // the rows that did not fit in the index were taken out of the table again.
+        assert_eq!(db.json("SELECT (id) FROM blob_values;"), "[[1]]");
+        db.run("INSERT INTO blob_values (id,data) VALUES (2,X'0304');");
+        assert_eq!(db.json("SELECT (id) FROM blob_values;"), "[[1],[2]]");
+    }
+
+    #[test]
+    fn test_descending_primary_key() {
+        let mut db = TestDb::new();
+
+        let ids = |rows: Option<Vec<Record>>| {
+            rows.unwrap_or_default()
+                .into_iter()
+                .map(|x| x.0[..x.0.len() - 1].to_vec())
+                .collect::<Vec<Vec<Value>>>()
+        };
+
+        db.run("CREATE TABLE events (id uint PRIMARY KEY DESC, name string);");
+        // enough rows to split the root, inserted out of order.
+        for id in (0..60).map(|x| x * 37 % 60) {
+            db.run(&format!(
+                "INSERT INTO events (id,name) VALUES ({},\"e\");",
+                id
+            ));
+        }
+        assert!(db
+            .try_run("INSERT INTO events (id,name) VALUES (23,\"e\");")
+            .is_err());
+
+        assert_eq!(
+            ids(db.run("SELECT * FROM events;")),
+            (0..60)
+                .rev()
+                .map(|x| vec![Value::UInt(x)])
+                .collect::<Vec<Vec<Value>>>()
+        );
+
+        // rows are still found by their key.
+        db.run("CREATE INDEX events_name ON events (name);");
+        db.run("UPDATE events SET name = \"f\" WHERE id = 17;");
+        db.run("DELETE FROM events WHERE name = \"e\";");
+        assert_eq!(
+            db.run("SELECT * FROM events;"),
+            Some(vec![Record(vec![
+                Value::UInt(17),
+                Value::String("f".into())
+            ])])
+        );
+
+        db.run("CREATE TABLE scores (team uint, score uint, name string, PRIMARY KEY (team, score DESC));");
+        for (team, score) in [(1, 10), (2, 5), (1, 30), (2, 50), (1, 20)] {
+            db.run(&format!(
+                "INSERT INTO scores (team,score,name) VALUES ({},{},\"s\");",
+                team, score
+            ));
+        }
+        assert_eq!(
+            ids(db.run("SELECT * FROM scores;")),
+            [(1, 30), (1, 20), (1, 10), (2, 50), (2, 5)]
+                .iter()
+                .map(|(a, b)| vec![Value::UInt(*a), Value::UInt(*b)])
+                .collect::<Vec<Vec<Value>>>()
+        );
+    }
+
+    #[test]
+    fn test_analyze() {
+        let mut db = TestDb::new();
+
+        db.run("CREATE TABLE analyzed (id uint PRIMARY KEY, age uint);");
+        for id in 0..100 {
+            db.run(&format!(
+                "INSERT INTO analyzed (id,age) VALUES ({},{});",
+                id, id
+            ));
+        }
+        db.run("CREATE INDEX analyzed_age ON analyzed (age);");
+
+        let detail = |rows: Option<Vec<Record>>| rows.and_then(|x| x[0].0.get(3).cloned());
+        let query = "EXPLAIN QUERY PLAN SELECT * FROM analyzed WHERE age > 97;";
+
+        // a range with one bound is guessed to match a quarter of a large table.
+        assert_eq!(
+            detail(db.run(query)),
+            Some(Value::String("SCAN analyzed".into()))
+        );
+
+        db.run("ANALYZE analyzed;");
+
+        assert_eq!(
+            detail(db.run(query)),
+            Some(Value::String(
+                "SEARCH analyzed USING INDEX analyzed_age (age>?)".into()
+            ))
+        );
+        assert_eq!(
+            db.run("SELECT * FROM analyzed WHERE age > 97;")
+                .map(|x| x.len()),
+            Some(2)
+        );
+
+        let schemas = db
+            .lock_table
+            .read()
+            .expect("Failed to lock")
+            .schemas(&Session::from(&Config::default()))
+            .expect("Failed to get schemas");
+        let stats = schemas[0].1.as_ref().expect("Table was not analyzed");
+        assert_eq!(stats.rows, 100);
+        assert_eq!(stats.columns[1].distinct, 100);
+        assert_eq!(stats.columns[1].max, Value::UInt(99));
+    }
+
+    #[test]
+    fn test_stream_statement() {
+        let mut db = TestDb::new();
+
+        let mut ignore = |_| Ok(());
+
+        db.stream("CREATE TABLE streamed (id uint PRIMARY KEY);", &mut ignore)
+            .expect("Failed to create");
+        for id in 0..50 {
+            db.stream(
+                &format!("INSERT INTO streamed (id) VALUES ({});", id),
+                &mut ignore,
+            )
+            .expect("Failed to insert");
+        }
+
+        let mut rows = vec![];
+        db.stream("SELECT * FROM streamed WHERE id < 10;", &mut |row| {
+            rows.push(row);
+            Ok(())
+        })
+        .expect("Failed to select");
+        assert_eq!(rows.len(), 10);
+
+        // the output failing stops the read.
+        let mut read = 0;
+        let result = db.stream("SELECT * FROM streamed;", &mut |_| {
+            read += 1;
+            Err(Error::Unexpexted("Client went away."))
+        });
+        assert!(result.is_err());
+        assert_eq!(read, 1);
+
+        // the table is usable again after a failed read.
+        db.stream("DROP TABLE streamed;", &mut ignore)
+            .expect("Failed to drop");
     }
 }
@@ -2,51 +2,175 @@ use std::{collections::HashMap, path::Path, sync::RwLock};
 
 use log::info;
 
-use crate::{engine::btree::BTreeBuilder, errors::Error};
+use crate::{
+    engine::{btree::BTreeBuilder, catalog::Catalog, file_lock::LockMode, page_cache::PageCache},
+    errors::Error,
+    sql::Statement,
+};
+
+use super::{
+    execute::{execute_statement, read_with_timeout, with_file_lock, AccessLockTable, LockTable},
+    session::Session,
+    storage::{Layout, Storage},
+};
+
+pub fn get_table_locks(storage: &Storage, cache_size: usize) -> Result<LockTable, Error> {
+    let mut table: HashMap<String, RwLock<()>> = HashMap::new();
 
-use super::execute::LockTable;
+    match &storage.layout {
+        Layout::Directory(file_dir) => {
+            // first run with this data directory.
+            storage.vfs.create_dir(file_dir)?;
 
-pub fn get_table_locks(file_dir: &'static str) -> Result<LockTable, Error> {
-    let dirs = Path::new(file_dir).read_dir()?;
+            for path in storage.vfs.list_dir(file_dir)? {
+                if !storage.exists(&path.join("table")) {
+                    continue;
+                }
 
-    let mut table: HashMap<String, RwLock<()>> = HashMap::new();
+                let item = path
+                    .iter()
+                    .next_back()
+                    .ok_or(Error::Unexpexted("Failed to get last el"))?;
+
+                table.insert(item.to_string_lossy().to_string(), RwLock::new(()));
+            }
+        }
+        Layout::SingleFile(path) => {
+            storage.create_parent(path)?;
 
-    for dir in dirs {
-        let path = dir?.path();
-        if !path.is_dir() {
+            if storage.exists(path) {
+                let mut file = storage.vfs.open(path)?;
+
+                for name in Catalog::read(file.as_mut())?.names() {
+                    table.insert(name, RwLock::new(()));
+                }
+            }
+        }
+    }
+
+    Ok(LockTable::new(
+        table,
+        storage.clone(),
+        PageCache::shared(cache_size),
+    ))
+}
+
+/// Copy every table and index of a directory layout database into the current single file database.
+pub fn migrate_directory(
+    source: &Path,
+    lock_table: AccessLockTable,
+    session: &mut Session,
+) -> Result<(), Error> {
+    if !read_with_timeout(&lock_table, session.busy_timeout)?
+        .storage()
+        .is_single_file()
+    {
+        return Err(Error::Unexpexted(
+            "Migrating requires a single file database, start with --file <path>.",
+        ));
+    }
+
+    for dir in source.read_dir()? {
+        let dir_path = dir?.path();
+        let path = dir_path.join("table");
+        if !path.is_file() {
             continue;
         }
 
-        let item = path
-            .iter()
-            .last()
-            .ok_or_else(|| Error::Unexpexted("Failed to get last el"))?;
+        let mut db = BTreeBuilder::new().b_parameter(10).path(path).build()?;
+
+        let (schema, rows) =
+            with_file_lock(&mut db, LockMode::Shared, session.busy_timeout, |db| {
+                Ok((db.get_table()?, db.select(&vec![], &None, None)?))
+            })?;
+
+        let create = Statement::Create {
+            primary_key: schema.key_columns(),
+            table: schema.name.clone(),
+            cols: schema.columns.clone(),
+        };
+        execute_statement(&create, lock_table.clone(), session)?;
+
+        let table_lock = read_with_timeout(&lock_table, session.busy_timeout)?;
+        let guards =
+            table_lock.lock_tables(&[(&schema.name, LockMode::Exclusive)], session.busy_timeout)?;
 
-        table.insert(item.to_string_lossy().to_string(), RwLock::new(()));
+        let mut db = table_lock.take_handle(&schema.name, session)?;
+        let count = rows.len();
+
+        with_file_lock(&mut db, LockMode::Exclusive, session.busy_timeout, |db| {
+            for row in rows {
+                db.insert(row)?;
+            }
+            Ok(())
+        })?;
+
+        table_lock.return_handle(&schema.name, db)?;
+        drop(guards);
+        drop(table_lock);
+
+        info!("Migrated table '{}' ({} rows)", schema.name, count);
+
+        let indexes = dir_path.join("indexes");
+        if !indexes.is_dir() {
+            continue;
+        }
+
+        // indexes are created again from the migrated rows.
+        for index_dir in indexes.read_dir()? {
+            let path = index_dir?.path().join("table");
+            if !path.is_file() {
+                continue;
+            }
+
+            let mut index = BTreeBuilder::new()
+                .b_parameter(10)
+                .path(path)
+                .index(schema.name.clone())
+                .build()?;
+            let index_schema =
+                with_file_lock(&mut index, LockMode::Shared, session.busy_timeout, |db| {
+                    Ok(db.get_table()?)
+                })?;
+
+            let indexed = &index_schema.columns[..index_schema.primary_key];
+            let create = Statement::CreateIndex {
+                name: index_schema.name.clone(),
+                table: schema.name.clone(),
+                columns: indexed.iter().map(|x| x.name.clone()).collect(),
+                unique: indexed.first().is_some_and(|x| x.unique),
+            };
+            execute_statement(&create, lock_table.clone(), session)?;
+        }
     }
 
-    Ok(LockTable::new(table))
+    Ok(())
 }
 
-pub fn run_meta_command(buffer: &String) -> Result<(), Error> {
+pub fn run_meta_command(
+    buffer: &str,
+    lock_table: AccessLockTable,
+    session: &mut Session,
+) -> Result<(), Error> {
     // remove \r
     let input: String = buffer.trim().chars().filter(|x| !x.is_control()).collect();
 
     match input.as_str() {
         ".exit" => std::process::exit(0),
         ".show tables" => {
-            let dirs = Path::new("./db").read_dir()?;
-
-            for dir in dirs.into_iter() {
-                let path = dir?.path().join("table");
-
-                let mut db = BTreeBuilder::new().b_parameter(10).path(path).build()?;
-
-                let table = db.get_table()?;
+            let tables = read_with_timeout(&lock_table, session.busy_timeout)?.schemas(session)?;
 
+            for (table, stats) in tables {
                 info!("{}", table);
+                if let Some(stats) = stats {
+                    info!("{}", stats);
+                }
             }
         }
+        _ if input.starts_with(".migrate ") => {
+            let source = input.trim_start_matches(".migrate ").trim();
+            migrate_directory(Path::new(source), lock_table, session)?;
+        }
         _ => {
             println!("Unknown Command: {}", input.escape_debug());
         }
@@ -2,3 +2,5 @@ pub mod args;
 pub mod execute;
 pub mod meta;
 pub mod prepare;
+pub mod session;
+pub mod storage;
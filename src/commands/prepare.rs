@@ -5,7 +5,8 @@ use crate::sql::{interperter::interpect, tokenizer::tokenizer};
 pub fn prepare_statement(buffer: &String) -> Result<Statement, Error> {
     let tokens = tokenizer(buffer)?;
 
-    let value = interpect(tokens)?;
+    let mut value = interpect(tokens)?;
+    value.fold_names();
 
     Ok(value)
 }
@@ -0,0 +1,98 @@
+use std::time::Duration;
+
+use crate::{
+    engine::{
+        decimal::Rounding,
+        structure::{Record, Value},
+        synchronous::Synchronous,
+    },
+    errors::Error,
+};
+
+use super::args::Config;
+
+/// Settings owned by a single connection, the repl or one tcp client.
+/// They start out with the values given on the command line and can be changed with `PRAGMA`.
+#[derive(Debug, Clone)]
+pub struct Session {
+    pub busy_timeout: Duration,
+    pub synchronous: Synchronous,
+    /// How decimals are rounded to the scale of their column.
+    pub rounding: Rounding,
+}
+
+impl From<&Config> for Session {
+    fn from(config: &Config) -> Self {
+        Self {
+            busy_timeout: config.busy_timeout,
+            synchronous: config.synchronous,
+            rounding: Rounding::default(),
+        }
+    }
+}
+
+impl Session {
+    /// Read a setting when no value is given, otherwise update it for this connection.
+    pub fn pragma(
+        &mut self,
+        name: &str,
+        value: &Option<String>,
+    ) -> Result<Option<Vec<Record>>, Error> {
+        match (name, value) {
+            ("synchronous", None) => Ok(Some(vec![Record(vec![Value::String(
+                self.synchronous.to_string(),
+            )])])),
+            ("synchronous", Some(value)) => {
+                self.synchronous = value.parse()?;
+                Ok(None)
+            }
+            ("busy_timeout", None) => Ok(Some(vec![Record(vec![Value::U64(
+                self.busy_timeout.as_millis() as u64,
+            )])])),
+            ("busy_timeout", Some(value)) => {
+                let millis = value.parse::<u64>().map_err(|_| Error::Argument)?;
+                self.busy_timeout = Duration::from_millis(millis);
+                Ok(None)
+            }
+            ("rounding", None) => Ok(Some(vec![Record(vec![Value::String(
+                self.rounding.to_string(),
+            )])])),
+            ("rounding", Some(value)) => {
+                self.rounding = value.parse()?;
+                Ok(None)
+            }
+            _ => Err(Error::Unexpexted("Unknown pragma.")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Session;
+    use crate::engine::{
+        decimal::Rounding,
+        structure::{Record, Value},
+        synchronous::Synchronous,
+    };
+    use std::time::Duration;
+
+    #[test]
+    fn test_pragma_synchronous() {
+        let mut session = Session {
+            busy_timeout: Duration::ZERO,
+            synchronous: Synchronous::Full,
+            rounding: Rounding::default(),
+        };
+
+        if let Err(err) = session.pragma("synchronous", &Some("off".into())) {
+            panic!("{}", err);
+        }
+
+        assert_eq!(session.synchronous, Synchronous::Off);
+
+        match session.pragma("synchronous", &None) {
+            Ok(value) => assert_eq!(value, Some(vec![Record(vec![Value::String("OFF".into())])])),
+            Err(err) => panic!("{}", err),
+        }
+    }
+}
@@ -0,0 +1,156 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::{
+    engine::{
+        catalog::Catalog,
+        vfs::{MemoryVfs, OsVfs, Vfs},
+    },
+    errors::Error,
+};
+
+/// How the tables of a database are laid out.
+#[derive(Debug, Clone)]
+pub enum Layout {
+    /// One directory per table, each holding a table file and its wal.
+    Directory(PathBuf),
+    /// Every table in one file, found through the catalog on its first page.
+    SingleFile(PathBuf),
+}
+
+/// Where the tables of a database are kept.
+#[derive(Debug, Clone)]
+pub struct Storage {
+    pub layout: Layout,
+    /// Every file of the database is opened through this.
+    pub vfs: Arc<dyn Vfs>,
+}
+
+impl Default for Storage {
+    fn default() -> Self {
+        Self {
+            layout: Layout::Directory(PathBuf::from("./db")),
+            vfs: Arc::new(OsVfs),
+        }
+    }
+}
+
+impl Storage {
+    /// Directory layout kept in memory, nothing is written to disk.
+    pub fn memory() -> Self {
+        Self {
+            vfs: Arc::new(MemoryVfs::new()),
+            ..Default::default()
+        }
+    }
+
+    /// Path of the file the pages of `table` are stored in.
+    pub fn table_path(&self, table: &str) -> PathBuf {
+        match &self.layout {
+            Layout::Directory(dir) => Self::table_dir(dir, table).join("table"),
+            Layout::SingleFile(file) => file.clone(),
+        }
+    }
+
+    /// Path of the file the pages of `index` on `table` are stored in.
+    /// In the directory layout every index has a directory inside the one of its table.
+    pub fn index_path(&self, table: &str, index: &str) -> PathBuf {
+        match &self.layout {
+            Layout::Directory(dir) => Self::table_dir(dir, table)
+                .join("indexes")
+                .join(index)
+                .join("table"),
+            Layout::SingleFile(file) => file.clone(),
+        }
+    }
+
+    fn table_dir(dir: &Path, table: &str) -> PathBuf {
+        dir.join(table)
+    }
+
+    /// Names of the indexes of `table`, in name order.
+    pub fn index_names(&self, table: &str) -> Result<Vec<String>, Error> {
+        let mut names = vec![];
+
+        match &self.layout {
+            Layout::Directory(dir) => {
+                let indexes = Self::table_dir(dir, table).join("indexes");
+                if !self.vfs.exists(&indexes) {
+                    return Ok(names);
+                }
+
+                for path in self.vfs.list_dir(&indexes)? {
+                    if !self.vfs.exists(&path.join("table")) {
+                        continue;
+                    }
+
+                    if let Some(name) = path.file_name() {
+                        names.push(name.to_string_lossy().to_string());
+                    }
+                }
+            }
+            Layout::SingleFile(file) => {
+                if !self.vfs.exists(file) {
+                    return Ok(names);
+                }
+
+                let mut file = self.vfs.open(file)?;
+                names = Catalog::read(file.as_mut())?.indexes(table);
+            }
+        }
+
+        names.sort();
+        Ok(names)
+    }
+
+    pub fn is_single_file(&self) -> bool {
+        matches!(self.layout, Layout::SingleFile(_))
+    }
+
+    pub fn exists(&self, path: &Path) -> bool {
+        self.vfs.exists(path)
+    }
+
+    /// Create the directory a new table file goes in.
+    pub fn create_parent(&self, path: &Path) -> Result<(), Error> {
+        if let Some(parent) = path.parent() {
+            self.vfs.create_dir(parent)?;
+        }
+
+        Ok(())
+    }
+
+    /// Remove the directory of a table or index and every file in it.
+    pub fn remove_table(&self, path: &Path) -> Result<(), Error> {
+        let table_dir = path
+            .parent()
+            .ok_or(Error::Unexpexted("Failed to get path parent"))?;
+
+        if let Layout::Directory(_) = self.layout {
+            self.vfs.remove_dir(table_dir)?;
+        }
+
+        Ok(())
+    }
+
+    /// Sync the directories a table file was created in or removed from,
+    /// so the change is not lost on power loss.
+    pub fn sync_parents(&self, path: &Path) -> Result<(), Error> {
+        let parent = match path.parent() {
+            Some(parent) => parent,
+            None => return Ok(()),
+        };
+
+        if self.vfs.exists(parent) {
+            self.vfs.sync_dir(parent)?;
+        }
+
+        if let (Layout::Directory(_), Some(db_dir)) = (&self.layout, parent.parent()) {
+            self.vfs.sync_dir(db_dir)?;
+        }
+
+        Ok(())
+    }
+}
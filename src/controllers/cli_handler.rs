@@ -1,8 +1,10 @@
 use crate::{
     commands::{
-        execute::{execute_statement, AccessLockTable},
+        execute::{stream_statement, AccessLockTable},
         meta::{self, get_table_locks},
         prepare,
+        session::Session,
+        storage::Storage,
     },
     errors::Error,
 };
@@ -30,33 +32,33 @@ impl log::Log for CliLogger {
     }
 }
 
-fn run_request(value: &String, lock_table: AccessLockTable) -> Result<(), Error> {
+fn run_request(
+    value: &String,
+    lock_table: AccessLockTable,
+    session: &mut Session,
+) -> Result<(), Error> {
     let statement = prepare::prepare_statement(&value)?;
 
-    let result = execute_statement(&statement, lock_table)?;
-
-    if let Some(v) = result {
-        for x in v {
-            for a in x.0 {
-                print!("{} | ", a);
-            }
-            println!();
+    // rows are printed as they are read.
+    stream_statement(&statement, lock_table, session, &mut |row| {
+        for a in row.0 {
+            print!("{} | ", a);
         }
-    }
-
-    Ok(())
+        println!();
+        Ok(())
+    })
 }
 
 static LOGGER: CliLogger = CliLogger;
 
-pub fn handle_cli() -> Result<(), Error> {
+pub fn handle_cli(mut session: Session, storage: Storage, cache_size: usize) -> Result<(), Error> {
     log::set_logger(&LOGGER)
         .map(|()| log::set_max_level(log::LevelFilter::Info))
         .map_err(|_| Error::Logger("Failed to set logger."))?;
 
     let mut input = String::new();
 
-    let lock_table: AccessLockTable = Arc::new(RwLock::new(get_table_locks("./db")?));
+    let lock_table: AccessLockTable = Arc::new(RwLock::new(get_table_locks(&storage, cache_size)?));
 
     loop {
         input.clear();
@@ -66,7 +68,7 @@ pub fn handle_cli() -> Result<(), Error> {
         stdin().read_line(&mut input)?;
 
         if input.starts_with(".") {
-            if let Err(err) = meta::run_meta_command(&input) {
+            if let Err(err) = meta::run_meta_command(&input, lock_table.clone(), &mut session) {
                 eprintln!("{}", err);
             }
             continue;
@@ -74,7 +76,7 @@ pub fn handle_cli() -> Result<(), Error> {
 
         let lock = lock_table.clone();
 
-        if let Err(e) = run_request(&input, lock) {
+        if let Err(e) = run_request(&input, lock, &mut session) {
             eprintln!("{}", e);
         }
     }
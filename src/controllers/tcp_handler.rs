@@ -1,11 +1,19 @@
-use crate::commands::execute::{execute_statement, AccessLockTable};
+use crate::commands::execute::{stream_statement, AccessLockTable};
 use crate::commands::meta::get_table_locks;
 use crate::commands::prepare;
+use crate::commands::session::Session;
+use crate::commands::storage::Storage;
 use crate::engine::structure::Record;
 use crate::errors::Error;
-use std::io::{Read, Write};
+use std::io::{BufWriter, Read, Write};
 use std::net::{TcpListener, TcpStream};
 use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+/// How long writing a response may block on a client that does not read it.
+/// Rows are written while the table is locked, so a stalled client would otherwise
+/// keep every writer of the table waiting.
+const WRITE_TIMEOUT: Duration = Duration::from_secs(10);
 
 fn create_response(value: String, status: &'static str, content_type: &'static str) -> String {
     format!(
@@ -17,21 +25,98 @@ fn create_response(value: String, status: &'static str, content_type: &'static s
     )
 }
 
-fn handle_request(value: &String, lock_table: AccessLockTable) -> Result<Option<String>, Error> {
-    let statement = prepare::prepare_statement(&value)?;
+/// Writes the rows of a response as one JSON array, sent with chunked transfer encoding
+/// so rows go out as they are read. The response only starts with the first row, until
+/// then an error can still be sent with its own status.
+///
+/// The table stays locked until the last row is written, the write timeout of the stream
+/// bounds how long a slow client can hold on to it.
+struct ChunkedRows<'a> {
+    stream: BufWriter<&'a mut TcpStream>,
+    started: bool,
+}
+
+impl<'a> ChunkedRows<'a> {
+    fn new(stream: &'a mut TcpStream) -> Self {
+        Self {
+            stream: BufWriter::new(stream),
+            started: false,
+        }
+    }
+
+    fn row(&mut self, row: &Record) -> Result<(), Error> {
+        let json = serde_json::to_string(row)?;
+
+        if self.started {
+            return self.chunk(format!(",{}", json).as_bytes());
+        }
+
+        self.stream.write_all(
+            "HTTP/1.1 200\r\nContent-Type: application/json\r\nTransfer-Encoding: chunked\r\n\r\n"
+                .as_bytes(),
+        )?;
+        self.started = true;
+        self.chunk(format!("[{}", json).as_bytes())
+    }
+
+    fn chunk(&mut self, data: &[u8]) -> Result<(), Error> {
+        write!(self.stream, "{:X}\r\n", data.len())?;
+        self.stream.write_all(data)?;
+        self.stream.write_all(b"\r\n")?;
+        Ok(())
+    }
+
+    /// End the array, or send an empty one when there were no rows.
+    fn finish(mut self) -> Result<(), Error> {
+        if self.started {
+            self.chunk(b"]")?;
+            self.stream.write_all(b"0\r\n\r\n")?;
+        } else {
+            let res = create_response("[]".to_string(), "200", "application/json");
+            self.stream.write_all(res.as_bytes())?;
+        }
 
-    let result = execute_statement(&statement, lock_table)?;
+        self.stream.flush()?;
+        Ok(())
+    }
 
-    match result {
-        Some(data) => {
-            let content = serde_json::to_string::<Vec<Record>>(&data)?;
-            Ok(Some(content))
+    fn error(mut self, err: Error) -> Result<(), Error> {
+        // the status was already sent, ending without the last chunk tells
+        // the client the response is incomplete.
+        if self.started {
+            eprintln!("{}", err);
+            self.stream.flush()?;
+            return Ok(());
         }
-        None => Ok(None),
+
+        let res = create_response(
+            serde_json::json!({ "error": err.to_string() }).to_string(),
+            // the database was busy, the client can retry the same request later.
+            if err.is_busy() { "503" } else { "400" },
+            "application/json",
+        );
+        self.stream.write_all(res.as_bytes())?;
+        self.stream.flush()?;
+        Ok(())
     }
 }
 
-fn handle_stream(mut stream: TcpStream, table_lock: AccessLockTable) -> Result<(), Error> {
+fn handle_request(
+    value: &String,
+    lock_table: AccessLockTable,
+    session: &mut Session,
+    rows: &mut ChunkedRows<'_>,
+) -> Result<(), Error> {
+    let statement = prepare::prepare_statement(&value)?;
+
+    stream_statement(&statement, lock_table, session, &mut |row| rows.row(&row))
+}
+
+fn handle_stream(
+    mut stream: TcpStream,
+    table_lock: AccessLockTable,
+    mut session: Session,
+) -> Result<(), Error> {
     let mut buffer = [0; 1024];
     stream.read(&mut buffer)?;
 
@@ -62,25 +147,22 @@ fn handle_stream(mut stream: TcpStream, table_lock: AccessLockTable) -> Result<(
 
     let request_body = body.to_string();
 
-    let res = match handle_request(&request_body, table_lock) {
-        Ok(result) => match result {
-            Some(v) => create_response(v, "200", "application/json"),
-            None => create_response("[]".to_string(), "200", "application/json"),
-        },
-        Err(err) => create_response(
-            format!("{{\"error\":\"{}\"}}", err.to_string()),
-            "400",
-            "application/json",
-        ),
-    };
-
-    stream.write_all(res.as_bytes())?;
-    stream.flush()?;
+    stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+    let mut rows = ChunkedRows::new(&mut stream);
 
-    Ok(())
+    match handle_request(&request_body, table_lock, &mut session, &mut rows) {
+        Ok(()) => rows.finish(),
+        Err(err) => rows.error(err),
+    }
 }
 
-pub fn hanlde_tcp<T>(address: T, port: T) -> Result<(), Error>
+pub fn hanlde_tcp<T>(
+    address: T,
+    port: T,
+    session: Session,
+    storage: Storage,
+    cache_size: usize,
+) -> Result<(), Error>
 where
     T: std::string::ToString,
 {
@@ -91,14 +173,17 @@ where
 
     println!("Running on {}:{}", config_address, config_port);
 
-    let exec_lock: AccessLockTable = Arc::new(RwLock::new(get_table_locks("./db")?));
+    let exec_lock: AccessLockTable = Arc::new(RwLock::new(get_table_locks(&storage, cache_size)?));
 
     for s in listener.incoming() {
         let stream = s.expect("Failed to get tcp stream");
 
         let lock = exec_lock.clone();
 
-        std::thread::spawn(move || handle_stream(stream, lock));
+        // every client gets its own copy of the settings, a pragma only affects that connection.
+        let client_session = session.clone();
+
+        std::thread::spawn(move || handle_stream(stream, lock, client_session));
     }
 
     Ok(())
@@ -1,26 +1,152 @@
-use crate::sql::{interperter::ColumnData, Condition};
+use crate::sql::{interperter::ColumnData, ColumnExpr, Condition};
 
 use super::{
+    cursor::Cursor,
+    decimal::Rounding,
     error::Error,
+    file_lock::LockMode,
     node::Node,
     node_type::{NodeType, Schema},
+    overflow::{self, OVERFLOW_THRESHOLD},
     page::Page,
+    page_cache::SharedPageCache,
+    page_layout::{INTERNAL_NODE_HEADER_SIZE, PAGE_SIZE, PTR_SIZE},
     pager::Pager,
-    structure::{ConditionValue, Offset, Operation, Record, Value},
+    stats::{StatsBuilder, TableStats},
+    structure::{Assignment, ConditionValue, Offset, Record, Value},
+    synchronous::Synchronous,
+    vfs::{OsVfs, Vfs},
     wal::Wal,
 };
-use std::{path::PathBuf, vec};
+use std::{cmp::Ordering, ops::Bound, path::PathBuf, sync::Arc, time::Duration, vec};
 
 pub struct BTree {
     pager: Pager,
     b: usize,
     wal: Wal,
+    synchronous: Synchronous,
+    /// Table this tree is an index of.
+    index_of: Option<String>,
+}
+
+/// What the rows of a tree are ordered by.
+enum RowKey {
+    /// The primary key of the table with this schema, and for each of its
+    /// columns if it is kept in descending order.
+    Table(Schema, Vec<bool>),
+    /// Every value of the row in ascending order, for an index.
+    Row,
+}
+
+impl RowKey {
+    fn get(&self, row: &Record) -> Result<Value, Error> {
+        match self {
+            RowKey::Table(schema, _) => schema.key(row),
+            RowKey::Row => Ok(Value::Tuple(row.0.clone())),
+        }
+    }
+
+    /// Compare two keys in the order of the tree.
+    fn cmp(&self, key: &Value, other: &Value) -> Ordering {
+        match other {
+            Value::Tuple(values) => self.compare_prefix(key, values),
+            value => self.compare_prefix(key, std::slice::from_ref(value)),
+        }
+    }
+
+    /// Compare the start of `key` with `bound` in the order of the tree, so a prefix
+    /// of the columns of an index can be searched.
+    fn compare_prefix(&self, key: &Value, bound: &[Value]) -> Ordering {
+        let (values, bound) = match key {
+            Value::Tuple(values) => (&values[..bound.len().min(values.len())], bound),
+            value => (std::slice::from_ref(value), &bound[..bound.len().min(1)]),
+        };
+
+        let descending = match self {
+            RowKey::Table(_, descending) if descending.contains(&true) => descending,
+            _ => return values.cmp(bound),
+        };
+
+        for (idx, (value, other)) in values.iter().zip(bound).enumerate() {
+            let order = match descending.get(idx) {
+                Some(true) => other.cmp(value),
+                _ => value.cmp(other),
+            };
+            if order != Ordering::Equal {
+                return order;
+            }
+        }
+
+        values.len().cmp(&bound.len())
+    }
+
+    fn above_lower(&self, key: &Value, lower: &Bound<Vec<Value>>) -> bool {
+        match lower {
+            Bound::Included(bound) => self.compare_prefix(key, bound) != Ordering::Less,
+            Bound::Excluded(bound) => self.compare_prefix(key, bound) == Ordering::Greater,
+            Bound::Unbounded => true,
+        }
+    }
+
+    fn below_upper(&self, key: &Value, upper: &Bound<Vec<Value>>) -> bool {
+        match upper {
+            Bound::Included(bound) => self.compare_prefix(key, bound) != Ordering::Greater,
+            Bound::Excluded(bound) => self.compare_prefix(key, bound) == Ordering::Less,
+            Bound::Unbounded => true,
+        }
+    }
+}
+
+/// Keys a cursor is limited to, in the order of the tree.
+pub(super) struct KeyRange {
+    row_key: RowKey,
+    lower: Bound<Vec<Value>>,
+    upper: Bound<Vec<Value>>,
+}
+
+impl KeyRange {
+    /// Children of an internal node that can hold keys in the range.
+    pub(super) fn children(&self, children: Vec<Offset>, keys: &[Value]) -> Vec<Offset> {
+        // child idx holds the keys after keys[idx - 1], up to and including keys[idx].
+        children
+            .into_iter()
+            .enumerate()
+            .filter(|(idx, _)| {
+                keys.get(*idx)
+                    .is_none_or(|high| self.row_key.above_lower(high, &self.lower))
+            })
+            .take_while(|(idx, _)| {
+                idx.checked_sub(1)
+                    .and_then(|x| keys.get(x))
+                    .is_none_or(|low| self.row_key.below_upper(low, &self.upper))
+            })
+            .map(|(_, child)| child)
+            .collect()
+    }
+
+    /// Where the row is compared to the range, `Less` before it and `Greater` past its end.
+    pub(super) fn locate(&self, row: &Record) -> Result<Ordering, Error> {
+        let key = self.row_key.get(row)?;
+
+        Ok(if !self.row_key.above_lower(&key, &self.lower) {
+            Ordering::Less
+        } else if !self.row_key.below_upper(&key, &self.upper) {
+            Ordering::Greater
+        } else {
+            Ordering::Equal
+        })
+    }
 }
 
 pub struct BTreeBuilder {
     path: PathBuf,
     b: usize,
     offset: usize,
+    synchronous: Synchronous,
+    cache: Option<SharedPageCache>,
+    table: Option<String>,
+    vfs: Arc<dyn Vfs>,
+    index_of: Option<String>,
 }
 
 impl BTree {
@@ -42,8 +168,102 @@ impl BTree {
         }
     }*/
 
+    /// Take the advisory lock on both the table file and its wal so other
+    /// processes working on the same table wait for us, or fail with `Error::Busy`.
+    pub fn lock(&mut self, mode: LockMode, timeout: Duration) -> Result<(), Error> {
+        if !self.shares_file() {
+            self.pager.lock(mode, timeout)?;
+
+            if let Err(err) = self.wal.lock(mode, timeout) {
+                self.pager.unlock()?;
+                return Err(err);
+            }
+        }
+
+        // another process may have written to the table since we last read it.
+        let (wal_len, wal_modified) = self.wal.version()?;
+        self.pager.validate_cache(wal_len, wal_modified)?;
+
+        if mode == LockMode::Exclusive {
+            self.pager.move_cursor_to_end()?;
+        }
+
+        Ok(())
+    }
+
+    pub fn set_synchronous(&mut self, synchronous: Synchronous) {
+        self.synchronous = synchronous;
+    }
+
+    pub fn unlock(&mut self) -> Result<(), Error> {
+        self.pager.flush()?;
+
+        let (wal_len, wal_modified) = self.wal.version()?;
+        self.pager.update_cache_version(wal_len, wal_modified)?;
+
+        if self.shares_file() {
+            return Ok(());
+        }
+
+        self.wal.unlock()?;
+        self.pager.unlock()
+    }
+
+    /// Release the locks after a failed action. The pages it wrote are thrown away
+    /// instead of flushed, the wal still points at the root from before them.
+    pub fn abort(&mut self) -> Result<(), Error> {
+        self.pager.discard()?;
+
+        if self.shares_file() {
+            return Ok(());
+        }
+
+        self.wal.unlock()?;
+        self.pager.unlock()
+    }
+
+    /// An index in a single file database lives in the file of its table,
+    /// which is locked through the table.
+    fn shares_file(&self) -> bool {
+        self.index_of.is_some() && self.wal.is_catalog()
+    }
+
+    fn row_key(&mut self) -> Result<RowKey, Error> {
+        if self.index_of.is_some() {
+            return Ok(RowKey::Row);
+        }
+
+        let schema = self.get_table()?;
+        let descending = schema.key_descending();
+        Ok(RowKey::Table(schema, descending))
+    }
+
+    /// Point the table at a new root, syncing the pages it refers to
+    /// and the wal depending on the synchronous mode.
+    fn commit_root(&mut self, root: &Offset) -> Result<(), Error> {
+        self.pager.flush()?;
+        self.sync_pages()?;
+
+        self.wal.set_root(root)?;
+
+        if self.synchronous == Synchronous::Full {
+            self.wal.sync()?;
+        }
+
+        Ok(())
+    }
+
+    fn sync_pages(&mut self) -> Result<(), Error> {
+        if self.synchronous >= Synchronous::Normal {
+            self.pager.sync()?;
+        }
+
+        Ok(())
+    }
+
     pub fn get_table(&mut self) -> Result<Schema, Error> {
-        let page = self.pager.get_schema()?;
+        let schema_offset = self.wal.get_schema()?;
+        let page = self.pager.get_schema(&schema_offset)?;
 
         let node = Node::try_from(page)?;
 
@@ -55,14 +275,10 @@ impl BTree {
 
     pub fn select(
         &mut self,
-        keep: &Vec<String>,
+        keep: &Vec<ColumnExpr>,
         target: &Option<Vec<Condition>>,
         _limit: Option<usize>,
     ) -> Result<Vec<Record>, Error> {
-        let root_offset = self.wal.get_root()?;
-        let root_page = self.pager.get_page(&root_offset)?;
-        let root = Node::try_from(root_page)?;
-
         let schema = self.get_table()?;
 
         let selection = if let Some(cond) = target {
@@ -71,80 +287,177 @@ impl BTree {
             None
         };
 
+        let mut calls = vec![];
         let indexs = if keep.len() == 0 {
             None
         } else {
-            Some(schema.get_indexs_from_names(keep))
+            Some(schema.get_indexs_from_names(keep, &mut calls)?)
         };
 
         let mut results = vec![];
 
-        self.select_node(root, &mut results, &indexs, &selection)?;
+        for row in self.cursor()? {
+            let row = row?;
+            if !row.match_condition(&selection)? {
+                continue;
+            }
+
+            results.push(match &indexs {
+                Some(idex) => row.with_calls(&calls)?.select_only(idex),
+                None => row,
+            });
+        }
 
         Ok(results)
     }
 
-    pub fn select_node(
-        &mut self,
-        node: Node,
-        results: &mut Vec<Record>,
-        indexs: &Option<Vec<usize>>,
-        selection: &Option<Vec<ConditionValue>>,
-    ) -> Result<(), Error> {
+    /// Read the rows of the tree one at a time, without collecting them.
+    pub fn cursor(&mut self) -> Result<Cursor<'_>, Error> {
+        let root_offset = self.wal.get_root()?;
+        Ok(Cursor::new(self, root_offset))
+    }
+
+    pub(super) fn read_node(&mut self, offset: &Offset) -> Result<Node, Error> {
+        Node::try_from(self.pager.get_page(offset)?)
+    }
+
+    /// The row with the blobs kept in overflow pages read back into it.
+    pub(super) fn load_row(&mut self, mut row: Record) -> Result<Record, Error> {
+        for value in row.0.iter_mut() {
+            if let Value::Overflow { page, len } = *value {
+                *value = Value::Blob(overflow::read(&mut self.pager, page, len)?);
+            }
+        }
+
+        Ok(row)
+    }
+
+    /// Move a large blob that is not part of the key to overflow pages, so the leaf does not
+    /// fill up with it. The pages are never freed, like every other page.
+    fn spill(&mut self, schema: &Schema, idx: usize, value: Value) -> Result<Value, Error> {
+        match value {
+            Value::Blob(blob) if blob.len() > OVERFLOW_THRESHOLD && !schema.is_key_column(idx) => {
+                overflow::write(&mut self.pager, &blob)
+            }
+            value => Ok(value),
+        }
+    }
+
+    /// Largest encoded key that is accepted. Key columns are kept in the nodes, so a full
+    /// internal node with all of its keys has to fit in a page.
+    fn max_key_size(&self) -> usize {
+        let keys = 2 * self.b - 1;
+        // the children, the length of the keys and the length of the list of keys.
+        let children = (keys + 2) * PTR_SIZE + 1;
+
+        (PAGE_SIZE - INTERNAL_NODE_HEADER_SIZE - children) / keys
+    }
+
+    /// Fail like an insert of `row` would when its key is too large, without writing it.
+    pub fn check_row(&mut self, row: &Record) -> Result<(), Error> {
+        let key = self.row_key()?.get(row)?;
+        self.check_key_size(&key)
+    }
+
+    fn check_key_size(&self, key: &Value) -> Result<(), Error> {
+        let size = bincode::serde::encode_to_vec(key, bincode::config::standard())?.len();
+        let max = self.max_key_size();
+
+        if size > max {
+            return Err(Error::Validate(format!(
+                "{} is too large, keys and indexed values are limited to {} bytes.",
+                match self.index_of {
+                    Some(_) => "Indexed value",
+                    None => "Primary key",
+                },
+                max
+            )));
+        }
+
+        Ok(())
+    }
+
+    fn spill_row(&mut self, row_key: &RowKey, row: Record) -> Result<Record, Error> {
+        // the rows of an index are its keys, so they are kept whole.
+        let RowKey::Table(schema, _) = row_key else {
+            return Ok(row);
+        };
+
+        let values = row
+            .0
+            .into_iter()
+            .enumerate()
+            .map(|(idx, value)| self.spill(schema, idx, value))
+            .collect::<Result<Vec<Value>, Error>>()?;
+
+        Ok(Record(values))
+    }
+
+    /// Walk the whole tree and collect the statistics of the table.
+    pub fn analyze(&mut self) -> Result<TableStats, Error> {
+        let schema = self.get_table()?;
+        let mut builder = StatsBuilder::new(&schema);
+
+        let root_offset = self.wal.get_root()?;
+        self.analyze_node(&root_offset, &mut builder)?;
+
+        Ok(builder.build())
+    }
+
+    fn analyze_node(&mut self, offset: &Offset, builder: &mut StatsBuilder) -> Result<(), Error> {
+        let node = Node::try_from(self.pager.get_page(offset)?)?;
+        builder.page();
+
         match node.node_type {
-            NodeType::Schema(_) => Err(Error::Unexpected),
-            NodeType::Internal(offsets, _) => {
-                for offset in offsets {
-                    let page = self.pager.get_page(&offset)?;
-                    let child_node = Node::try_from(page)?;
-                    self.select_node(child_node, results, indexs, selection)?;
+            NodeType::Internal(children, _) => {
+                for child in children {
+                    self.analyze_node(&child, builder)?;
                 }
-
-                Ok(())
             }
             NodeType::Leaf(rows) => {
-                if let Some(idex) = &indexs {
-                    for row in rows {
-                        if !row.match_condition(selection)? {
-                            continue;
-                        }
-                        results.push(row.select_only(&idex))
-                    }
-                } else {
-                    for row in rows {
-                        if !row.match_condition(selection)? {
-                            continue;
-                        }
-                        results.push(row);
-                    }
+                for row in rows {
+                    builder.row(&self.load_row(row)?);
                 }
-
-                Ok(())
             }
-            NodeType::Unexpected => Err(Error::Unexpected),
+            NodeType::Schema(_) | NodeType::Unexpected => return Err(Error::Unexpected),
         }
+
+        Ok(())
+    }
+
+    /// Statistics of the last `ANALYZE` of the table.
+    pub fn get_stats(&mut self) -> Result<Option<TableStats>, Error> {
+        match self.wal.read_stats()? {
+            Some(data) => Ok(Some(TableStats::from_bytes(&data)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn set_stats(&mut self, stats: &TableStats) -> Result<(), Error> {
+        self.wal
+            .write_stats(&stats.to_bytes()?, self.synchronous >= Synchronous::Normal)
     }
 
     pub fn update(
         &mut self,
         columns: &Vec<(String, ColumnData)>,
         target: &Option<Vec<Condition>>,
+        rounding: Rounding,
     ) -> Result<(), Error> {
-        let mut update = vec![];
         let schema = self.get_table()?;
-        for (col, data) in columns {
-            let item = match data {
-                ColumnData::Null => (
-                    Value::Null,
-                    schema
-                        .get_column_idx_by_name(&col)
-                        .ok_or_else(|| Error::UnexpectedWithReason("Failed to get column."))?,
-                ),
-                ColumnData::Value(d) => schema.parse_value_by_col(&col, &d)?,
-            };
-
-            update.push(item);
-        }
+        let update = schema.parse_assignments(columns, rounding)?;
+
+        // a blob is written to overflow pages once and shared by every row it is set on.
+        self.pager.move_cursor_to_end()?;
+        let update = update
+            .into_iter()
+            .map(|(assignment, idx)| match assignment {
+                Assignment::Value(value) => {
+                    Ok((Assignment::Value(self.spill(&schema, idx, value)?), idx))
+                }
+                assignment => Ok((assignment, idx)),
+            })
+            .collect::<Result<Vec<(Assignment, usize)>, Error>>()?;
 
         let selection = if let Some(cond) = target {
             Some(self.parse_conditions(&cond)?)
@@ -156,13 +469,25 @@ impl BTree {
         let root_page = self.pager.get_page(&root_offset)?;
         let mut root = Node::try_from(root_page)?;
 
-        self.update_item(&update, &selection, &mut root, &root_offset)
+        self.update_item(
+            &schema,
+            &update,
+            &selection,
+            rounding,
+            &mut root,
+            &root_offset,
+        )?;
+
+        // the root does not move, but appending it again lets other processes see the table changed.
+        self.commit_root(&root_offset)
     }
 
     fn update_item(
         &mut self,
-        data: &Vec<(Value, usize)>,
+        schema: &Schema,
+        data: &Vec<(Assignment, usize)>,
         selection: &Option<Vec<ConditionValue>>,
+        rounding: Rounding,
         node: &mut Node,
         node_offset: &Offset,
     ) -> Result<(), Error> {
@@ -178,22 +503,25 @@ impl BTree {
 
                     let mut child_node = Node::try_from(child_page)?;
 
-                    self.update_item(&data, &selection, &mut child_node, node_offset)?;
+                    self.update_item(
+                        schema,
+                        data,
+                        selection,
+                        rounding,
+                        &mut child_node,
+                        child_offset,
+                    )?;
                 }
 
                 Ok(())
             }
             NodeType::Leaf(ref mut rows) => {
                 for row in rows {
-                    if !row.match_condition(&selection)? {
+                    if !self.load_row(row.clone())?.match_condition(selection)? {
                         continue;
                     }
 
-                    for item in data {
-                        if let Some(v) = row.0.get_mut(item.1) {
-                            *v = item.0.to_owned();
-                        }
-                    }
+                    schema.assign(row, data, rounding)?;
                 }
 
                 self.pager
@@ -206,13 +534,16 @@ impl BTree {
     }
 
     pub fn create_table(&mut self, schema: Schema) -> Result<(), Error> {
+        if self.wal.is_catalog() {
+            return self.create_table_in_catalog(schema);
+        }
+
         //let root_page = self.pager.get_page(&Offset(0))?;
         let root_offset = Offset(256);
 
         let schema_node = Node::new(NodeType::Schema(schema), true, None);
 
-        self.pager
-            .write_page_at_offset(Page::try_from(&schema_node)?, &Offset(0))?;
+        self.pager.write_schema(Page::try_from(&schema_node)?)?;
 
         let node = Node::new(NodeType::Leaf(vec![]), true, None);
 
@@ -220,13 +551,75 @@ impl BTree {
 
         let root = self.pager.write_page(Page::try_from(&node)?)?;
 
-        self.wal.set_root(&root)
+        self.commit_root(&root)
+    }
+
+    /// In a single file database the schema and root get their own pages at
+    /// the end of the file and the table is added to the catalog.
+    fn create_table_in_catalog(&mut self, schema: Schema) -> Result<(), Error> {
+        self.wal.init_catalog()?;
+        self.pager.move_cursor_to_end()?;
+
+        let schema_node = Node::new(NodeType::Schema(schema), true, None);
+        let schema_offset = self.pager.write_page(Page::try_from(&schema_node)?)?;
+
+        let node = Node::new(NodeType::Leaf(vec![]), true, None);
+        let root = self.pager.write_page(Page::try_from(&node)?)?;
+
+        self.pager.flush()?;
+        self.sync_pages()?;
+
+        self.wal
+            .register(&schema_offset, &root, self.index_of.as_deref())?;
+
+        if self.synchronous == Synchronous::Full {
+            self.wal.sync()?;
+        }
+
+        Ok(())
+    }
+
+    /// Remove the table from the catalog of a single file database.
+    pub fn drop_table(&mut self) -> Result<(), Error> {
+        self.wal.unregister()?;
+
+        if self.synchronous == Synchronous::Full {
+            self.wal.sync()?;
+        }
+
+        Ok(())
     }
 
     pub fn insert(&mut self, row: Record) -> Result<(), Error> {
-        let root_offset = self.wal.get_root()?;
+        self.insert_many(vec![row])
+    }
 
-        let root_page = self.pager.get_page(&root_offset)?;
+    /// Insert every row, pointing the table at the new root once at the end.
+    pub fn insert_many(&mut self, rows: Vec<Record>) -> Result<(), Error> {
+        let row_key = self.row_key()?;
+
+        // another tree in the same file may have added pages since we were locked.
+        self.pager.move_cursor_to_end()?;
+
+        let mut root_offset = self.wal.get_root()?;
+        for row in rows {
+            root_offset = self.insert_at(&root_offset, &row_key, row)?;
+        }
+
+        self.commit_root(&root_offset)
+    }
+
+    /// Insert the row into the tree at `root_offset`, returning the offset of the new root.
+    fn insert_at(
+        &mut self,
+        root_offset: &Offset,
+        row_key: &RowKey,
+        row: Record,
+    ) -> Result<Offset, Error> {
+        let row = self.spill_row(row_key, row)?;
+        let key = row_key.get(&row)?;
+        self.check_key_size(&key)?;
+        let root_page = self.pager.get_page(root_offset)?;
 
         let new_root_offset: Offset;
         let mut new_root: Node;
@@ -239,7 +632,7 @@ impl BTree {
             root.parent_offset = Some(new_root_offset.clone());
             root.is_root = false;
 
-            let (median, sibling) = root.split(self.b)?;
+            let (median, sibling) = root.split(self.b, &|row| row_key.get(row))?;
 
             let old_root_offset = self.pager.write_page(Page::try_from(&root)?)?;
             let sibling_offset = self.pager.write_page(Page::try_from(&sibling)?)?;
@@ -254,15 +647,17 @@ impl BTree {
             new_root_offset = self.pager.write_page(Page::try_from(&new_root)?)?;
         }
 
-        self.insert_non_full(&mut new_root, new_root_offset.clone(), row)?;
+        self.insert_non_full(&mut new_root, new_root_offset.clone(), row_key, key, row)?;
 
-        self.wal.set_root(&new_root_offset)
+        Ok(new_root_offset)
     }
 
     fn insert_non_full(
         &mut self,
         node: &mut Node,
         node_offset: Offset,
+        row_key: &RowKey,
+        key: Value,
         row: Record,
     ) -> Result<(), Error> {
         match &mut node.node_type {
@@ -272,11 +667,12 @@ impl BTree {
                 ))
             }
             NodeType::Internal(ref mut children, ref mut keys) => {
-                let key = row.0.get(0).expect("Failed to get key").clone();
-                let idx = keys.binary_search(&key.clone()).unwrap_or_else(|x| x);
+                let idx = keys
+                    .binary_search_by(|x| row_key.cmp(x, &key))
+                    .unwrap_or_else(|x| x);
 
                 let child_offset = children
-                    .get(0)
+                    .get(idx)
                     .ok_or(Error::UnexpectedWithReason("Failed to get child offset"))?
                     .clone();
                 let child_page = self.pager.get_page(&child_offset)?;
@@ -288,7 +684,7 @@ impl BTree {
                 children[idx] = new_child_offset.to_owned();
 
                 if self.is_node_full(&child)? {
-                    let (median, mut sibling) = child.split(self.b)?;
+                    let (median, mut sibling) = child.split(self.b, &|row| row_key.get(row))?;
                     self.pager
                         .write_page_at_offset(Page::try_from(&child)?, &new_child_offset)?;
 
@@ -300,21 +696,32 @@ impl BTree {
                     self.pager
                         .write_page_at_offset(Page::try_from(&*node)?, &node_offset)?;
 
-                    if key <= median {
-                        self.insert_non_full(&mut child, new_child_offset, row)
+                    if row_key.cmp(&key, &median) != Ordering::Greater {
+                        self.insert_non_full(&mut child, new_child_offset, row_key, key, row)
                     } else {
-                        self.insert_non_full(&mut sibling, sibling_offset, row)
+                        self.insert_non_full(&mut sibling, sibling_offset, row_key, key, row)
                     }
                 } else {
                     self.pager
                         .write_page_at_offset(Page::try_from(&*node)?, &node_offset)?;
-                    self.insert_non_full(&mut child, new_child_offset, row)
+                    self.insert_non_full(&mut child, new_child_offset, row_key, key, row)
                 }
             }
             NodeType::Leaf(ref mut rows) => {
-                //let idx = rows.binary_search().unwrap_or_else(|x| x);
-
-                rows.push(row);
+                let keys = rows
+                    .iter()
+                    .map(|x| row_key.get(x))
+                    .collect::<Result<Vec<Value>, Error>>()?;
+
+                match keys.binary_search_by(|x| row_key.cmp(x, &key)) {
+                    Ok(_) => {
+                        return Err(Error::Validate(format!(
+                            "UNIQUE constraint failed: primary key {}",
+                            key
+                        )))
+                    }
+                    Err(idx) => rows.insert(idx, row),
+                }
 
                 self.pager
                     .write_page_at_offset(Page::try_from(&*node)?, &node_offset)
@@ -370,109 +777,7 @@ impl BTree {
         &mut self,
         condition: &Vec<Condition>,
     ) -> Result<Vec<ConditionValue>, Error> {
-        let schema = self.get_table()?;
-
-        let mut result = vec![];
-        let mut invert = false;
-        for x in condition {
-            match x {
-                Condition::E(column, value) => {
-                    let (col_value, idx) = schema.parse_value_by_col(&column, &value)?;
-
-                    result.push(ConditionValue::Value {
-                        invert: invert,
-                        idx: idx,
-                        opt: Operation::Equal,
-                        values: [col_value, Value::Null],
-                    });
-                    invert = false;
-                }
-                Condition::GT(column, value) => {
-                    let (col_value, idx) = schema.parse_value_by_col(&column, &value)?;
-
-                    result.push(ConditionValue::Value {
-                        invert: invert,
-                        idx: idx,
-                        opt: Operation::GT,
-                        values: [col_value, Value::Null],
-                    });
-                    invert = false;
-                }
-                Condition::LT(column, value) => {
-                    let (col_value, idx) = schema.parse_value_by_col(&column, &value)?;
-
-                    result.push(ConditionValue::Value {
-                        invert: invert,
-                        idx: idx,
-                        opt: Operation::LT,
-                        values: [col_value, Value::Null],
-                    });
-                    invert = false;
-                }
-                Condition::GTE(column, value) => {
-                    let (col_value, idx) = schema.parse_value_by_col(&column, &value)?;
-
-                    result.push(ConditionValue::Value {
-                        invert: invert,
-                        idx: idx,
-                        opt: Operation::GTE,
-                        values: [col_value, Value::Null],
-                    });
-                    invert = false;
-                }
-                Condition::LTE(column, value) => {
-                    let (col_value, idx) = schema.parse_value_by_col(&column, &value)?;
-
-                    result.push(ConditionValue::Value {
-                        invert: invert,
-                        idx: idx,
-                        opt: Operation::LTE,
-                        values: [col_value, Value::Null],
-                    });
-                    invert = false;
-                }
-                Condition::NE(column, value) => {
-                    let (col_value, idx) = schema.parse_value_by_col(&column, &value)?;
-
-                    result.push(ConditionValue::Value {
-                        invert: !invert,
-                        idx: idx,
-                        opt: Operation::Equal,
-                        values: [col_value, Value::Null],
-                    });
-                    invert = false;
-                }
-                Condition::NOT => {
-                    invert = true;
-                }
-                Condition::BETWEEN(column, range_start, range_end) => {
-                    let (start_col, idx) = schema.parse_value_by_col(&column, &range_start)?;
-                    let (end_col, _) = schema.parse_value_by_col(&column, &range_end)?;
-                    result.push(ConditionValue::Value {
-                        invert: invert,
-                        idx: idx,
-                        opt: Operation::BETWEEN,
-                        values: [start_col, end_col],
-                    });
-                    invert = false;
-                }
-                Condition::LIKE(column, value) => {
-                    let (col_value, idx) = schema.parse_value_by_col(&column, &value)?;
-
-                    result.push(ConditionValue::Value {
-                        invert: invert,
-                        idx: idx,
-                        opt: Operation::LIKE,
-                        values: [col_value, Value::Null],
-                    });
-                    invert = false;
-                }
-                Condition::AND => result.push(ConditionValue::AND),
-                Condition::OR => result.push(ConditionValue::OR),
-            }
-        }
-
-        Ok(result)
+        self.get_table()?.parse_conditions(condition)
     }
 
     pub fn delete(&mut self, condition: Option<&Vec<Condition>>) -> Result<(), Error> {
@@ -482,6 +787,8 @@ impl BTree {
             None
         };
 
+        self.pager.move_cursor_to_end()?;
+
         let root_offset = self.wal.get_root()?;
         let root_page = self.pager.get_page(&root_offset)?;
 
@@ -490,7 +797,7 @@ impl BTree {
         let new_root_offset = self.pager.write_page(new_root_page)?;
 
         self.delete_key_from_subtree(&values, &mut new_root, &new_root_offset)?;
-        self.wal.set_root(&new_root_offset)
+        self.commit_root(&new_root_offset)
     }
 
     fn delete_key_from_subtree(
@@ -529,7 +836,17 @@ impl BTree {
                 return Ok(());
             }
             NodeType::Leaf(ref mut rows) => {
-                rows.retain(|x| !x.match_condition(selection).unwrap_or(false));
+                let mut kept = vec![];
+                for row in rows.drain(..) {
+                    if !self
+                        .load_row(row.clone())?
+                        .match_condition(selection)
+                        .unwrap_or(false)
+                    {
+                        kept.push(row);
+                    }
+                }
+                *rows = kept;
 
                 /*let idx = rows
                     .binary_search_by_key(&key, |x| x.0.get(0).expect("").clone())
@@ -548,6 +865,177 @@ impl BTree {
         Ok(())
     }
 
+    /// Rows with a key between `lower` and `upper`, in key order. The bounds are
+    /// compared with the start of the key, so an index can be searched by its first columns.
+    pub fn range(
+        &mut self,
+        lower: &Bound<Vec<Value>>,
+        upper: &Bound<Vec<Value>>,
+    ) -> Result<Vec<Record>, Error> {
+        self.range_cursor(lower.clone(), upper.clone())?.collect()
+    }
+
+    /// Like `range`, but the rows are read one page at a time while the cursor is used.
+    pub fn range_cursor(
+        &mut self,
+        lower: Bound<Vec<Value>>,
+        upper: Bound<Vec<Value>>,
+    ) -> Result<Cursor<'_>, Error> {
+        let range = KeyRange {
+            row_key: self.row_key()?,
+            lower,
+            upper,
+        };
+        let root_offset = self.wal.get_root()?;
+
+        Ok(Cursor::new(self, root_offset).range(range))
+    }
+
+    /// The row with the given primary key.
+    pub fn get(&mut self, key: &Value) -> Result<Option<Record>, Error> {
+        // a composite key is compared column by column.
+        let bound = Bound::Included(match key {
+            Value::Tuple(values) => values.clone(),
+            value => vec![value.clone()],
+        });
+        self.range_cursor(bound.clone(), bound)?.next().transpose()
+    }
+
+    /// Remove the rows with the given keys.
+    pub fn remove(&mut self, keys: &[Value]) -> Result<(), Error> {
+        if keys.is_empty() {
+            return Ok(());
+        }
+
+        let row_key = self.row_key()?;
+        let mut keys = keys.to_vec();
+        keys.sort_by(|a, b| row_key.cmp(a, b));
+
+        let root_offset = self.wal.get_root()?;
+        let mut root = Node::try_from(self.pager.get_page(&root_offset)?)?;
+
+        self.remove_from_subtree(&keys, &row_key, &mut root, &root_offset)?;
+        self.commit_root(&root_offset)
+    }
+
+    fn remove_from_subtree(
+        &mut self,
+        keys: &[Value],
+        row_key: &RowKey,
+        node: &mut Node,
+        node_offset: &Offset,
+    ) -> Result<(), Error> {
+        match &mut node.node_type {
+            NodeType::Internal(children, separators) => {
+                for (idx, child_offset) in children.iter().enumerate() {
+                    // the keys routed to this child, like an insert would.
+                    let start = match idx.checked_sub(1) {
+                        Some(prev) => keys.partition_point(|x| {
+                            row_key.cmp(x, &separators[prev]) != Ordering::Greater
+                        }),
+                        None => 0,
+                    };
+                    let end = match separators.get(idx) {
+                        Some(separator) => {
+                            keys.partition_point(|x| row_key.cmp(x, separator) != Ordering::Greater)
+                        }
+                        None => keys.len(),
+                    };
+
+                    if start >= end {
+                        continue;
+                    }
+
+                    let mut child = Node::try_from(self.pager.get_page(child_offset)?)?;
+                    self.remove_from_subtree(&keys[start..end], row_key, &mut child, child_offset)?;
+                }
+
+                Ok(())
+            }
+            NodeType::Leaf(rows) => {
+                let before = rows.len();
+                rows.retain(|row| match row_key.get(row) {
+                    Ok(key) => keys.binary_search_by(|x| row_key.cmp(x, &key)).is_err(),
+                    Err(_) => true,
+                });
+
+                if rows.len() != before {
+                    self.pager
+                        .write_page_at_offset(Page::try_from(&*node)?, node_offset)?;
+                }
+
+                Ok(())
+            }
+            NodeType::Schema(_) | NodeType::Unexpected => Err(Error::Unexpected),
+        }
+    }
+
+    /// Are the rows stored in key order, so they can be searched by key.
+    /// Tables written before rows were kept sorted are not.
+    pub fn is_ordered(&mut self) -> Result<bool, Error> {
+        let row_key = self.row_key()?;
+        let root_offset = self.wal.get_root()?;
+        let root = Node::try_from(self.pager.get_page(&root_offset)?)?;
+
+        self.is_node_ordered(root, &row_key, None, None)
+    }
+
+    fn is_node_ordered(
+        &mut self,
+        node: Node,
+        row_key: &RowKey,
+        low: Option<&Value>,
+        high: Option<&Value>,
+    ) -> Result<bool, Error> {
+        let keys = match node.node_type {
+            NodeType::Internal(_, ref keys) => keys.clone(),
+            NodeType::Leaf(ref rows) => rows
+                .iter()
+                .map(|x| row_key.get(x))
+                .collect::<Result<Vec<Value>, Error>>()?,
+            NodeType::Schema(_) | NodeType::Unexpected => return Err(Error::Unexpected),
+        };
+
+        let in_bounds = keys
+            .windows(2)
+            .all(|x| row_key.cmp(&x[0], &x[1]) == Ordering::Less)
+            && keys
+                .iter()
+                .all(|x| low.is_none_or(|low| row_key.cmp(x, low) == Ordering::Greater))
+            && keys
+                .iter()
+                .all(|x| high.is_none_or(|high| row_key.cmp(x, high) != Ordering::Greater));
+
+        if !in_bounds {
+            return Ok(false);
+        }
+
+        if let NodeType::Internal(children, _) = node.node_type {
+            for (idx, child_offset) in children.iter().enumerate() {
+                let child = Node::try_from(self.pager.get_page(child_offset)?)?;
+                let child_low = idx.checked_sub(1).and_then(|x| keys.get(x)).or(low);
+                let child_high = keys.get(idx).or(high);
+
+                if !self.is_node_ordered(child, row_key, child_low, child_high)? {
+                    return Ok(false);
+                }
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// Replace the rows of the table with `rows`, written into a new tree in key order.
+    pub fn rebuild(&mut self, rows: Vec<Record>) -> Result<(), Error> {
+        self.pager.move_cursor_to_end()?;
+
+        let node = Node::new(NodeType::Leaf(vec![]), true, None);
+        let root = self.pager.write_page(Page::try_from(&node)?)?;
+        self.commit_root(&root)?;
+
+        self.insert_many(rows)
+    }
+
     /*fn borrow_if_needed(&mut self, node: Node, key: &Value) -> Result<(), Error> {
             if self.is_node_underflow(&node)? {
                 let parent_offset = node.parent_offset.clone().ok_or(Error::Unexpected)?;
@@ -696,6 +1184,11 @@ impl BTreeBuilder {
             path: PathBuf::new(),
             b: 0,
             offset: 0,
+            synchronous: Synchronous::default(),
+            cache: None,
+            table: None,
+            vfs: Arc::new(OsVfs),
+            index_of: None,
         }
     }
     pub fn path(mut self, path: PathBuf) -> Self {
@@ -712,6 +1205,35 @@ impl BTreeBuilder {
         self
     }
 
+    pub fn synchronous(mut self, synchronous: Synchronous) -> Self {
+        self.synchronous = synchronous;
+        self
+    }
+
+    pub fn cache(mut self, cache: SharedPageCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Open `table` inside the single file database at `path`
+    /// instead of a table file with its own wal.
+    pub fn table(mut self, table: String) -> Self {
+        self.table = Some(table);
+        self
+    }
+
+    /// Open the table files through `vfs` instead of directly on disk.
+    pub fn vfs(mut self, vfs: Arc<dyn Vfs>) -> Self {
+        self.vfs = vfs;
+        self
+    }
+
+    /// Open an index of `table`, its rows are ordered by every value instead of the primary key.
+    pub fn index(mut self, table: String) -> Self {
+        self.index_of = Some(table);
+        self
+    }
+
     pub fn build(&self) -> Result<BTree, Error> {
         if self.path.to_string_lossy() == "" {
             return Err(Error::UnexpectedWithReason("File path is empty"));
@@ -723,23 +1245,33 @@ impl BTreeBuilder {
             ));
         }
 
-        let mut pager = Pager::new(self.path.clone())?;
+        let mut pager = Pager::new(self.vfs.as_ref(), self.path.clone())?;
 
         if self.offset != 0 {
             pager.set_cursor(256)
         }
 
+        if let Some(cache) = &self.cache {
+            pager.set_cache(cache.clone());
+        }
+
         //let mut pager = ?;
 
-        let parent_directory = self
-            .path
-            .parent()
-            .ok_or_else(|| Error::UnexpectedWithReason("Failed to get parent of given path."))?;
+        let parent_directory = self.path.parent().ok_or(Error::UnexpectedWithReason(
+            "Failed to get parent of given path.",
+        ))?;
+
+        let wal = match &self.table {
+            Some(table) => Wal::catalog(self.vfs.as_ref(), self.path.clone(), table.clone())?,
+            None => Wal::new(self.vfs.as_ref(), parent_directory.to_path_buf())?,
+        };
 
         Ok(BTree {
             pager,
             b: self.b,
-            wal: Wal::new(parent_directory.to_path_buf())?,
+            wal,
+            synchronous: self.synchronous,
+            index_of: self.index_of.clone(),
         })
     }
 }
@@ -747,10 +1279,14 @@ impl BTreeBuilder {
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::engine::structure::Value;
+    use crate::engine::{
+        page_cache::{lock_cache, PageCache},
+        structure::Value,
+        vfs::MemoryVfs,
+    };
 
     fn get_db() -> BTree {
-        let tree = match BTreeBuilder::new()
+        match BTreeBuilder::new()
             .b_parameter(10)
             .path(PathBuf::from("./db/test.bin"))
             .cursor_offset(256)
@@ -758,9 +1294,7 @@ mod test {
         {
             Ok(value) => value,
             Err(e) => panic!("{}", e),
-        };
-
-        tree
+        }
     }
 
     #[test]
@@ -774,6 +1308,98 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_range_and_remove() {
+        let mut tree = BTreeBuilder::new()
+            .b_parameter(2)
+            .path(PathBuf::from("db/range/table"))
+            .cursor_offset(256)
+            .vfs(Arc::new(MemoryVfs::new()))
+            .build()
+            .expect("Failed to build");
+
+        tree.create_table(Schema::new("range".into(), 0, vec![], None))
+            .expect("Failed to create");
+
+        // out of order, so leaves get split.
+        let rows: Vec<Record> = (0..40u64)
+            .map(|x| Record(vec![Value::U64((x * 7) % 40)]))
+            .collect();
+        tree.insert_many(rows).expect("Failed to insert");
+
+        assert!(tree.is_ordered().expect("Failed to check order"));
+        assert_eq!(
+            tree.cursor()
+                .expect("Failed to open cursor")
+                .map(|row| row.expect("Failed to read").0[0].clone())
+                .collect::<Vec<_>>(),
+            (0..40u64).map(Value::U64).collect::<Vec<_>>()
+        );
+        assert!(tree.insert(Record(vec![Value::U64(3)])).is_err());
+
+        let keys = |rows: Vec<Record>| -> Vec<Value> {
+            rows.into_iter().map(|x| x.0[0].clone()).collect()
+        };
+
+        let found = tree
+            .range(
+                &Bound::Excluded(vec![Value::U64(10)]),
+                &Bound::Included(vec![Value::U64(14)]),
+            )
+            .expect("Failed to search");
+        assert_eq!(keys(found), (11..=14).map(Value::U64).collect::<Vec<_>>());
+
+        tree.remove(&[Value::U64(12), Value::U64(30)])
+            .expect("Failed to remove");
+        assert_eq!(tree.get(&Value::U64(12)).expect("Failed to get"), None);
+        assert_eq!(
+            tree.get(&Value::U64(13)).expect("Failed to get"),
+            Some(Record(vec![Value::U64(13)]))
+        );
+        assert_eq!(
+            tree.select(&vec![], &None, None)
+                .expect("Failed to select")
+                .len(),
+            38
+        );
+    }
+
+    #[test]
+    fn test_abort_discards_pages() {
+        let cache = PageCache::shared(16);
+        let mut tree = BTreeBuilder::new()
+            .b_parameter(2)
+            .path(PathBuf::from("db/abort/table"))
+            .cursor_offset(256)
+            .vfs(Arc::new(MemoryVfs::new()))
+            .cache(cache.clone())
+            .build()
+            .expect("Failed to build");
+
+        tree.create_table(Schema::new("abort".into(), 0, vec![], None))
+            .expect("Failed to create");
+        tree.insert(Record(vec![Value::U64(1)]))
+            .expect("Failed to insert");
+
+        // a failed statement that overwrote the root without committing it.
+        tree.lock(LockMode::Exclusive, Duration::ZERO)
+            .expect("Failed to lock");
+        let root = tree.wal.get_root().expect("Failed to get root");
+        tree.pager
+            .write_page_at_offset(Page::new([0xff; PAGE_SIZE]), &root)
+            .expect("Failed to write");
+        tree.abort().expect("Failed to abort");
+
+        assert!(lock_cache(&cache)
+            .expect("Failed to lock cache")
+            .take_dirty(&PathBuf::from("db/abort/table"))
+            .is_empty());
+        assert_eq!(
+            tree.get(&Value::U64(1)).expect("Failed to get"),
+            Some(Record(vec![Value::U64(1)]))
+        );
+    }
+
     #[test]
     fn test_insert() {
         let mut tree = get_db();
@@ -0,0 +1,239 @@
+use serde::{Deserialize, Serialize};
+
+use crate::sql::{ColumnDef, ColumnExpr, Condition, Ordering};
+
+use super::{
+    data_type::DataType,
+    error::Error,
+    node_type::Schema,
+    page_layout::PAGE_SIZE,
+    structure::{Record, Value},
+    vfs::VfsFile,
+};
+
+/// Name the catalog can be queried by, like `sqlite_master`.
+pub const CATALOG_TABLE: &str = "sqlite_master";
+
+const MAGIC: &[u8; 8] = b"RSQLITE1";
+const HEADER_SIZE: usize = 16;
+
+/// One table or index stored in a single file database.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CatalogEntry {
+    /// "table" or "index".
+    pub kind: String,
+    pub name: String,
+    /// Table an index belongs to, the name itself for a table.
+    pub table: String,
+    pub schema_page: usize,
+    pub root_page: usize,
+    /// First page of the statistics of the table, 0 until it is analyzed.
+    pub stats_page: usize,
+}
+
+/// First page of a single file database.
+///
+/// Layout: magic (8 bytes), length of the encoded catalog (8 bytes), then the
+/// catalog itself. Table pages start after this page.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Catalog {
+    /// Bumped on every write so other processes know their cached pages are stale.
+    pub change_counter: u64,
+    pub entries: Vec<CatalogEntry>,
+}
+
+impl Catalog {
+    /// Read the catalog from the start of the file. An empty file has an empty catalog.
+    pub fn read(file: &mut dyn VfsFile) -> Result<Self, Error> {
+        if file.len()? == 0 {
+            return Ok(Self::default());
+        }
+
+        let mut page: [u8; PAGE_SIZE] = [0x00; PAGE_SIZE];
+        file.read_at(&mut page, 0)?;
+
+        let magic = &page[0..8];
+        if magic != MAGIC {
+            return Err(Error::UnexpectedWithReason(
+                "File is not a single file database.",
+            ));
+        }
+
+        let len = u64::from_be_bytes(
+            page[8..HEADER_SIZE]
+                .try_into()
+                .map_err(|_| Error::TryFromSlice)?,
+        ) as usize;
+
+        if len > PAGE_SIZE - HEADER_SIZE {
+            return Err(Error::UnexpectedWithReason("Catalog is corrupted."));
+        }
+
+        let data = &page[HEADER_SIZE..HEADER_SIZE + len];
+        let config = bincode::config::standard();
+        let (catalog, _) = bincode::serde::decode_from_slice(data, config)?;
+
+        Ok(catalog)
+    }
+
+    pub fn write(&self, file: &mut dyn VfsFile) -> Result<(), Error> {
+        let config = bincode::config::standard();
+        let data = bincode::serde::encode_to_vec(self, config)?;
+
+        if data.len() > PAGE_SIZE - HEADER_SIZE {
+            return Err(Error::Validate(
+                "Catalog is full, no more tables can be created.".into(),
+            ));
+        }
+
+        let mut page: [u8; PAGE_SIZE] = [0x00; PAGE_SIZE];
+        page[0..8].copy_from_slice(MAGIC);
+        page[8..HEADER_SIZE].copy_from_slice(&(data.len() as u64).to_be_bytes());
+        page[HEADER_SIZE..HEADER_SIZE + data.len()].copy_from_slice(&data);
+
+        file.write_at(&page, 0)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&CatalogEntry> {
+        self.entries.iter().find(|entry| entry.name == name)
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut CatalogEntry> {
+        self.entries.iter_mut().find(|entry| entry.name == name)
+    }
+
+    /// Names of every table.
+    pub fn names(&self) -> Vec<String> {
+        self.entries
+            .iter()
+            .filter(|entry| entry.kind == "table")
+            .map(|entry| entry.name.clone())
+            .collect()
+    }
+
+    /// Names of the indexes of `table`.
+    pub fn indexes(&self, table: &str) -> Vec<String> {
+        self.entries
+            .iter()
+            .filter(|entry| entry.kind == "index" && entry.table == table)
+            .map(|entry| entry.name.clone())
+            .collect()
+    }
+
+    /// Schema of the catalog, so it can be selected from like any other table.
+    pub fn schema() -> Schema {
+        let column = |name: &str, data_type: DataType| {
+            ColumnDef::new(
+                name.into(),
+                false,
+                false,
+                data_type,
+                false,
+                Ordering::Asc,
+                None,
+            )
+        };
+
+        Schema::new(
+            CATALOG_TABLE.into(),
+            1,
+            vec![
+                column("type", DataType::String),
+                column("name", DataType::String),
+                column("tbl_name", DataType::String),
+                column("schema_page", DataType::U64),
+                column("root_page", DataType::U64),
+            ],
+            None,
+        )
+    }
+
+    pub fn select(
+        &self,
+        keep: &Vec<ColumnExpr>,
+        target: &Option<Vec<Condition>>,
+    ) -> Result<Vec<Record>, Error> {
+        let schema = Self::schema();
+
+        let selection = match target {
+            Some(cond) => Some(schema.parse_conditions(cond)?),
+            None => None,
+        };
+
+        let mut calls = vec![];
+        let indexs = schema.get_indexs_from_names(keep, &mut calls)?;
+
+        let mut results = vec![];
+        for entry in &self.entries {
+            let row = Record(vec![
+                Value::String(entry.kind.clone()),
+                Value::String(entry.name.clone()),
+                Value::String(entry.table.clone()),
+                Value::U64(entry.schema_page as u64),
+                Value::U64(entry.root_page as u64),
+            ]);
+
+            if !row.match_condition(&selection)? {
+                continue;
+            }
+
+            results.push(if keep.is_empty() {
+                row
+            } else {
+                row.with_calls(&calls)?.select_only(&indexs)
+            });
+        }
+
+        Ok(results)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::OpenOptions;
+
+    #[test]
+    fn test_catalog_roundtrip() {
+        let path = std::env::temp_dir().join("rsqlite_catalog_roundtrip.db");
+        let _ = std::fs::remove_file(&path);
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .write(true)
+            .truncate(false)
+            .open(&path)
+            .expect("Failed to open file");
+
+        assert_eq!(
+            Catalog::read(&mut file).expect("Failed to read"),
+            Catalog::default()
+        );
+
+        let catalog = Catalog {
+            change_counter: 3,
+            entries: vec![CatalogEntry {
+                kind: "table".into(),
+                name: "users".into(),
+                table: "users".into(),
+                schema_page: PAGE_SIZE,
+                root_page: PAGE_SIZE * 2,
+                stats_page: 0,
+            }],
+        };
+
+        catalog.write(&mut file).expect("Failed to write");
+
+        let read = Catalog::read(&mut file).expect("Failed to read");
+        assert_eq!(read, catalog);
+        assert_eq!(read.get("users").map(|e| e.root_page), Some(PAGE_SIZE * 2));
+
+        let rows = read
+            .select(&vec!["name".into()], &None)
+            .expect("Failed to select");
+        assert_eq!(rows, vec![Record(vec![Value::String("users".into())])]);
+
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,100 @@
+use std::{cmp::Ordering, vec};
+
+use super::{
+    btree::{BTree, KeyRange},
+    error::Error,
+    node_type::NodeType,
+    structure::{Offset, Record},
+};
+
+/// Reads the rows of a tree in order, one page at a time.
+///
+/// Only the leaf being read and the children of the internal nodes above it are
+/// kept in memory, so a table of any size can be read without collecting it.
+pub struct Cursor<'a> {
+    tree: &'a mut BTree,
+    /// Children still to be read of each internal node above the current leaf.
+    stack: Vec<vec::IntoIter<Offset>>,
+    rows: vec::IntoIter<Record>,
+    /// Only the rows with a key in the range are read, every row without one.
+    range: Option<KeyRange>,
+}
+
+impl<'a> Cursor<'a> {
+    pub fn new(tree: &'a mut BTree, root: Offset) -> Self {
+        Self {
+            tree,
+            stack: vec![vec![root].into_iter()],
+            rows: vec![].into_iter(),
+            range: None,
+        }
+    }
+
+    /// Skip the pages and rows outside of `range`, ending once the rows are past it.
+    pub(super) fn range(mut self, range: KeyRange) -> Self {
+        self.range = Some(range);
+        self
+    }
+
+    fn finish(&mut self) {
+        self.stack.clear();
+        self.rows = vec![].into_iter();
+    }
+
+    fn next_page(&mut self) -> Option<Offset> {
+        loop {
+            match self.stack.last_mut()?.next() {
+                Some(offset) => return Some(offset),
+                None => {
+                    self.stack.pop();
+                }
+            }
+        }
+    }
+}
+
+impl Iterator for Cursor<'_> {
+    type Item = Result<Record, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(row) = self.rows.next() {
+                match self.range.as_ref().map(|range| range.locate(&row)) {
+                    Some(Ok(Ordering::Less)) => continue,
+                    Some(Ok(Ordering::Greater)) => {
+                        self.finish();
+                        return None;
+                    }
+                    Some(Err(err)) => {
+                        self.finish();
+                        return Some(Err(err));
+                    }
+                    Some(Ok(Ordering::Equal)) | None => return Some(self.tree.load_row(row)),
+                }
+            }
+
+            let offset = self.next_page()?;
+
+            match self.tree.read_node(&offset).map(|x| x.node_type) {
+                Ok(NodeType::Internal(children, keys)) => {
+                    let children = match &self.range {
+                        Some(range) => range.children(children, &keys),
+                        None => children,
+                    };
+                    self.stack.push(children.into_iter());
+                }
+                Ok(NodeType::Leaf(rows)) => self.rows = rows.into_iter(),
+                Ok(_) => {
+                    self.finish();
+                    return Some(Err(Error::UnexpectedWithReason(
+                        "Expected an internal or leaf node.",
+                    )));
+                }
+                Err(err) => {
+                    self.finish();
+                    return Some(Err(err));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,346 @@
+use std::{fmt::Display, mem::discriminant};
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    datetime,
+    decimal::{self, Rounding},
+    error::Error,
+    json,
+    structure::Value,
+    uuid,
+};
+
+/// Type of a column.
+///
+/// Schema pages store a type by its position, so new types are only ever added at the end.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DataType {
+    String,
+    UInt,
+    U64,
+    /// Only holds null. Columns of tables created before unknown type names
+    /// were rejected have it.
+    Null,
+    /// Signed 64 bit integer.
+    Int,
+    /// 64 bit floating point number.
+    Real,
+    Boolean,
+    Blob,
+    Date,
+    Time,
+    /// A date and time in UTC.
+    Timestamp,
+    /// Exact number with at most `precision` digits, `scale` of them after the point.
+    Decimal {
+        precision: u8,
+        scale: u8,
+    },
+    Json,
+    Uuid,
+}
+
+/// Every name a type can be declared with and how many arguments it takes, `VARCHAR(255)`.
+/// Like in sqlite the arguments of a string type are accepted but not enforced.
+/// The first name of a type is the one it is shown with.
+const NAMES: &[(&str, DataType, usize)] = &[
+    ("string", DataType::String, 0),
+    ("text", DataType::String, 0),
+    ("varchar", DataType::String, 1),
+    ("char", DataType::String, 1),
+    ("character", DataType::String, 1),
+    ("clob", DataType::String, 0),
+    ("uint", DataType::UInt, 0),
+    ("u64", DataType::U64, 0),
+    ("i64", DataType::Int, 0),
+    ("integer", DataType::Int, 0),
+    ("int", DataType::Int, 0),
+    ("bigint", DataType::Int, 0),
+    ("real", DataType::Real, 0),
+    ("f64", DataType::Real, 0),
+    ("double", DataType::Real, 0),
+    ("float", DataType::Real, 0),
+    ("boolean", DataType::Boolean, 0),
+    ("bool", DataType::Boolean, 0),
+    ("blob", DataType::Blob, 0),
+    ("date", DataType::Date, 0),
+    ("time", DataType::Time, 0),
+    ("timestamp", DataType::Timestamp, 0),
+    ("datetime", DataType::Timestamp, 0),
+    ("decimal", DECIMAL, 2),
+    ("numeric", DECIMAL, 2),
+    ("json", DataType::Json, 0),
+    ("uuid", DataType::Uuid, 0),
+    ("null", DataType::Null, 0),
+];
+
+/// `DECIMAL` without arguments, as many digits as can be kept and none after the point.
+const DECIMAL: DataType = DataType::Decimal {
+    precision: decimal::MAX_PRECISION,
+    scale: 0,
+};
+
+impl DataType {
+    /// Type declared as `name` with `args`, or `None` when no type is called
+    /// that or it does not take that many arguments.
+    pub fn from_declaration(name: &str, args: &[usize]) -> Option<Self> {
+        let name = name.to_lowercase();
+        let data_type = NAMES
+            .iter()
+            .find(|(x, _, max)| *x == name && args.len() <= *max)
+            .map(|(_, data_type, _)| *data_type)?;
+
+        match data_type {
+            // DECIMAL(p) and DECIMAL(p, s), the scale can not be larger than the precision.
+            DataType::Decimal { .. } if !args.is_empty() => {
+                let precision = u8::try_from(args[0]).ok()?;
+                let scale = u8::try_from(args.get(1).copied().unwrap_or(0)).ok()?;
+
+                if !(1..=decimal::MAX_PRECISION).contains(&precision) || scale > precision {
+                    return None;
+                }
+
+                Some(DataType::Decimal { precision, scale })
+            }
+            data_type => Some(data_type),
+        }
+    }
+
+    /// Type of the code schema pages stored before types were an enum.
+    pub fn from_code(code: u8) -> Self {
+        match code {
+            0x00 => DataType::String,
+            0x01 => DataType::UInt,
+            0x02 => DataType::U64,
+            _ => DataType::Null,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        NAMES
+            .iter()
+            .find(|(_, data_type, _)| discriminant(data_type) == discriminant(self))
+            .map(|(name, _, _)| *name)
+            .unwrap_or("null")
+    }
+
+    pub fn is_number(&self) -> bool {
+        matches!(
+            self,
+            DataType::UInt
+                | DataType::U64
+                | DataType::Int
+                | DataType::Real
+                | DataType::Decimal { .. }
+        )
+    }
+
+    /// Parse a literal into a value of this type.
+    pub fn parse(&self, value: &str) -> Result<Value, Error> {
+        self.parse_rounded(value, Rounding::default())
+    }
+
+    /// Like `parse`, with the digits of a decimal that do not fit the scale rounded with `rounding`.
+    pub fn parse_rounded(&self, value: &str, rounding: Rounding) -> Result<Value, Error> {
+        let result = match self {
+            DataType::String => Value::String(value.to_owned()),
+            // a whole number written as `12.0` or `1e3` is taken as well, `12.5` is not.
+            DataType::UInt | DataType::U64 | DataType::Int => {
+                let whole = parse_whole(value)
+                    .ok_or_else(|| invalid(value, self.name(), "a whole number"))?;
+                let fit = || Error::Validate(format!("'{}' does not fit {}.", value, self));
+
+                match self {
+                    DataType::UInt => Value::UInt(usize::try_from(whole).map_err(|_| fit())?),
+                    DataType::U64 => Value::U64(u64::try_from(whole).map_err(|_| fit())?),
+                    _ => Value::Int(i64::try_from(whole).map_err(|_| fit())?),
+                }
+            }
+            // json has no infinity or NaN, `inf` and `1e999` are not taken.
+            DataType::Real => Value::Real(
+                value
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|x| x.is_finite())
+                    .ok_or_else(|| invalid(value, "real", "a finite number"))?,
+            ),
+            // flags written as 1 and 0 are taken as well.
+            DataType::Boolean => match value.to_lowercase().as_str() {
+                "true" | "1" => Value::Bool(true),
+                "false" | "0" => Value::Bool(false),
+                _ => {
+                    return Err(Error::Validate(format!(
+                        "'{}' is not a boolean, expected true or false.",
+                        value
+                    )))
+                }
+            },
+            // a blob literal is given as its hex digits, X'DEADBEEF'.
+            DataType::Blob => Value::Blob(parse_hex(value)?),
+            DataType::Date => match datetime::parse_date(value).map(i32::try_from) {
+                Some(Ok(days)) => Value::Date(days),
+                _ => return Err(invalid(value, "date", "YYYY-MM-DD")),
+            },
+            DataType::Time => match datetime::parse_time(value) {
+                Some(micros) => Value::Time(micros),
+                None => return Err(invalid(value, "time", "HH:MM:SS")),
+            },
+            // seconds since 1970 are taken as well, so u64 columns can be moved over.
+            DataType::Timestamp => match datetime::parse_timestamp(value) {
+                Some(micros) => Value::Timestamp(micros),
+                None => return Err(invalid(value, "timestamp", "YYYY-MM-DD HH:MM:SS")),
+            },
+            DataType::Decimal { scale, .. } => {
+                match decimal::parse(value)
+                    .and_then(|(v, from)| decimal::rescale(v, from, *scale, rounding))
+                {
+                    Some(v) => Value::Decimal(v, *scale),
+                    None => return Err(invalid(value, "decimal", "a number")),
+                }
+            }
+            // the document is checked and kept without its whitespace.
+            DataType::Json => Value::Json(json::minify(value)?),
+            DataType::Uuid => match uuid::parse(value) {
+                Some(uuid) => Value::Uuid(uuid),
+                None => {
+                    return Err(invalid(
+                        value,
+                        "uuid",
+                        "xxxxxxxx-xxxx-xxxx-xxxx-xxxxxxxxxxxx",
+                    ))
+                }
+            },
+            DataType::Null => Value::Null,
+        };
+
+        Ok(result)
+    }
+
+    /// Can a column of this type hold `value`.
+    pub fn matches(&self, value: &Value, nullable: bool) -> bool {
+        match value {
+            Value::String(_) => self == &DataType::String,
+            Value::UInt(_) => self == &DataType::UInt,
+            Value::U64(_) => self == &DataType::U64,
+            Value::Int(_) => self == &DataType::Int,
+            Value::Real(_) => self == &DataType::Real,
+            Value::Bool(_) => self == &DataType::Boolean,
+            Value::Blob(_) | Value::Overflow { .. } => self == &DataType::Blob,
+            Value::Date(_) => self == &DataType::Date,
+            Value::Time(_) => self == &DataType::Time,
+            Value::Timestamp(_) => self == &DataType::Timestamp,
+            Value::Decimal(..) => matches!(self, DataType::Decimal { .. }),
+            Value::Json(_) => self == &DataType::Json,
+            Value::Uuid(_) => self == &DataType::Uuid,
+            Value::Null => self == &DataType::Null || nullable,
+            Value::Tuple(_) => false,
+        }
+    }
+}
+
+fn invalid(value: &str, name: &str, format: &str) -> Error {
+    Error::Validate(format!(
+        "'{}' is not a {}, expected {}.",
+        value, name, format
+    ))
+}
+
+/// An integer written as `12`, `12.0` or `1.2e1`, but not `12.5`.
+fn parse_whole(value: &str) -> Option<i128> {
+    if let Ok(whole) = value.parse::<i128>() {
+        return Some(whole);
+    }
+
+    let (value, scale) = decimal::parse(value)?;
+    let whole = decimal::rescale(value, scale, 0, Rounding::Down)?;
+    (decimal::rescale(whole, 0, scale, Rounding::Down)? == value).then_some(whole)
+}
+
+fn parse_hex(value: &str) -> Result<Vec<u8>, Error> {
+    let invalid = || Error::Validate(format!("'{}' is not a blob, expected hex digits.", value));
+
+    if !value.len().is_multiple_of(2) {
+        return Err(invalid());
+    }
+
+    (0..value.len())
+        .step_by(2)
+        .map(|x| {
+            value
+                .get(x..x + 2)
+                .and_then(|x| u8::from_str_radix(x, 16).ok())
+                .ok_or_else(invalid)
+        })
+        .collect()
+}
+
+impl Display for DataType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DataType::Decimal { precision, scale } => {
+                write!(f, "{}({}, {})", self.name(), precision, scale)
+            }
+            _ => write!(f, "{}", self.name()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_declaration() {
+        assert_eq!(
+            DataType::from_declaration("TEXT", &[]),
+            Some(DataType::String)
+        );
+        assert_eq!(
+            DataType::from_declaration("varchar", &[255]),
+            Some(DataType::String)
+        );
+        assert_eq!(
+            DataType::from_declaration("INTEGER", &[]),
+            Some(DataType::Int)
+        );
+        assert_eq!(DataType::from_declaration("integer", &[8]), None);
+        assert_eq!(DataType::from_declaration("money", &[]), None);
+
+        assert_eq!(DataType::from_code(0x02), DataType::U64);
+        assert_eq!(DataType::from_code(0x09), DataType::Null);
+        assert_eq!(DataType::String.to_string(), "string");
+
+        assert_eq!(
+            DataType::Blob.parse("deadBEEF").expect("Failed to parse"),
+            Value::Blob(vec![0xDE, 0xAD, 0xBE, 0xEF])
+        );
+        assert!(DataType::Blob.parse("abc").is_err());
+
+        let money = DataType::from_declaration("NUMERIC", &[10, 2]).expect("Failed to declare");
+        assert_eq!(money.to_string(), "decimal(10, 2)");
+        assert_eq!(DataType::from_declaration("decimal", &[2, 3]), None);
+        assert_eq!(DataType::from_declaration("decimal", &[39]), None);
+        assert_eq!(
+            money.parse_rounded("1.005", Rounding::HalfEven).ok(),
+            Some(Value::Decimal(100, 2))
+        );
+        assert_eq!(money.parse("1.005").ok(), Some(Value::Decimal(101, 2)));
+        assert!(money.parse("1,00").is_err());
+        assert!(DataType::Blob.parse("zz").is_err());
+
+        assert_eq!(
+            DataType::Real.parse("-1.5e3").ok(),
+            Some(Value::Real(-1500.0))
+        );
+        for value in ["inf", "-infinity", "NaN", "1e999"] {
+            assert!(DataType::Real.parse(value).is_err(), "{} was parsed", value);
+        }
+
+        assert_eq!(DataType::UInt.parse("12.0").ok(), Some(Value::UInt(12)));
+        assert_eq!(DataType::Int.parse("-1e3").ok(), Some(Value::Int(-1000)));
+        assert!(DataType::Int.parse("12.5").is_err());
+        assert!(DataType::UInt.parse("-1").is_err());
+        assert!(DataType::Real.parse("abc").is_err());
+    }
+}
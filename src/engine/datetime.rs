@@ -0,0 +1,326 @@
+//! Dates and times in UTC, shared by the temporal types and the date functions.
+//!
+//! A date is kept as days since 1970-01-01, a time as microseconds since midnight
+//! and a timestamp as microseconds since 1970-01-01T00:00:00Z.
+
+pub const MICROS_PER_SECOND: i64 = 1_000_000;
+pub const MICROS_PER_DAY: i64 = 86_400 * MICROS_PER_SECOND;
+
+/// Days since 1970-01-01 of a date in the proleptic gregorian calendar.
+pub fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let month = month as i64;
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Year, month and day of the date `days` after 1970-01-01.
+pub fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = (if mp < 10 { mp + 3 } else { mp - 9 }) as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    (year, month, day)
+}
+
+fn is_leap_year(year: i64) -> bool {
+    year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
+}
+
+fn days_in_month(year: i64, month: u32) -> u32 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Only years 0000 to 9999 can be written, so only those are kept.
+fn in_range(micros: i64) -> Option<i64> {
+    let min = days_from_civil(0, 1, 1) * MICROS_PER_DAY;
+    let max = days_from_civil(10_000, 1, 1) * MICROS_PER_DAY;
+    (min..max).contains(&micros).then_some(micros)
+}
+
+fn number(value: &str, digits: usize) -> Option<i64> {
+    if value.len() != digits || !value.bytes().all(|x| x.is_ascii_digit()) {
+        return None;
+    }
+    value.parse().ok()
+}
+
+/// `YYYY-MM-DD` as days since 1970-01-01.
+pub fn parse_date(value: &str) -> Option<i64> {
+    let mut parts = value.split('-');
+    let year = number(parts.next()?, 4)?;
+    let month = number(parts.next()?, 2)? as u32;
+    let day = number(parts.next()?, 2)? as u32;
+
+    if parts.next().is_some() || !(1..=12).contains(&month) {
+        return None;
+    }
+    if day == 0 || day > days_in_month(year, month) {
+        return None;
+    }
+
+    Some(days_from_civil(year, month, day))
+}
+
+/// `HH:MM`, `HH:MM:SS` or `HH:MM:SS.SSS` as microseconds since midnight.
+/// Digits of the fraction past microseconds are dropped.
+pub fn parse_time(value: &str) -> Option<i64> {
+    let (time, fraction) = match value.split_once('.') {
+        Some((time, fraction)) => (time, Some(fraction)),
+        None => (value, None),
+    };
+
+    let mut parts = time.split(':');
+    let hour = number(parts.next()?, 2)?;
+    let minute = number(parts.next()?, 2)?;
+    let second = match parts.next() {
+        Some(second) => number(second, 2)?,
+        None if fraction.is_none() => 0,
+        None => return None,
+    };
+
+    if parts.next().is_some() || hour > 23 || minute > 59 || second > 59 {
+        return None;
+    }
+
+    let micros = match fraction {
+        Some(fraction) => {
+            if fraction.is_empty() || !fraction.bytes().all(|x| x.is_ascii_digit()) {
+                return None;
+            }
+            format!("{:0<6}", &fraction[..fraction.len().min(6)])
+                .parse::<i64>()
+                .ok()?
+        }
+        None => 0,
+    };
+
+    Some(((hour * 60 + minute) * 60 + second) * MICROS_PER_SECOND + micros)
+}
+
+/// An ISO-8601 date and time, `2024-01-31`, `2024-01-31 13:45:00` or
+/// `2024-01-31T13:45:00.5+02:00`, as microseconds since 1970-01-01 in UTC.
+/// Times without an offset are in UTC. A whole number is taken as seconds since 1970-01-01.
+pub fn parse_timestamp(value: &str) -> Option<i64> {
+    if let Ok(seconds) = value.parse::<i64>() {
+        return in_range(seconds.checked_mul(MICROS_PER_SECOND)?);
+    }
+
+    let date = parse_date(value.get(..10)?)?;
+    let rest = &value[10..];
+
+    let mut micros = date * MICROS_PER_DAY;
+    if !rest.is_empty() {
+        let rest = rest
+            .strip_prefix('T')
+            .or_else(|| rest.strip_prefix('t'))
+            .or_else(|| rest.strip_prefix(' '))?;
+
+        let (time, offset) = if let Some(time) = rest.strip_suffix(['Z', 'z']) {
+            (time, 0)
+        } else if let Some(idx) = rest.find(['+', '-']) {
+            let sign = if rest[idx..].starts_with('-') { -1 } else { 1 };
+            let offset = parse_time(&rest[idx + 1..]).filter(|x| x % 60_000_000 == 0)?;
+            (&rest[..idx], sign * offset)
+        } else {
+            (rest, 0)
+        };
+
+        micros += parse_time(time)? - offset;
+    }
+
+    in_range(micros)
+}
+
+pub fn format_date(days: i64) -> String {
+    let (year, month, day) = civil_from_days(days);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// The fraction of a second is only written when there is one.
+pub fn format_time(micros: i64) -> String {
+    let seconds = micros / MICROS_PER_SECOND;
+    let fraction = micros % MICROS_PER_SECOND;
+
+    let mut output = format!(
+        "{:02}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    );
+    if fraction != 0 {
+        output.push_str(format!(".{:06}", fraction).trim_end_matches('0'));
+    }
+
+    output
+}
+
+/// ISO-8601 in UTC, `2024-01-31T13:45:00Z`.
+pub fn format_timestamp(micros: i64) -> String {
+    format!(
+        "{}T{}Z",
+        format_date(micros.div_euclid(MICROS_PER_DAY)),
+        format_time(micros.rem_euclid(MICROS_PER_DAY))
+    )
+}
+
+/// Format a timestamp like `strftime` of sqlite, `%Y-%m-%d %H:%M:%S`.
+pub fn strftime(format: &str, micros: i64) -> Option<String> {
+    let days = micros.div_euclid(MICROS_PER_DAY);
+    let time = micros.rem_euclid(MICROS_PER_DAY);
+    let (year, month, day) = civil_from_days(days);
+    let seconds = time / MICROS_PER_SECOND;
+
+    let mut output = String::new();
+    let mut chars = format.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            output.push(c);
+            continue;
+        }
+
+        let part = match chars.next()? {
+            'Y' => format!("{:04}", year),
+            'm' => format!("{:02}", month),
+            'd' => format!("{:02}", day),
+            'H' => format!("{:02}", seconds / 3600),
+            'M' => format!("{:02}", seconds / 60 % 60),
+            'S' => format!("{:02}", seconds % 60),
+            'f' => format!("{:02}.{:03}", seconds % 60, time % MICROS_PER_SECOND / 1000),
+            'j' => format!("{:03}", days - days_from_civil(year, 1, 1) + 1),
+            'w' => (days + 4).rem_euclid(7).to_string(),
+            's' => micros.div_euclid(MICROS_PER_SECOND).to_string(),
+            'F' => format_date(days),
+            'T' => format_time(time - time % MICROS_PER_SECOND),
+            '%' => "%".into(),
+            _ => return None,
+        };
+        output.push_str(&part);
+    }
+
+    Some(output)
+}
+
+/// Apply a modifier of the date functions to a timestamp, `+7 days`, `-1 month` or `start of month`.
+/// Adding months or years keeps the day, or the last day of a shorter month.
+pub fn apply_modifier(micros: i64, modifier: &str) -> Option<i64> {
+    let modifier = modifier.trim().to_lowercase();
+    let days = micros.div_euclid(MICROS_PER_DAY);
+    let (year, month, day) = civil_from_days(days);
+
+    let result = match modifier.as_str() {
+        "start of day" => days * MICROS_PER_DAY,
+        "start of month" => days_from_civil(year, month, 1) * MICROS_PER_DAY,
+        "start of year" => days_from_civil(year, 1, 1) * MICROS_PER_DAY,
+        _ => {
+            let (amount, unit) = modifier.split_once(char::is_whitespace)?;
+            if !amount.starts_with(['+', '-']) {
+                return None;
+            }
+            let amount = amount.parse::<f64>().ok().filter(|x| x.is_finite())?;
+
+            let unit_micros = match unit.trim().trim_end_matches('s') {
+                "day" => MICROS_PER_DAY,
+                "hour" => 3600 * MICROS_PER_SECOND,
+                "minute" => 60 * MICROS_PER_SECOND,
+                "second" => MICROS_PER_SECOND,
+                unit @ ("month" | "year") => {
+                    if amount.fract() != 0.0 || amount.abs() > 120_000.0 {
+                        return None;
+                    }
+                    let months = if unit == "year" { 12 } else { 1 } * amount as i64;
+
+                    let total = year * 12 + month as i64 - 1 + months;
+                    let (year, month) = (total.div_euclid(12), total.rem_euclid(12) as u32 + 1);
+                    let day = day.min(days_in_month(year, month));
+
+                    return in_range(
+                        days_from_civil(year, month, day) * MICROS_PER_DAY
+                            + micros.rem_euclid(MICROS_PER_DAY),
+                    );
+                }
+                _ => return None,
+            };
+
+            let delta = amount * unit_micros as f64;
+            if delta.abs() > i64::MAX as f64 / 2.0 {
+                return None;
+            }
+            micros.checked_add(delta.round() as i64)?
+        }
+    };
+
+    in_range(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_format() {
+        assert_eq!(parse_date("1970-01-01"), Some(0));
+        assert_eq!(
+            parse_date("2024-02-29").map(format_date).as_deref(),
+            Some("2024-02-29")
+        );
+        assert_eq!(parse_date("2023-02-29"), None);
+        assert_eq!(parse_date("2024-1-01"), None);
+
+        assert_eq!(parse_time("13:45"), parse_time("13:45:00"));
+        assert_eq!(parse_time("00:00:01.5"), Some(1_500_000));
+        assert_eq!(parse_time("24:00:00"), None);
+        assert_eq!(format_time(1_500_000), "00:00:01.5");
+
+        let expected = Some(1_706_708_700 * MICROS_PER_SECOND);
+        assert_eq!(parse_timestamp("2024-01-31 13:45:00"), expected);
+        assert_eq!(parse_timestamp("2024-01-31T15:45:00+02:00"), expected);
+        assert_eq!(parse_timestamp("2024-01-31T13:45Z"), expected);
+        assert_eq!(parse_timestamp("1706708700"), expected);
+        assert_eq!(parse_timestamp("2024-01-31X13:45"), None);
+        assert_eq!(parse_timestamp("9223372036854775807"), None);
+        assert_eq!(
+            format_timestamp(parse_timestamp("1969-12-31 23:59:59").unwrap_or_default()),
+            "1969-12-31T23:59:59Z"
+        );
+    }
+
+    #[test]
+    fn test_modifiers_and_strftime() {
+        let jan_31 = parse_timestamp("2024-01-31 13:45:00").unwrap_or_default();
+
+        let add = |modifier: &str| apply_modifier(jan_31, modifier).map(format_timestamp);
+        assert_eq!(add("+1 month").as_deref(), Some("2024-02-29T13:45:00Z"));
+        assert_eq!(add("-1 year").as_deref(), Some("2023-01-31T13:45:00Z"));
+        assert_eq!(add("+1.5 days").as_deref(), Some("2024-02-02T01:45:00Z"));
+        assert_eq!(add("-90 minutes").as_deref(), Some("2024-01-31T12:15:00Z"));
+        assert_eq!(
+            add("start of month").as_deref(),
+            Some("2024-01-01T00:00:00Z")
+        );
+        assert_eq!(add("+1 fortnight"), None);
+        assert_eq!(add("+10000 years"), None);
+
+        assert_eq!(
+            strftime("%Y/%m/%d %H:%M:%S %j %w %s %%", jan_31).as_deref(),
+            Some("2024/01/31 13:45:00 031 3 1706708700 %")
+        );
+        assert_eq!(strftime("%Q", jan_31), None);
+    }
+}
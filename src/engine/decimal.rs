@@ -0,0 +1,329 @@
+//! Exact fixed point numbers for `DECIMAL(p, s)` columns.
+//!
+//! A decimal is kept as an integer of its digits and a scale, the number of them after the point,
+//! so `12.50` is `1250` with a scale of 2.
+
+use std::{cmp::Ordering, fmt::Display, str::FromStr};
+
+use super::error::Error;
+
+/// Most digits a decimal can have, the most that always fit in an i128.
+pub const MAX_PRECISION: u8 = 38;
+
+/// How digits that do not fit the scale of a column are dropped, set with `PRAGMA rounding`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Rounding {
+    /// Halves are rounded away from zero, `2.5` is `3` and `-2.5` is `-3`.
+    #[default]
+    HalfUp,
+    /// Halves are rounded to the even digit, `2.5` is `2` and `3.5` is `4`.
+    HalfEven,
+    /// Towards zero, the digits are cut off.
+    Down,
+    /// Away from zero.
+    Up,
+    Floor,
+    Ceiling,
+}
+
+impl FromStr for Rounding {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "half_up" => Ok(Rounding::HalfUp),
+            "half_even" => Ok(Rounding::HalfEven),
+            "down" => Ok(Rounding::Down),
+            "up" => Ok(Rounding::Up),
+            "floor" => Ok(Rounding::Floor),
+            "ceiling" => Ok(Rounding::Ceiling),
+            _ => Err(Error::Validate(format!(
+                "Unknown rounding mode '{}', expected HALF_UP, HALF_EVEN, DOWN, UP, FLOOR or CEILING.",
+                value
+            ))),
+        }
+    }
+}
+
+impl Display for Rounding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Rounding::HalfUp => write!(f, "HALF_UP"),
+            Rounding::HalfEven => write!(f, "HALF_EVEN"),
+            Rounding::Down => write!(f, "DOWN"),
+            Rounding::Up => write!(f, "UP"),
+            Rounding::Floor => write!(f, "FLOOR"),
+            Rounding::Ceiling => write!(f, "CEILING"),
+        }
+    }
+}
+
+fn power(exponent: u32) -> Option<i128> {
+    10i128.checked_pow(exponent)
+}
+
+/// Number of digits of `value`, without the sign.
+pub fn digits(value: i128) -> u32 {
+    value.unsigned_abs().checked_ilog10().unwrap_or(0) + 1
+}
+
+/// Parse `12`, `-12.50` or `1.5e3` exactly, keeping every digit that is written.
+pub fn parse(value: &str) -> Option<(i128, u8)> {
+    let (number, exponent) = match value.split_once(['e', 'E']) {
+        Some((number, exponent)) => (number, exponent.parse::<i32>().ok()?),
+        None => (value, 0),
+    };
+
+    let (negative, number) = match number.strip_prefix('-') {
+        Some(number) => (true, number),
+        None => (false, number.strip_prefix('+').unwrap_or(number)),
+    };
+    let (whole, fraction) = number.split_once('.').unwrap_or((number, ""));
+
+    let all_digits = |x: &str| x.bytes().all(|x| x.is_ascii_digit());
+    if whole.is_empty() || !all_digits(whole) || !all_digits(fraction) {
+        return None;
+    }
+
+    let digits = format!("{}{}", whole, fraction);
+    let digits = digits.trim_start_matches('0');
+    if digits.len() > MAX_PRECISION as usize {
+        return None;
+    }
+
+    let mut result = if digits.is_empty() {
+        0
+    } else {
+        digits.parse::<i128>().ok()?
+    };
+    let mut scale = fraction.len() as i32 - exponent;
+    if scale < 0 {
+        result = result.checked_mul(power(scale.unsigned_abs())?)?;
+        scale = 0;
+    }
+    if scale > MAX_PRECISION as i32 || digits_fit(result).is_none() {
+        return None;
+    }
+
+    Some((if negative { -result } else { result }, scale as u8))
+}
+
+fn digits_fit(value: i128) -> Option<i128> {
+    (digits(value) <= MAX_PRECISION as u32).then_some(value)
+}
+
+/// `value / divisor` rounded to a whole number, the divisor is never zero.
+fn round_div(value: i128, divisor: i128, rounding: Rounding) -> i128 {
+    let (value, divisor) = if divisor < 0 {
+        (-value, -divisor)
+    } else {
+        (value, divisor)
+    };
+
+    let quotient = value / divisor;
+    let remainder = (value % divisor).abs();
+    if remainder == 0 {
+        return quotient;
+    }
+
+    // compared without doubling the remainder, which could overflow.
+    let half = remainder.cmp(&(divisor - remainder));
+    let away = match rounding {
+        Rounding::Down => false,
+        Rounding::Up => true,
+        Rounding::Floor => value < 0,
+        Rounding::Ceiling => value > 0,
+        Rounding::HalfUp => half != Ordering::Less,
+        Rounding::HalfEven => {
+            half == Ordering::Greater || (half == Ordering::Equal && quotient % 2 != 0)
+        }
+    };
+
+    if away {
+        quotient + value.signum()
+    } else {
+        quotient
+    }
+}
+
+/// The value with `scale` digits after the point instead of `from`.
+pub fn rescale(value: i128, from: u8, scale: u8, rounding: Rounding) -> Option<i128> {
+    match scale.cmp(&from) {
+        Ordering::Equal => Some(value),
+        Ordering::Greater => value.checked_mul(power((scale - from) as u32)?),
+        Ordering::Less => Some(round_div(value, power((from - scale) as u32)?, rounding)),
+    }
+}
+
+pub fn format(value: i128, scale: u8) -> String {
+    let digits = value.unsigned_abs().to_string();
+    let sign = if value < 0 { "-" } else { "" };
+    let scale = scale as usize;
+
+    if scale == 0 {
+        return format!("{}{}", sign, digits);
+    }
+
+    let digits = format!("{:0>width$}", digits, width = scale + 1);
+    let (whole, fraction) = digits.split_at(digits.len() - scale);
+    format!("{}{}.{}", sign, whole, fraction)
+}
+
+pub fn to_f64(value: i128, scale: u8) -> f64 {
+    format(value, scale).parse().unwrap_or(f64::NAN)
+}
+
+/// Compare exactly whatever the scales, by the whole part and then the fraction.
+pub fn compare(a: (i128, u8), b: (i128, u8)) -> Ordering {
+    let (whole_a, fraction_a) = split(a);
+    let (whole_b, fraction_b) = split(b);
+
+    let scale = a.1.max(b.1);
+    let widen = |fraction: i128, from: u8| fraction * power((scale - from) as u32).unwrap_or(1);
+
+    whole_a
+        .cmp(&whole_b)
+        .then_with(|| widen(fraction_a, a.1).cmp(&widen(fraction_b, b.1)))
+}
+
+/// Compare exactly with a real, a NaN is larger than every decimal like it is for reals.
+pub fn compare_f64(a: (i128, u8), b: f64) -> Ordering {
+    // the nearest real keeps the order, only a decimal the real can not be told apart
+    // from has to be compared digit by digit.
+    match to_f64(a.0, a.1).partial_cmp(&b) {
+        Some(Ordering::Equal) => {}
+        Some(order) => return order,
+        None => return Ordering::Less,
+    }
+
+    let (whole, fraction) = split(a);
+    let whole_b = b.floor();
+
+    whole.cmp(&(whole_b as i128)).then_with(|| {
+        // the fraction of a real always ends, after at most 1074 digits.
+        let digits = format!("{:.1074}", b - whole_b);
+        let (head, rest) = digits[2..].split_at(a.1 as usize);
+        let fraction = match a.1 {
+            0 => String::new(),
+            scale => format!("{:0>width$}", fraction, width = scale as usize),
+        };
+
+        fraction
+            .as_str()
+            .cmp(head)
+            .then_with(|| match rest.bytes().any(|x| x != b'0') {
+                true => Ordering::Less,
+                false => Ordering::Equal,
+            })
+    })
+}
+
+/// The whole part rounded down and the digits after the point, which are never negative.
+fn split((value, scale): (i128, u8)) -> (i128, i128) {
+    let unit = power(scale as u32).unwrap_or(1);
+    (value.div_euclid(unit), value.rem_euclid(unit))
+}
+
+/// Add or subtract exactly, with the larger scale of the two.
+pub fn add(a: (i128, u8), b: (i128, u8)) -> Option<(i128, u8)> {
+    let scale = a.1.max(b.1);
+    let a = rescale(a.0, a.1, scale, Rounding::Down)?;
+    let b = rescale(b.0, b.1, scale, Rounding::Down)?;
+    Some((digits_fit(a.checked_add(b)?)?, scale))
+}
+
+pub fn multiply(a: (i128, u8), b: (i128, u8)) -> Option<(i128, u8)> {
+    let scale = a.1.checked_add(b.1).filter(|x| *x <= MAX_PRECISION)?;
+    Some((digits_fit(a.0.checked_mul(b.0)?)?, scale))
+}
+
+/// `a / b` rounded to `scale` digits after the point, `None` when dividing by zero.
+pub fn divide(a: (i128, u8), b: (i128, u8), scale: u8, rounding: Rounding) -> Option<(i128, u8)> {
+    if b.0 == 0 {
+        return None;
+    }
+
+    // a / 10^sa / (b / 10^sb) in units of 10^-scale.
+    let exponent = b.1 as i32 + scale as i32 - a.1 as i32;
+    let result = if exponent >= 0 {
+        round_div(a.0.checked_mul(power(exponent as u32)?)?, b.0, rounding)
+    } else {
+        round_div(
+            a.0,
+            b.0.checked_mul(power(exponent.unsigned_abs())?)?,
+            rounding,
+        )
+    };
+
+    Some((digits_fit(result)?, scale))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_format() {
+        assert_eq!(parse("12.50"), Some((1250, 2)));
+        assert_eq!(parse("-0.05"), Some((-5, 2)));
+        assert_eq!(parse("1.5e3"), Some((1500, 0)));
+        assert_eq!(parse("25e-3"), Some((25, 3)));
+        assert_eq!(parse("1."), Some((1, 0)));
+        assert_eq!(parse(".5"), None);
+        assert_eq!(parse("1e40"), None);
+        assert_eq!(parse("abc"), None);
+
+        assert_eq!(format(1250, 2), "12.50");
+        assert_eq!(format(-5, 3), "-0.005");
+        assert_eq!(format(7, 0), "7");
+        assert_eq!(digits(-999), 3);
+        assert_eq!(digits(0), 1);
+    }
+
+    #[test]
+    fn test_rounding() {
+        let round = |value: i128, rounding: Rounding| rescale(value, 1, 0, rounding);
+
+        assert_eq!(round(25, Rounding::HalfUp), Some(3));
+        assert_eq!(round(-25, Rounding::HalfUp), Some(-3));
+        assert_eq!(round(25, Rounding::HalfEven), Some(2));
+        assert_eq!(round(35, Rounding::HalfEven), Some(4));
+        assert_eq!(round(-27, Rounding::Down), Some(-2));
+        assert_eq!(round(21, Rounding::Up), Some(3));
+        assert_eq!(round(-21, Rounding::Floor), Some(-3));
+        assert_eq!(round(-21, Rounding::Ceiling), Some(-2));
+        assert_eq!(
+            "half_even".parse::<Rounding>().ok(),
+            Some(Rounding::HalfEven)
+        );
+        assert!("bankers".parse::<Rounding>().is_err());
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        assert_eq!(compare((1250, 2), (125, 1)), Ordering::Equal);
+        assert_eq!(compare((-5, 2), (0, 0)), Ordering::Less);
+        assert_eq!(compare((-15, 1), (-1, 0)), Ordering::Less);
+        assert_eq!(compare((i128::MAX / 10, 38), (1, 0)), Ordering::Less);
+
+        // 0.1 as a real is a little more than 0.1.
+        assert_eq!(compare_f64((1, 1), 0.1), Ordering::Less);
+        assert_eq!(compare_f64((-15, 1), -1.5), Ordering::Equal);
+        assert_eq!(compare_f64((250, 2), 2.5), Ordering::Equal);
+        assert_eq!(
+            compare_f64((9007199254740993, 0), 9007199254740992.0),
+            Ordering::Greater
+        );
+        assert_eq!(compare_f64((1, 38), 0.0), Ordering::Greater);
+        assert_eq!(compare_f64((1, 0), f64::NAN), Ordering::Less);
+        assert_eq!(compare_f64((i128::MAX, 0), f64::INFINITY), Ordering::Less);
+
+        // 0.1 + 0.2 is exactly 0.3, unlike with floats.
+        assert_eq!(add((1, 1), (2, 1)), Some((3, 1)));
+        assert_eq!(add((1999, 2), (-5, 0)), Some((1499, 2)));
+        assert_eq!(multiply((150, 2), (3, 1)), Some((450, 3)));
+        assert_eq!(divide((100, 2), (3, 0), 2, Rounding::HalfUp), Some((33, 2)));
+        assert_eq!(divide((200, 2), (3, 0), 2, Rounding::HalfUp), Some((67, 2)));
+        assert_eq!(divide((1, 0), (0, 2), 2, Rounding::HalfUp), None);
+    }
+}
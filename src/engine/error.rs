@@ -1,4 +1,4 @@
-use std::num::ParseIntError;
+use std::num::{ParseFloatError, ParseIntError};
 
 use thiserror::Error;
 #[derive(Debug, Error)]
@@ -26,6 +26,10 @@ pub enum Error {
 
     #[error("Parse Int Error: {0}")]
     ParseInt(#[from] ParseIntError),
+    #[error("Parse Float Error: {0}")]
+    ParseFloat(#[from] ParseFloatError),
     #[error("Failed to lock")]
     Lock,
+    #[error("database is locked")]
+    Busy,
 }
@@ -0,0 +1,99 @@
+use std::{
+    fs::{File, TryLockError},
+    thread,
+    time::{Duration, Instant},
+};
+
+use super::error::Error;
+
+/// How often a blocked lock request re-checks the file while waiting for the busy timeout.
+const BUSY_RETRY_INTERVAL: Duration = Duration::from_millis(10);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockMode {
+    /// Held by readers, any number of processes can hold it at once.
+    Shared,
+    /// Held by a single writer, no other process can hold any lock on the file.
+    Exclusive,
+}
+
+/// Take an OS level advisory lock on the given file.
+/// If another process holds a conflicting lock, the call keeps retrying
+/// until `timeout` has passed and then returns `Error::Busy`.
+pub fn lock_file(file: &File, mode: LockMode, timeout: Duration) -> Result<(), Error> {
+    retry_lock(timeout, || {
+        let result = match mode {
+            LockMode::Shared => file.try_lock_shared(),
+            LockMode::Exclusive => file.try_lock(),
+        };
+
+        match result {
+            Ok(()) => Ok(true),
+            Err(TryLockError::WouldBlock) => Ok(false),
+            Err(TryLockError::Error(err)) => Err(Error::Io(err)),
+        }
+    })
+}
+
+/// Call `try_lock` until it returns true, or return `Error::Busy` once `timeout` has passed.
+pub fn retry_lock(
+    timeout: Duration,
+    mut try_lock: impl FnMut() -> Result<bool, Error>,
+) -> Result<(), Error> {
+    let started = Instant::now();
+
+    loop {
+        if try_lock()? {
+            return Ok(());
+        }
+
+        let elapsed = started.elapsed();
+        if elapsed >= timeout {
+            return Err(Error::Busy);
+        }
+
+        thread::sleep(BUSY_RETRY_INTERVAL.min(timeout - elapsed));
+    }
+}
+
+pub fn unlock_file(file: &File) -> Result<(), Error> {
+    file.unlock()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{lock_file, LockMode};
+    use crate::engine::error::Error;
+    use std::{fs::OpenOptions, time::Duration};
+
+    #[test]
+    fn test_exclusive_lock_is_busy() {
+        let path = std::env::temp_dir().join("rust_database_file_lock.bin");
+        let open = || {
+            OpenOptions::new()
+                .create(true)
+                .read(true)
+                .write(true)
+                .truncate(false)
+                .open(&path)
+                .expect("Failed to open lock file")
+        };
+
+        let (first, second) = (open(), open());
+
+        lock_file(&first, LockMode::Shared, Duration::ZERO).expect("Failed to lock");
+        lock_file(&second, LockMode::Shared, Duration::ZERO).expect("Failed to share lock");
+
+        super::unlock_file(&second).expect("Failed to unlock");
+
+        match lock_file(&second, LockMode::Exclusive, Duration::from_millis(20)) {
+            Err(Error::Busy) => {}
+            other => panic!("Expected busy error, got {:?}", other),
+        }
+
+        super::unlock_file(&first).expect("Failed to unlock");
+
+        lock_file(&second, LockMode::Exclusive, Duration::ZERO).expect("Failed to lock");
+    }
+}
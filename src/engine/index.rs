@@ -0,0 +1,235 @@
+use std::ops::Bound;
+
+use super::{
+    btree::BTree,
+    error::Error,
+    node_type::Schema,
+    structure::{ConditionValue, Operation, Record, Value},
+};
+
+/// A secondary index of a table.
+///
+/// The index is a tree of its own. Each of its rows holds the values of the
+/// indexed columns followed by the primary key columns of the table row, and
+/// the rows are ordered by all of them.
+pub struct Index {
+    pub name: String,
+    /// Columns of the table, in the order they are indexed.
+    pub columns: Vec<usize>,
+    pub unique: bool,
+    pub tree: BTree,
+}
+
+/// Range of index rows that can match a `WHERE` clause.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Seek {
+    /// Number of indexed columns the bounds narrow down.
+    pub columns: usize,
+    /// Number of leading columns compared with `=`, a range on the next one may follow.
+    pub equal: usize,
+    pub lower: Bound<Vec<Value>>,
+    pub upper: Bound<Vec<Value>>,
+}
+
+impl Index {
+    pub fn new(name: String, columns: Vec<usize>, unique: bool, tree: BTree) -> Self {
+        Self {
+            name,
+            columns,
+            unique,
+            tree,
+        }
+    }
+
+    /// Open the index stored in `tree`, finding its columns in the schema of `table`.
+    pub fn open(mut tree: BTree, table: &Schema) -> Result<Self, Error> {
+        let schema = tree.get_table()?;
+        let indexed = &schema.columns[..schema.primary_key];
+
+        let names: Vec<String> = indexed.iter().map(|x| x.name.clone()).collect();
+        let columns = Self::column_indexes(table, &names)?;
+        let unique = indexed.first().is_some_and(|x| x.unique);
+
+        Ok(Self::new(schema.name, columns, unique, tree))
+    }
+
+    /// Schema of the tree of a new index on `columns` of `table`. The primary key
+    /// columns of the table are stored after them.
+    pub fn schema(
+        name: &str,
+        table: &Schema,
+        columns: &[String],
+        unique: bool,
+    ) -> Result<Schema, Error> {
+        let mut defs = vec![];
+        for idx in Self::column_indexes(table, columns)? {
+            let mut def = table.columns[idx].clone();
+            def.unique = unique;
+            def.autoincrement = false;
+            defs.push(def);
+        }
+
+        let indexed = defs.len();
+        for idx in table.key_columns() {
+            let key = table
+                .columns
+                .get(idx)
+                .cloned()
+                .ok_or(Error::UnexpectedWithReason("Table has no primary key."))?;
+            defs.push(key);
+        }
+
+        Ok(Schema::new(name.to_owned(), indexed, defs, None))
+    }
+
+    /// Indexes of the table columns named in `columns`.
+    pub fn column_indexes(table: &Schema, columns: &[String]) -> Result<Vec<usize>, Error> {
+        if columns.is_empty() {
+            return Err(Error::Validate(
+                "An index needs at least one column.".into(),
+            ));
+        }
+
+        columns
+            .iter()
+            .map(|column| {
+                table.get_column_idx_by_name(column).ok_or_else(|| {
+                    Error::Validate(format!(
+                        "Table '{}' has no column named '{}'.",
+                        table.name, column
+                    ))
+                })
+            })
+            .collect()
+    }
+
+    /// Values of the indexed columns of a table row.
+    pub fn values(&self, row: &Record) -> Result<Vec<Value>, Error> {
+        self.columns.iter().map(|idx| row.get_key(*idx)).collect()
+    }
+
+    /// Row of the index pointing at a table row.
+    pub fn entry(&self, table: &Schema, row: &Record) -> Result<Record, Error> {
+        let mut values = self.values(row)?;
+        for idx in table.key_columns() {
+            values.push(row.get_key(idx)?);
+        }
+        Ok(Record(values))
+    }
+
+    /// Key the index row of a table row is stored under.
+    pub fn key(&self, table: &Schema, row: &Record) -> Result<Value, Error> {
+        Ok(Value::Tuple(self.entry(table, row)?.0))
+    }
+
+    /// Primary key of the table row an index row points at.
+    pub fn primary_key(&self, entry: &Record) -> Result<Value, Error> {
+        Self::entry_primary_key(self.columns.len(), entry)
+    }
+
+    /// Like `primary_key`, for an index on `columns` columns, while its tree is in use.
+    pub fn entry_primary_key(columns: usize, entry: &Record) -> Result<Value, Error> {
+        match entry.0.get(columns..) {
+            Some([key]) => Ok(key.clone()),
+            Some(key) if !key.is_empty() => Ok(Value::Tuple(key.to_vec())),
+            _ => Err(Error::UnexpectedWithReason("Index row has no primary key.")),
+        }
+    }
+
+    /// Index rows with the given values. Like in SQL, a unique index allows
+    /// any number of rows with a null value, so those are never found.
+    pub fn find(&mut self, values: Vec<Value>) -> Result<Vec<Record>, Error> {
+        if values.contains(&Value::Null) {
+            return Ok(vec![]);
+        }
+
+        let bound = Bound::Included(values);
+        self.tree.range(&bound, &bound)
+    }
+
+    /// Error for a value already used in a unique index.
+    pub fn unique_error(&self, values: &[Value]) -> Error {
+        let values: Vec<String> = values.iter().map(|x| x.to_string()).collect();
+        Error::Validate(format!(
+            "UNIQUE constraint failed: {} ({})",
+            self.name,
+            values.join(", ")
+        ))
+    }
+
+    /// Range of the index that holds every row matching `selection`, when the
+    /// selection has an equality or range predicate on the leading indexed columns.
+    /// The rows in the range still have to be checked against the whole selection.
+    pub fn seek(&self, selection: &[ConditionValue]) -> Option<Seek> {
+        // with an OR any row could match.
+        if selection.contains(&ConditionValue::OR) {
+            return None;
+        }
+
+        let predicates = |column: usize| {
+            selection.iter().filter_map(move |x| match x {
+                ConditionValue::Value {
+                    invert: false,
+                    idx,
+                    opt,
+                    values,
+                } if *idx == column => Some((opt, values)),
+                _ => None,
+            })
+        };
+
+        let mut prefix = vec![];
+        let mut lower = None;
+        let mut upper = None;
+
+        // equal values narrow down the next column, a range ends the prefix.
+        for column in &self.columns {
+            if let Some((_, values)) = predicates(*column).find(|x| x.0 == &Operation::Equal) {
+                prefix.push(values[0].clone());
+                continue;
+            }
+
+            for (opt, values) in predicates(*column) {
+                match opt {
+                    Operation::GT => lower = Some(Bound::Excluded(values[0].clone())),
+                    Operation::GTE => lower = Some(Bound::Included(values[0].clone())),
+                    Operation::LT => upper = Some(Bound::Excluded(values[0].clone())),
+                    Operation::LTE => upper = Some(Bound::Included(values[0].clone())),
+                    Operation::BETWEEN => {
+                        lower = Some(Bound::Excluded(values[0].clone()));
+                        upper = Some(Bound::Excluded(values[1].clone()));
+                    }
+                    Operation::Equal | Operation::LIKE => {}
+                }
+            }
+            break;
+        }
+
+        let bound = |range: Option<Bound<Value>>| {
+            let with = |value: Value| {
+                let mut values = prefix.clone();
+                values.push(value);
+                values
+            };
+
+            match range {
+                Some(Bound::Included(value)) => Bound::Included(with(value)),
+                Some(Bound::Excluded(value)) => Bound::Excluded(with(value)),
+                _ if prefix.is_empty() => Bound::Unbounded,
+                _ => Bound::Included(prefix.clone()),
+            }
+        };
+
+        let columns = prefix.len() + usize::from(lower.is_some() || upper.is_some());
+        if columns == 0 {
+            return None;
+        }
+
+        Some(Seek {
+            columns,
+            equal: prefix.len(),
+            lower: bound(lower),
+            upper: bound(upper),
+        })
+    }
+}
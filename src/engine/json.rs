@@ -0,0 +1,254 @@
+//! Documents of `JSON` columns and the functions reading them.
+//!
+//! Paths are written like in sqlite, `$` is the whole document, `.name` or `."a name"` a key of an
+//! object and `[0]` an element of an array. `[#]` is the end of an array, for appending with `json_set`.
+
+use serde_json::Value as Json;
+
+use super::{error::Error, structure::Value};
+
+#[derive(Debug, PartialEq)]
+enum Step {
+    Key(String),
+    Index(usize),
+    End,
+}
+
+/// Parse `text`, the error names the text.
+pub fn parse(text: &str) -> Result<Json, Error> {
+    serde_json::from_str(text)
+        .map_err(|_| Error::Validate(format!("'{}' is not valid JSON.", text)))
+}
+
+/// `text` without whitespace, how a document is stored.
+pub fn minify(text: &str) -> Result<String, Error> {
+    Ok(parse(text)?.to_string())
+}
+
+fn parse_path(path: &str) -> Result<Vec<Step>, Error> {
+    let invalid = || Error::Validate(format!("Invalid JSON path '{}'.", path));
+
+    let mut rest = path.strip_prefix('$').ok_or_else(invalid)?;
+    let mut steps = vec![];
+
+    while !rest.is_empty() {
+        if let Some(quoted) = rest.strip_prefix(".\"") {
+            let end = quoted.find('"').ok_or_else(invalid)?;
+            steps.push(Step::Key(quoted[..end].to_owned()));
+            rest = &quoted[end + 1..];
+        } else if let Some(key) = rest.strip_prefix('.') {
+            let end = key.find(['.', '[']).unwrap_or(key.len());
+            if end == 0 {
+                return Err(invalid());
+            }
+            steps.push(Step::Key(key[..end].to_owned()));
+            rest = &key[end..];
+        } else if let Some(index) = rest.strip_prefix('[') {
+            let end = index.find(']').ok_or_else(invalid)?;
+            steps.push(match &index[..end] {
+                "#" => Step::End,
+                value => Step::Index(value.parse().map_err(|_| invalid())?),
+            });
+            rest = &index[end + 1..];
+        } else {
+            return Err(invalid());
+        }
+    }
+
+    Ok(steps)
+}
+
+fn step<'a>(document: &'a Json, step: &Step) -> Option<&'a Json> {
+    match step {
+        Step::Key(key) => document.as_object()?.get(key),
+        Step::Index(index) => document.as_array()?.get(*index),
+        Step::End => None,
+    }
+}
+
+/// Part of `document` at `path`, `None` when there is nothing there.
+pub fn extract<'a>(document: &'a Json, path: &str) -> Result<Option<&'a Json>, Error> {
+    Ok(parse_path(path)?
+        .iter()
+        .try_fold(document, |document, x| step(document, x)))
+}
+
+/// Set the part of `document` at `path` to `value`, adding it when only its last step is missing.
+/// Like in sqlite a path whose parent does not exist leaves the document as it is.
+pub fn set(document: &mut Json, path: &str, value: Json) -> Result<(), Error> {
+    let steps = parse_path(path)?;
+    let Some((last, parents)) = steps.split_last() else {
+        *document = value;
+        return Ok(());
+    };
+
+    let mut parent = Some(document);
+    for x in parents {
+        parent = parent.and_then(|document| match x {
+            Step::Key(key) => document.as_object_mut()?.get_mut(key),
+            Step::Index(index) => document.as_array_mut()?.get_mut(*index),
+            Step::End => None,
+        });
+    }
+
+    match (parent, last) {
+        (Some(Json::Object(object)), Step::Key(key)) => {
+            object.insert(key.to_owned(), value);
+        }
+        (Some(Json::Array(array)), Step::Index(index)) if *index < array.len() => {
+            array[*index] = value;
+        }
+        (Some(Json::Array(array)), Step::Index(index)) if *index == array.len() => {
+            array.push(value);
+        }
+        (Some(Json::Array(array)), Step::End) => array.push(value),
+        _ => {}
+    }
+
+    Ok(())
+}
+
+/// A literal given to a function, JSON when it is and a string otherwise, so `"5"` is a number
+/// and `"Bob"` a string.
+pub fn literal(text: &str) -> Json {
+    serde_json::from_str(text).unwrap_or_else(|_| Json::String(text.to_owned()))
+}
+
+/// A value of a row for a part of a document, objects and arrays stay JSON.
+pub fn to_value(document: &Json) -> Value {
+    match document {
+        Json::Null => Value::Null,
+        Json::Bool(v) => Value::Bool(*v),
+        Json::Number(v) => match (v.as_i64(), v.as_u64()) {
+            (Some(v), _) => Value::Int(v),
+            (None, Some(v)) => Value::U64(v),
+            _ => Value::Real(v.as_f64().unwrap_or(f64::NAN)),
+        },
+        Json::String(v) => Value::String(v.to_owned()),
+        Json::Array(_) | Json::Object(_) => Value::Json(document.to_string()),
+    }
+}
+
+pub fn is_function(name: &str) -> bool {
+    matches!(
+        name.to_lowercase().as_str(),
+        "json" | "json_extract" | "json_set" | "json_array_length"
+    )
+}
+
+/// Call the JSON function `name` on `document`, the value of a JSON or string column, with the
+/// literals after it. A null document gives null.
+pub fn call(name: &str, document: &Value, args: &[String]) -> Result<Value, Error> {
+    let mut document = match document {
+        Value::Json(text) | Value::String(text) => parse(text)?,
+        Value::Null => return Ok(Value::Null),
+        value => {
+            return Err(Error::Validate(format!(
+                "{} expects JSON, not {}.",
+                name, value
+            )))
+        }
+    };
+
+    let result = match (name.to_lowercase().as_str(), args) {
+        ("json", []) => Value::Json(document.to_string()),
+        ("json_extract", [path]) => extract(&document, path)?.map_or(Value::Null, to_value),
+        // several paths give an array of what each of them points to.
+        ("json_extract", paths) if !paths.is_empty() => {
+            let values = paths
+                .iter()
+                .map(|x| Ok(extract(&document, x)?.cloned().unwrap_or(Json::Null)))
+                .collect::<Result<Vec<Json>, Error>>()?;
+            Value::Json(Json::Array(values).to_string())
+        }
+        ("json_set", pairs) if pairs.len() % 2 == 0 => {
+            for pair in pairs.chunks(2) {
+                set(&mut document, &pair[0], literal(&pair[1]))?;
+            }
+            Value::Json(document.to_string())
+        }
+        ("json_array_length", [] | [_]) => {
+            let path = args.first().map_or("$", |x| x.as_str());
+            match extract(&document, path)? {
+                Some(Json::Array(array)) => Value::Int(array.len() as i64),
+                Some(_) => Value::Int(0),
+                None => Value::Null,
+            }
+        }
+        (name, _) if is_function(name) => {
+            return Err(Error::Validate(format!(
+                "Wrong number of arguments for {}.",
+                name
+            )))
+        }
+        (name, _) => return Err(Error::Validate(format!("Unknown function '{}'.", name))),
+    };
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_paths() {
+        let mut document = parse(r#"{"a": {"b": [1, 2.5, "x"]}, "c d": null}"#).expect("Failed");
+
+        assert_eq!(
+            extract(&document, "$.a.b[2]").expect("Failed"),
+            Some(&Json::String("x".into()))
+        );
+        assert_eq!(
+            extract(&document, "$.\"c d\"").expect("Failed"),
+            Some(&Json::Null)
+        );
+        assert_eq!(extract(&document, "$.a.z").expect("Failed"), None);
+        assert!(extract(&document, "a.b").is_err());
+        assert!(extract(&document, "$.a[x]").is_err());
+
+        set(&mut document, "$.a.b[#]", Json::Bool(true)).expect("Failed");
+        set(&mut document, "$.e", literal("{\"f\":1}")).expect("Failed");
+        set(&mut document, "$.missing.g", literal("1")).expect("Failed");
+        assert_eq!(
+            document.to_string(),
+            r#"{"a":{"b":[1,2.5,"x",true]},"c d":null,"e":{"f":1}}"#
+        );
+    }
+
+    #[test]
+    fn test_call() {
+        let document = Value::Json(minify(r#"{ "name": "Ada", "tags": [1, 2] }"#).expect("Failed"));
+        let call = |name: &str, args: &[&str]| {
+            let args: Vec<String> = args.iter().map(|x| x.to_string()).collect();
+            call(name, &document, &args).expect("Failed to call")
+        };
+
+        assert_eq!(
+            call("json_extract", &["$.name"]),
+            Value::String("Ada".into())
+        );
+        assert_eq!(call("json_extract", &["$.tags[1]"]), Value::Int(2));
+        assert_eq!(
+            call("json_extract", &["$.tags"]),
+            Value::Json("[1,2]".into())
+        );
+        assert_eq!(
+            call("json_extract", &["$.name", "$.none"]),
+            Value::Json(r#"["Ada",null]"#.into())
+        );
+        assert_eq!(call("json_array_length", &["$.tags"]), Value::Int(2));
+        assert_eq!(call("json_array_length", &[]), Value::Int(0));
+        assert_eq!(
+            call("JSON_SET", &["$.name", "Bob", "$.age", "30"]),
+            Value::Json(r#"{"age":30,"name":"Bob","tags":[1,2]}"#.into())
+        );
+
+        assert!(super::call("json_set", &document, &["$.a".into()]).is_err());
+        assert!(super::call("json_extract", &Value::Int(1), &["$".into()]).is_err());
+        assert_eq!(
+            super::call("json_extract", &Value::Null, &["$".into()]).expect("Failed"),
+            Value::Null
+        );
+    }
+}
@@ -1,17 +1,35 @@
 pub mod btree;
+pub mod catalog;
+pub mod cursor;
+pub mod data_type;
+pub mod datetime;
+pub mod decimal;
 pub mod error;
+pub mod file_lock;
+pub mod index;
+pub mod json;
 mod node;
 pub mod node_type;
+mod overflow;
 mod page;
+pub mod page_cache;
 mod page_layout;
 mod pager;
+pub mod planner;
+pub mod stats;
 pub mod structure;
+pub mod synchronous;
+pub mod table;
+pub mod uuid;
+pub mod vfs;
 mod wal;
 
 #[cfg(test)]
 mod tests {
     use serde::{Deserialize, Serialize};
-    use std::os::windows::prelude::FileExt;
+    use std::fs::File;
+    use std::io::{Read, Seek, SeekFrom, Write};
+    use std::path::{Path, PathBuf};
 
     #[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
     enum Test {
@@ -21,6 +39,30 @@ mod tests {
     #[derive(Serialize, Deserialize, PartialEq, Debug)]
     struct Record(Vec<Test>);
 
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("rust_database_{}", name))
+    }
+
+    fn seek_write(fd: &mut File, buf: &[u8], offset: u64) {
+        fd.seek(SeekFrom::Start(offset)).unwrap();
+        fd.write_all(buf).unwrap();
+    }
+
+    fn seek_read(fd: &mut File, buf: &mut [u8], offset: u64) {
+        fd.seek(SeekFrom::Start(offset)).unwrap();
+        fd.read_exact(buf).unwrap();
+    }
+
+    fn open(path: &Path) -> File {
+        std::fs::OpenOptions::new()
+            .create(true)
+            .read(true)
+            .write(true)
+            .truncate(true)
+            .open(path)
+            .unwrap()
+    }
+
     #[test]
     fn test_encode_enum() {
         let config = bincode::config::standard();
@@ -29,101 +71,105 @@ mod tests {
         let encoded: Vec<u8> = bincode::serde::encode_to_vec(&item, config).unwrap();
 
         println!("Enum Len {}", encoded.len());
-        let mut fd = std::fs::OpenOptions::new()
-            .create(true)
-            .read(true)
-            .write(true)
-            .truncate(true)
-            .open("./enum_bin.bin")
-            .unwrap();
+        let path = temp_path("enum_bin.bin");
+        let mut fd = open(&path);
 
-        fd.seek_write(&encoded, 0).unwrap();
+        seek_write(&mut fd, &encoded, 0);
 
-        //let mut buf = [0; 4];
-        // fd.seek(SeekFrom::Start(0)).unwrap();
-        //fd.read_exact(&mut buf).unwrap();
-        //let decoded: (Test, usize) = bincode::serde::decode_from_slice(&encoded, config).unwrap();
-        //println!("{:#?}", decoded);
+        let mut buf = vec![0; encoded.len()];
+        seek_read(&mut fd, &mut buf, 0);
+        let (decoded, _): (Test, usize) = bincode::serde::decode_from_slice(&buf, config).unwrap();
+        assert_eq!(decoded, item);
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    /// Write a leaf holding `data` the way pages were first laid out.
+    // | IS-ROOT 1 byte | TYPE 1 byte | OFFSET - 8 bytes | rows - 8 byte
+    // | LEN - 8 bytes | ROW #N - N bytes |
+    fn write_leaf(path: &Path, data: &Record) {
+        let config = bincode::config::standard();
+
+        let encoded: Vec<u8> = bincode::serde::encode_to_vec(data, config).unwrap();
+        let data_len = encoded.len();
+
+        let mut fd = open(path);
+
+        let num_rows: usize = 1;
+        let offset: usize = 0;
+
+        // is root;
+        seek_write(&mut fd, &[0x00; 1], 0);
+        // write type
+        seek_write(&mut fd, &[0x03; 1], 1);
+        // write offset
+        seek_write(&mut fd, &offset.to_be_bytes(), 2);
+        // write rows num
+        seek_write(&mut fd, &num_rows.to_be_bytes(), 2 + 8);
+
+        // data len
+        seek_write(&mut fd, &data_len.to_be_bytes(), 2 + 8 + 8);
+        // col data
+        seek_write(&mut fd, &encoded, 2 + 8 + 8 + 8);
     }
 
     #[test]
     fn test_decode() {
         let config = bincode::config::standard();
-        let fd = std::fs::OpenOptions::new()
-            .read(true)
-            .open("./db.bin")
-            .unwrap();
+        let path = temp_path("decode.bin");
+        let data = Record(vec![Test::String("hello".to_string()), Test::Null]);
+        write_leaf(&path, &data);
+
+        let mut fd = std::fs::OpenOptions::new().read(true).open(&path).unwrap();
 
         let mut is_root = [0; 1];
-        fd.seek_read(&mut is_root[..], 0).unwrap();
+        seek_read(&mut fd, &mut is_root[..], 0);
 
         println!("IS ROOT: {}", is_root[0] == 0x01);
 
         let mut node_type = [0; 1];
-        fd.seek_read(&mut node_type, 1).unwrap();
+        seek_read(&mut fd, &mut node_type, 1);
 
-        println!("NODE TYPE: {}", u8::from(node_type[0]));
+        println!("NODE TYPE: {}", node_type[0]);
 
         let mut offset = [0; 8];
-        fd.seek_read(&mut offset, 2).unwrap();
+        seek_read(&mut fd, &mut offset, 2);
 
         println!("PARENT OFFSET: {}", usize::from_be_bytes(offset));
 
         let mut row_num = [0; 8];
-        fd.seek_read(&mut row_num, 2 + 8).unwrap();
+        seek_read(&mut fd, &mut row_num, 2 + 8);
 
         println!("NUM OF ROWS: {}", usize::from_be_bytes(row_num));
 
         let mut col_size = [0; 8];
-        fd.seek_read(&mut col_size, 2 + 8 + 8).unwrap();
+        seek_read(&mut fd, &mut col_size, 2 + 8 + 8);
         let col_size_num: usize = usize::from_be_bytes(col_size);
         println!("DATA SIZE: {}", col_size_num);
 
-        let mut data = vec![0; col_size_num];
-        fd.seek_read(&mut data, 2 + 8 + 8 + 8).unwrap();
+        let mut data_buf = vec![0; col_size_num];
+        seek_read(&mut fd, &mut data_buf, 2 + 8 + 8 + 8);
 
-        let decoded: (Record, usize) = bincode::serde::decode_from_slice(&data, config).unwrap();
+        let decoded: (Record, usize) =
+            bincode::serde::decode_from_slice(&data_buf, config).unwrap();
 
         println!("{:#?}", decoded);
+        assert_eq!(decoded.0, data);
+
+        let _ = std::fs::remove_file(&path);
     }
 
     #[test]
     fn test_encode() {
-        // | IS-ROOT 1 byte | TYPE 1 byte | OFFSET - 8 bytes | rows - 8 byte
-        // | LEN - 8 bytes | ROW #N - N bytes |
-
-        let config = bincode::config::standard();
+        let path = temp_path("encode.bin");
         let data = Record(vec![
             Test::String("hello".to_string()),
             Test::String("a".to_string()),
         ]);
 
-        let encoded: Vec<u8> = bincode::serde::encode_to_vec(&data, config).unwrap();
-        let data_len = encoded.len();
+        write_leaf(&path, &data);
+        assert!(std::fs::metadata(&path).unwrap().len() > 2 + 8 + 8 + 8);
 
-        let fd = std::fs::OpenOptions::new()
-            .create(true)
-            .read(true)
-            .write(true)
-            .truncate(true)
-            .open("./db.bin")
-            .unwrap();
-
-        let num_rows: usize = 1;
-        let offset: usize = 0;
-
-        // is root;
-        fd.seek_write(&[0x00; 1], 0).unwrap();
-        // write type
-        fd.seek_write(&[0x03; 1], 1).unwrap();
-        // write offset
-        fd.seek_write(&offset.to_be_bytes(), 2).unwrap();
-        // write rows num
-        fd.seek_write(&num_rows.to_be_bytes(), 2 + 8).unwrap();
-
-        // data len
-        fd.seek_write(&data_len.to_be_bytes(), 2 + 8 + 8).unwrap();
-        // col data
-        fd.seek_write(&encoded, 2 + 8 + 8 + 8).unwrap();
+        let _ = std::fs::remove_file(&path);
     }
 }
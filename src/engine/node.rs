@@ -1,6 +1,6 @@
 use super::{
     error::Error,
-    node_type::NodeType,
+    node_type::{NodeType, Schema},
     page::Page,
     page_layout::{
         FromByte, INTERNAL_NODE_HEADER_SIZE, INTERNAL_NODE_NUM_CHILDREN_OFFSET, IS_ROOT_OFFSET,
@@ -26,7 +26,13 @@ impl Node {
         }
     }
 
-    pub fn split(&mut self, b: usize) -> Result<(Value, Node), Error> {
+    /// Split off the upper half of the node, returning the median key and the new sibling.
+    /// `key` gives the key a row of a leaf is ordered by.
+    pub fn split(
+        &mut self,
+        b: usize,
+        key: &dyn Fn(&Record) -> Result<Value, Error>,
+    ) -> Result<(Value, Node), Error> {
         match self.node_type {
             NodeType::Internal(ref mut children, ref mut keys) => {
                 let mut sibling_keys = keys.split_off(b - 1);
@@ -46,10 +52,10 @@ impl Node {
             NodeType::Leaf(ref mut rows) => {
                 let sibling_rows = rows.split_off(b);
 
-                let median_pair = rows.get(b - 1).ok_or(Error::Unexpected)?.clone();
+                let median_pair = rows.get(b - 1).ok_or(Error::Unexpected)?;
 
                 Ok((
-                    median_pair.0.get(0).expect("Failed to get key").clone(),
+                    key(median_pair)?,
                     Node::new(
                         NodeType::Leaf(sibling_rows),
                         false,
@@ -83,7 +89,7 @@ impl TryFrom<Page> for Node {
                 let buffer = page
                     .get_ptr_from_offset(SCHEMA_DATA_LEN_OFFSET + SCHMEA_DATA_LEN_SIZE, data_len);
 
-                let (data, size) = bincode::serde::decode_from_slice(&buffer, config)?;
+                let (data, size) = Schema::decode(buffer, raw[NODE_TYPE_OFFSET])?;
 
                 if size != data_len {
                     return Err(Error::UnexpectedWithReason(
@@ -220,7 +226,7 @@ mod tests {
 
         println!("{:#?}", node);
 
-        assert_eq!(node.is_root, true);
+        assert!(node.is_root);
         assert_eq!(node.node_type, NodeType::Leaf(vec![item]));
         assert_eq!(node.parent_offset, None);
 
@@ -1,20 +1,77 @@
 use std::fmt::Display;
 
-use crate::sql::ColumnDef;
+use crate::sql::{
+    interperter::ColumnData, ColumnDef, ColumnExpr, Condition, DefaultValue, Ordering,
+};
 
 use super::{
+    data_type::DataType,
+    decimal::{self, Rounding},
     error::Error,
-    structure::{Offset, Record, Value},
+    json,
+    structure::{Assignment, Call, ConditionValue, Offset, Operation, Record, Value},
 };
 use serde::{Deserialize, Serialize};
 
+/// Page type of schemas written before composite primary keys, with the column types stored
+/// as codes and the rows kept ascending.
+const SCHEMA_V1: u8 = 0x03;
+const SCHEMA: u8 = 0x05;
+
 #[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
 pub struct Schema {
     pub name: String,
+    /// Column of the primary key, the first one of a composite key.
     pub primary_key: usize,
     // (Column Name, Data Type, Nullable)
     pub columns: Vec<ColumnDef>,
     child_offset: Option<usize>,
+    /// Columns of a composite primary key in key order, empty when the key is only `primary_key`.
+    pub composite_key: Vec<usize>,
+}
+
+#[derive(Deserialize)]
+struct SchemaV1 {
+    name: String,
+    primary_key: usize,
+    columns: Vec<ColumnDefV1>,
+    child_offset: Option<usize>,
+}
+
+impl From<SchemaV1> for Schema {
+    fn from(value: SchemaV1) -> Self {
+        Self::new(
+            value.name,
+            value.primary_key,
+            value.columns.into_iter().map(ColumnDef::from).collect(),
+            value.child_offset,
+        )
+    }
+}
+
+#[derive(Deserialize)]
+struct ColumnDefV1 {
+    name: String,
+    nullable: bool,
+    data_type: u8,
+    unique: bool,
+    autoincrement: bool,
+    ordering: Ordering,
+    default_value: Option<String>,
+}
+
+impl From<ColumnDefV1> for ColumnDef {
+    fn from(value: ColumnDefV1) -> Self {
+        ColumnDef::new(
+            value.name,
+            value.nullable,
+            value.unique,
+            DataType::from_code(value.data_type),
+            value.autoincrement,
+            value.ordering,
+            value.default_value.map(DefaultValue::Value),
+        )
+    }
 }
 
 impl Display for Schema {
@@ -25,11 +82,11 @@ impl Display for Schema {
                 f,
                 "{} {}{}{}{}{}",
                 col.name,
-                Value::print_type(col.data_type),
-                if idx == self.primary_key {
-                    " PRIMARY KEY"
-                } else {
-                    ""
+                col.data_type,
+                match idx == self.primary_key && self.composite_key.is_empty() {
+                    true if col.ordering == Ordering::Desc => " PRIMARY KEY DESC",
+                    true => " PRIMARY KEY",
+                    false => "",
                 },
                 if col.nullable { "?" } else { "" },
                 if col.unique && idx != self.primary_key {
@@ -44,6 +101,18 @@ impl Display for Schema {
                 }
             )?;
         }
+        if !self.composite_key.is_empty() {
+            let names: Vec<String> = self
+                .composite_key
+                .iter()
+                .filter_map(|idx| self.columns.get(*idx))
+                .map(|x| match x.ordering {
+                    Ordering::Asc => x.name.clone(),
+                    Ordering::Desc => format!("{} DESC", x.name),
+                })
+                .collect();
+            writeln!(f, "PRIMARY KEY ({})", names.join(", "))?;
+        }
         write!(f, "")
     }
 }
@@ -55,6 +124,7 @@ impl Default for Schema {
             primary_key: 0,
             columns: vec![],
             child_offset: Some(256),
+            composite_key: vec![],
         }
     }
 }
@@ -71,8 +141,78 @@ impl Schema {
             primary_key: primary_key_index,
             columns: columns,
             child_offset,
+            composite_key: vec![],
         }
     }
+
+    /// Key the table by all of `columns`, in order. A single column is a plain primary key.
+    pub fn with_primary_key(mut self, columns: Vec<usize>) -> Self {
+        if let Some(first) = columns.first() {
+            self.primary_key = *first;
+        }
+        self.composite_key = if columns.len() > 1 { columns } else { vec![] };
+        self
+    }
+
+    /// Columns of the primary key, in key order.
+    pub fn key_columns(&self) -> Vec<usize> {
+        if self.composite_key.is_empty() {
+            return vec![self.primary_key];
+        }
+        self.composite_key.clone()
+    }
+
+    /// For each column of the primary key, is it kept in descending order.
+    pub fn key_descending(&self) -> Vec<bool> {
+        self.key_columns()
+            .iter()
+            .map(|idx| {
+                self.columns
+                    .get(*idx)
+                    .is_some_and(|x| x.ordering == Ordering::Desc)
+            })
+            .collect()
+    }
+
+    pub fn is_key_column(&self, idx: usize) -> bool {
+        idx == self.primary_key || self.composite_key.contains(&idx)
+    }
+
+    /// Primary key of `row`. A composite key is the tuple of its columns, so rows
+    /// are ordered by its first column, then the next one and so on.
+    pub fn key(&self, row: &Record) -> Result<Value, Error> {
+        if self.composite_key.is_empty() {
+            return row.get_key(self.primary_key);
+        }
+
+        Ok(Value::Tuple(
+            self.composite_key
+                .iter()
+                .map(|idx| row.get_key(*idx))
+                .collect::<Result<Vec<Value>, Error>>()?,
+        ))
+    }
+
+    /// Decode a schema stored in a page of type `page_type`, with the number of bytes read.
+    pub fn decode(data: &[u8], page_type: u8) -> Result<(Self, usize), Error> {
+        let config = bincode::config::standard();
+
+        if page_type == SCHEMA_V1 {
+            let (schema, size): (SchemaV1, usize) =
+                bincode::serde::decode_from_slice(data, config)?;
+            // older tables kept their rows ascending whatever order was declared.
+            return Ok((Schema::from(schema).ascending(), size));
+        }
+
+        Ok(bincode::serde::decode_from_slice(data, config)?)
+    }
+
+    fn ascending(mut self) -> Self {
+        for column in self.columns.iter_mut() {
+            column.ordering = Ordering::Asc;
+        }
+        self
+    }
     pub fn get_child_offset(&self) -> Option<Offset> {
         if let Some(offset) = self.child_offset {
             return Some(Offset(offset));
@@ -80,18 +220,78 @@ impl Schema {
         None
     }
 
-    pub fn get_indexs_from_names(&self, values: &Vec<String>) -> Vec<usize> {
-        self.columns
+    /// Indexes of what a select keeps, the columns in the order of the table and then the
+    /// functions called on them, which are added to `calls`.
+    pub fn get_indexs_from_names(
+        &self,
+        values: &Vec<ColumnExpr>,
+        calls: &mut Vec<Call>,
+    ) -> Result<Vec<usize>, Error> {
+        let mut indexs = self
+            .columns
             .iter()
             .enumerate()
             .filter_map(|(idx, col)| {
-                if values.contains(&col.name) {
+                if values.contains(&ColumnExpr::Column(col.name.clone())) {
                     return Some(idx);
                 }
 
                 None
             })
-            .collect::<Vec<usize>>()
+            .collect::<Vec<usize>>();
+
+        for value in values {
+            if let ColumnExpr::Call { .. } | ColumnExpr::Cast { .. } = value {
+                indexs.push(self.resolve_call(value, calls)?);
+            }
+        }
+
+        Ok(indexs)
+    }
+
+    /// Index a call is read at, after the columns of the row. The call is added to `calls`
+    /// unless the same one already is.
+    fn resolve_call(&self, expr: &ColumnExpr, calls: &mut Vec<Call>) -> Result<usize, Error> {
+        let column_idx = |column: &String| {
+            self.get_column_idx_by_name(column)
+                .ok_or_else(|| Error::Validate(format!("No column named '{}'.", column)))
+        };
+
+        let call = match expr {
+            ColumnExpr::Call {
+                function,
+                column,
+                args,
+            } => {
+                if !json::is_function(function) {
+                    return Err(Error::Validate(format!(
+                        "Function '{}' can not be called on a column.",
+                        function
+                    )));
+                }
+
+                Call::Json {
+                    function: function.to_owned(),
+                    idx: column_idx(column)?,
+                    args: args.to_owned(),
+                }
+            }
+            ColumnExpr::Cast { column, data_type } => Call::Cast {
+                idx: column_idx(column)?,
+                data_type: *data_type,
+            },
+            ColumnExpr::Column(_) => return Err(Error::Unexpected),
+        };
+
+        let position = match calls.iter().position(|x| x == &call) {
+            Some(position) => position,
+            None => {
+                calls.push(call);
+                calls.len() - 1
+            }
+        };
+
+        Ok(self.len() + position)
     }
     pub fn validate_record(&self, record: &Record) -> Result<(), Error> {
         let schema_len = self.len();
@@ -102,13 +302,30 @@ impl Schema {
 
         for x in 0..schema_len {
             let item = record.0.get(x).ok_or_else(|| Error::Unexpected)?;
-            let col = self.columns.get(x).ok_or_else(|| Error::Unexpected)?;
+            self.validate_value(x, item)?;
+        }
+
+        Ok(())
+    }
+
+    fn validate_value(&self, idx: usize, item: &Value) -> Result<(), Error> {
+        let col = self.columns.get(idx).ok_or(Error::Unexpected)?;
 
-            if !item.is_type(col.data_type, col.nullable) {
+        if !col.data_type.matches(item, col.nullable) {
+            return Err(Error::Validate(format!(
+                "value for column \"{}\" is not of type {}",
+                col.name, col.data_type
+            )));
+        }
+
+        // a decimal has the scale of its column and no more digits then it allows.
+        if let (DataType::Decimal { precision, scale }, Value::Decimal(value, value_scale)) =
+            (col.data_type, item)
+        {
+            if *value_scale != scale || decimal::digits(*value) > precision as u32 {
                 return Err(Error::Validate(format!(
-                    "value for column \"{}\" is not of type {}",
-                    col.name,
-                    Value::print_type(col.data_type)
+                    "value {} for column \"{}\" does not fit {}",
+                    item, col.name, col.data_type
                 )));
             }
         }
@@ -119,7 +336,7 @@ impl Schema {
     pub fn parse_value_by_col(
         &self,
         column: &String,
-        value: &String,
+        value: &str,
     ) -> Result<(Value, usize), Error> {
         let id = self.get_column_idx_by_name(column).ok_or_else(|| {
             Error::UnexpectedWithReason("Schema does not have column with given name.")
@@ -128,7 +345,263 @@ impl Schema {
             .columns
             .get(id)
             .ok_or_else(|| Error::UnexpectedWithReason("Failed to get column data"))?;
-        Ok((Value::parse(value, column_data.data_type)?, id))
+        Ok((column_data.data_type.parse(value)?, id))
+    }
+
+    /// Like `parse_value_by_col`, but a number that does not fit the type of a number
+    /// column is kept as a signed integer, a decimal or a real, so `WHERE id > -1` works on a
+    /// uint column and `WHERE id < 2.5` on any number column. A decimal is never rounded,
+    /// `WHERE price = 1.005` does not match `1.01`.
+    fn parse_condition_value(
+        &self,
+        column: &ColumnExpr,
+        value: &str,
+        calls: &mut Vec<Call>,
+    ) -> Result<(Value, usize), Error> {
+        let column = match column {
+            ColumnExpr::Column(name) => name,
+            // compared as the type it is cast to, text that is not one never matches.
+            ColumnExpr::Cast { data_type, .. } => {
+                let value = data_type
+                    .parse(value)
+                    .unwrap_or_else(|_| Value::String(value.to_owned()));
+                return Ok((value, self.resolve_call(column, calls)?));
+            }
+            // what a function gives is only known once it is called, so the value is read
+            // as the JSON it looks like, `30` is a number and `Ada` a string.
+            call => {
+                let value = json::to_value(&json::literal(value));
+                return Ok((value, self.resolve_call(call, calls)?));
+            }
+        };
+
+        let exact = self.get_column_idx_by_name(column).and_then(|idx| {
+            match self.columns[idx].data_type {
+                DataType::Decimal { .. } => decimal::parse(value),
+                _ => None,
+            }
+            .map(|(v, scale)| (Value::Decimal(v, scale), idx))
+        });
+        if let Some(result) = exact {
+            return Ok(result);
+        }
+
+        let result = self.parse_value_by_col(column, value);
+        if result.is_ok() {
+            return result;
+        }
+
+        if let Some(idx) = self.get_column_idx_by_name(column) {
+            if self.columns[idx].data_type.is_number() {
+                if let Ok(number) = value.parse::<i64>() {
+                    return Ok((Value::Int(number), idx));
+                }
+                if let Some((number, scale)) = decimal::parse(value) {
+                    return Ok((Value::Decimal(number, scale), idx));
+                }
+                if let Ok(number) = value.parse::<f64>() {
+                    return Ok((Value::Real(number), idx));
+                }
+            }
+
+            if value == "true" || value == "false" {
+                return Err(Error::Validate(format!(
+                    "column '{}' is {}, not boolean",
+                    column, self.columns[idx].data_type
+                )));
+            }
+
+            // like in sqlite text that is not of the column's type is compared as text,
+            // which is never equal to, larger or smaller than a value of another type.
+            return Ok((Value::String(value.to_owned()), idx));
+        }
+
+        result
+    }
+
+    /// Values of the `SET` part of an update, with the index of their column.
+    pub fn parse_assignments(
+        &self,
+        columns: &Vec<(String, ColumnData)>,
+        rounding: Rounding,
+    ) -> Result<Vec<(Assignment, usize)>, Error> {
+        let mut update = vec![];
+        for (col, data) in columns {
+            let idx = self
+                .get_column_idx_by_name(col)
+                .ok_or(Error::UnexpectedWithReason("Failed to get column."))?;
+
+            let assignment = match data {
+                ColumnData::Null => Assignment::Value(Value::Null),
+                ColumnData::Value(d) => {
+                    Assignment::Value(self.columns[idx].data_type.parse_rounded(d, rounding)?)
+                }
+                // the value is read like in a condition, `price * 1.5` on an integer column is exact.
+                ColumnData::Column(source, op, d) => Assignment::Arithmetic(
+                    self.get_column_idx_by_name(source)
+                        .ok_or_else(|| Error::Validate(format!("No column named '{}'.", source)))?,
+                    *op,
+                    self.parse_condition_value(&col.as_str().into(), d, &mut vec![])?
+                        .0,
+                ),
+                ColumnData::Call(expr) => {
+                    let mut calls = vec![];
+                    self.resolve_call(expr, &mut calls)?;
+                    Assignment::Call(calls.remove(0))
+                }
+            };
+
+            update.push((assignment, idx));
+        }
+
+        Ok(update)
+    }
+
+    /// Set the columns of an update on `row`. Every value is computed from the row as it was.
+    pub fn assign(
+        &self,
+        row: &mut Record,
+        update: &[(Assignment, usize)],
+        rounding: Rounding,
+    ) -> Result<(), Error> {
+        let values = update
+            .iter()
+            .map(|(assignment, idx)| match assignment {
+                Assignment::Value(value) => Ok(value.clone()),
+                Assignment::Arithmetic(source, op, value) => {
+                    let current = row.0.get(*source).ok_or(Error::Unexpected)?;
+                    current.arithmetic(*op, value, self.columns[*idx].data_type, rounding)
+                }
+                // a document can be kept in a string column as well.
+                Assignment::Call(call) => match (call.apply(row)?, self.columns[*idx].data_type) {
+                    (Value::Json(text), DataType::String) => Ok(Value::String(text)),
+                    (value, _) => Ok(value),
+                },
+            })
+            .collect::<Result<Vec<Value>, Error>>()?;
+
+        for ((_, idx), value) in update.iter().zip(values) {
+            self.validate_value(*idx, &value)?;
+            if let Some(v) = row.0.get_mut(*idx) {
+                *v = value;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn parse_conditions(
+        &self,
+        condition: &Vec<Condition>,
+    ) -> Result<Vec<ConditionValue>, Error> {
+        let mut result = vec![];
+        let mut calls = vec![];
+        let mut invert = false;
+        for x in condition {
+            match x {
+                Condition::E(column, value) => {
+                    let (col_value, idx) = self.parse_condition_value(column, value, &mut calls)?;
+
+                    result.push(ConditionValue::Value {
+                        invert,
+                        idx,
+                        opt: Operation::Equal,
+                        values: [col_value, Value::Null],
+                    });
+                    invert = false;
+                }
+                Condition::GT(column, value) => {
+                    let (col_value, idx) = self.parse_condition_value(column, value, &mut calls)?;
+
+                    result.push(ConditionValue::Value {
+                        invert,
+                        idx,
+                        opt: Operation::GT,
+                        values: [col_value, Value::Null],
+                    });
+                    invert = false;
+                }
+                Condition::LT(column, value) => {
+                    let (col_value, idx) = self.parse_condition_value(column, value, &mut calls)?;
+
+                    result.push(ConditionValue::Value {
+                        invert,
+                        idx,
+                        opt: Operation::LT,
+                        values: [col_value, Value::Null],
+                    });
+                    invert = false;
+                }
+                Condition::GTE(column, value) => {
+                    let (col_value, idx) = self.parse_condition_value(column, value, &mut calls)?;
+
+                    result.push(ConditionValue::Value {
+                        invert,
+                        idx,
+                        opt: Operation::GTE,
+                        values: [col_value, Value::Null],
+                    });
+                    invert = false;
+                }
+                Condition::LTE(column, value) => {
+                    let (col_value, idx) = self.parse_condition_value(column, value, &mut calls)?;
+
+                    result.push(ConditionValue::Value {
+                        invert,
+                        idx,
+                        opt: Operation::LTE,
+                        values: [col_value, Value::Null],
+                    });
+                    invert = false;
+                }
+                Condition::NE(column, value) => {
+                    let (col_value, idx) = self.parse_condition_value(column, value, &mut calls)?;
+
+                    result.push(ConditionValue::Value {
+                        invert: !invert,
+                        idx,
+                        opt: Operation::Equal,
+                        values: [col_value, Value::Null],
+                    });
+                    invert = false;
+                }
+                Condition::NOT => {
+                    invert = true;
+                }
+                Condition::BETWEEN(column, range_start, range_end) => {
+                    let (start_col, idx) =
+                        self.parse_condition_value(column, range_start, &mut calls)?;
+                    let (end_col, _) = self.parse_condition_value(column, range_end, &mut calls)?;
+                    result.push(ConditionValue::Value {
+                        invert,
+                        idx,
+                        opt: Operation::BETWEEN,
+                        values: [start_col, end_col],
+                    });
+                    invert = false;
+                }
+                Condition::LIKE(column, value) => {
+                    let (col_value, idx) = self.parse_condition_value(column, value, &mut calls)?;
+
+                    result.push(ConditionValue::Value {
+                        invert,
+                        idx,
+                        opt: Operation::LIKE,
+                        values: [col_value, Value::Null],
+                    });
+                    invert = false;
+                }
+                Condition::AND => result.push(ConditionValue::AND),
+                Condition::OR => result.push(ConditionValue::OR),
+            }
+        }
+
+        // the calls come first, so every row has their results before it is matched.
+        Ok(calls
+            .into_iter()
+            .map(ConditionValue::Call)
+            .chain(result)
+            .collect())
     }
 
     pub fn get_column_idx_by_name(&self, column: &String) -> Option<usize> {
@@ -152,7 +625,7 @@ impl From<u8> for NodeType {
         match value {
             0x01 => NodeType::Internal(Vec::<Offset>::new(), Vec::<Value>::new()),
             0x02 => NodeType::Leaf(Vec::<Record>::new()),
-            0x03 => NodeType::Schema(Schema::default()),
+            SCHEMA_V1 | SCHEMA => NodeType::Schema(Schema::default()),
             _ => NodeType::Unexpected,
         }
     }
@@ -163,8 +636,40 @@ impl From<&NodeType> for u8 {
         match value {
             NodeType::Internal(_, _) => 0x01,
             NodeType::Leaf(_) => 0x02,
-            NodeType::Schema(_) => 0x03,
+            NodeType::Schema(_) => SCHEMA,
             NodeType::Unexpected => 0x04,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use serde::Serialize;
+
+    use super::*;
+
+    #[derive(Serialize)]
+    struct OldColumn(String, bool, u8, bool, bool, Ordering, Option<String>);
+
+    #[test]
+    fn test_decode_old_schema() {
+        let columns = vec![
+            OldColumn("id".into(), false, 0x02, true, false, Ordering::Desc, None),
+            OldColumn("name".into(), true, 0x00, false, false, Ordering::Asc, None),
+        ];
+        let data = bincode::serde::encode_to_vec(
+            ("users", 0usize, columns, Some(256usize)),
+            bincode::config::standard(),
+        )
+        .expect("Failed to encode");
+
+        let (schema, size) = Schema::decode(&data, SCHEMA_V1).expect("Failed to decode");
+        assert_eq!(size, data.len());
+        assert_eq!(schema.columns[0].data_type, DataType::U64);
+        assert_eq!(schema.columns[1].data_type, DataType::String);
+        assert!(schema.composite_key.is_empty());
+
+        // rows of these were kept ascending.
+        assert_eq!(schema.columns[0].ordering, Ordering::Asc);
+    }
+}
@@ -0,0 +1,58 @@
+//! Blobs too large to be kept in a leaf are written to a chain of overflow pages.
+//!
+//! | NEXT PAGE 8 bytes, 0 for the last page | DATA |
+//!
+//! The row keeps a `Value::Overflow` with the first page and the length of the blob.
+
+use super::{
+    error::Error,
+    page::Page,
+    page_layout::{PAGE_SIZE, PTR_SIZE},
+    pager::Pager,
+    structure::{Offset, Value},
+};
+
+/// Blobs longer than this are moved out of the leaf.
+pub const OVERFLOW_THRESHOLD: usize = 64;
+
+const CHUNK_SIZE: usize = PAGE_SIZE - PTR_SIZE;
+
+/// Write the blob to overflow pages, returning the value that refers to them.
+pub fn write(pager: &mut Pager, blob: &[u8]) -> Result<Value, Error> {
+    let mut next: usize = 0;
+
+    // the last chunk is written first so every page knows the one after it.
+    for chunk in blob.chunks(CHUNK_SIZE).rev() {
+        let mut data = [0x00; PAGE_SIZE];
+        data[..PTR_SIZE].clone_from_slice(&next.to_be_bytes());
+        data[PTR_SIZE..PTR_SIZE + chunk.len()].clone_from_slice(chunk);
+
+        let Offset(offset) = pager.write_page(Page::new(data))?;
+        next = offset;
+    }
+
+    Ok(Value::Overflow {
+        page: next,
+        len: blob.len(),
+    })
+}
+
+/// Read the blob of `len` bytes starting at `page`.
+pub fn read(pager: &mut Pager, page: usize, len: usize) -> Result<Vec<u8>, Error> {
+    let mut blob = Vec::with_capacity(len);
+    let mut offset = page;
+
+    while blob.len() < len {
+        if offset == 0 {
+            return Err(Error::UnexpectedWithReason("Overflow chain ended early."));
+        }
+
+        let page = pager.get_page(&Offset(offset))?;
+        let size = CHUNK_SIZE.min(len - blob.len());
+
+        blob.extend_from_slice(page.get_ptr_from_offset(PTR_SIZE, size));
+        offset = page.get_value_from_offset(0)?;
+    }
+
+    Ok(blob)
+}
@@ -76,6 +76,17 @@ impl Page {
     }
 }
 
+/// Fail unless `end` bytes fit in a page, rows or keys too large for a page can not be stored.
+fn check_fits(end: usize) -> Result<(), Error> {
+    if end > PAGE_SIZE {
+        return Err(Error::Validate(format!(
+            "Node of {} bytes does not fit in a page of {} bytes.",
+            end, PAGE_SIZE
+        )));
+    }
+    Ok(())
+}
+
 impl TryFrom<&Node> for Page {
     type Error = Error;
 
@@ -104,6 +115,7 @@ impl TryFrom<&Node> for Page {
                     .clone_from_slice(&offsets.len().to_be_bytes());
 
                 let mut page_offset = INTERNAL_NODE_HEADER_SIZE;
+                check_fits(page_offset + offsets.len() * PTR_SIZE)?;
 
                 for Offset(child_offset) in offsets {
                     data[page_offset..page_offset + PTR_SIZE]
@@ -113,6 +125,7 @@ impl TryFrom<&Node> for Page {
 
                 let encoded_keys = bincode::serde::encode_to_vec(keys, config)?;
                 let len = encoded_keys.len();
+                check_fits(page_offset + PTR_SIZE + len)?;
 
                 data[page_offset..page_offset + PTR_SIZE].clone_from_slice(&len.to_be_bytes());
 
@@ -125,6 +138,7 @@ impl TryFrom<&Node> for Page {
             super::node_type::NodeType::Schema(schema) => {
                 let enconded_data = bincode::serde::encode_to_vec(schema, config)?;
                 let encoded_len = enconded_data.len();
+                check_fits(SCHEMA_DATA_LEN_OFFSET + SCHMEA_DATA_LEN_SIZE + encoded_len)?;
                 // write content data size
                 data[SCHEMA_DATA_LEN_OFFSET..SCHEMA_DATA_LEN_OFFSET + SCHMEA_DATA_LEN_SIZE]
                     .clone_from_slice(&encoded_len.to_be_bytes());
@@ -141,6 +155,7 @@ impl TryFrom<&Node> for Page {
                 for row in rows {
                     let enconded_data = bincode::serde::encode_to_vec(row, config)?;
                     let data_len = enconded_data.len();
+                    check_fits(page_offset + PTR_SIZE + data_len)?;
 
                     // data size
                     data[page_offset..page_offset + PTR_SIZE]
@@ -193,4 +208,15 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn node_too_large_for_page() {
+        let leaf = Node::new(
+            NodeType::Leaf(vec![Record(vec![Value::Blob(vec![0x01; 5000])])]),
+            true,
+            None,
+        );
+
+        assert!(matches!(Page::try_from(&leaf), Err(Error::Validate(_))));
+    }
 }
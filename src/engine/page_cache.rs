@@ -0,0 +1,275 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+use super::{error::Error, page_layout::PAGE_SIZE};
+
+pub type SharedPageCache = Arc<Mutex<PageCache>>;
+
+/// Pages are cached per table file, keyed by the file path and the page offset in it.
+type PageKey = (PathBuf, usize);
+
+/// State of a table file and its wal the last time this process held a lock on it.
+/// When another process writes to the table the wal grows, so a different version
+/// means the cached pages of that file can no longer be trusted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileVersion {
+    pub table_len: u64,
+    pub wal_len: u64,
+    pub wal_modified: Option<SystemTime>,
+}
+
+struct CachedPage {
+    data: Box<[u8; PAGE_SIZE]>,
+    dirty: bool,
+    last_used: u64,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+    pub write_backs: u64,
+    pub pages: usize,
+    pub capacity: usize,
+}
+
+/// Size bounded LRU cache shared by every table, sits between the `Pager` and the table files.
+/// Written pages stay dirty in the cache until the pager flushes them at the end of a statement.
+pub struct PageCache {
+    capacity: usize,
+    pages: HashMap<PageKey, CachedPage>,
+    /// Usage order, the smallest tick is the least recently used page.
+    lru: BTreeMap<u64, PageKey>,
+    tick: u64,
+    versions: HashMap<PathBuf, FileVersion>,
+    stats: CacheStats,
+}
+
+impl PageCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            pages: HashMap::new(),
+            lru: BTreeMap::new(),
+            tick: 0,
+            versions: HashMap::new(),
+            stats: CacheStats::default(),
+        }
+    }
+
+    pub fn shared(capacity: usize) -> SharedPageCache {
+        Arc::new(Mutex::new(Self::new(capacity)))
+    }
+
+    pub fn get(&mut self, file: &Path, offset: usize) -> Option<[u8; PAGE_SIZE]> {
+        let key = (file.to_path_buf(), offset);
+        let tick = self.next_tick();
+
+        match self.pages.get_mut(&key) {
+            Some(page) => {
+                self.lru.remove(&page.last_used);
+                page.last_used = tick;
+                self.lru.insert(tick, key);
+                self.stats.hits += 1;
+                Some(*page.data)
+            }
+            None => {
+                self.stats.misses += 1;
+                None
+            }
+        }
+    }
+
+    /// Add or replace a page. Returns the dirty pages of `file` that had to be evicted
+    /// to stay within capacity, the caller has to write them back to the file.
+    /// Dirty pages of other files are never evicted, they belong to another writer.
+    pub fn put(
+        &mut self,
+        file: &Path,
+        offset: usize,
+        data: [u8; PAGE_SIZE],
+        dirty: bool,
+    ) -> Vec<(usize, [u8; PAGE_SIZE])> {
+        let key = (file.to_path_buf(), offset);
+        let tick = self.next_tick();
+
+        let dirty = match self.pages.remove(&key) {
+            Some(old) => {
+                self.lru.remove(&old.last_used);
+                dirty || old.dirty
+            }
+            None => dirty,
+        };
+
+        self.pages.insert(
+            key.clone(),
+            CachedPage {
+                data: Box::new(data),
+                dirty,
+                last_used: tick,
+            },
+        );
+        self.lru.insert(tick, key.clone());
+
+        self.evict(&key)
+    }
+
+    /// Evict least recently used pages until the cache fits its capacity, never evicting `keep`.
+    fn evict(&mut self, keep: &PageKey) -> Vec<(usize, [u8; PAGE_SIZE])> {
+        let mut write_back = vec![];
+
+        if self.pages.len() <= self.capacity {
+            return write_back;
+        }
+
+        let candidates: Vec<(u64, PageKey)> = self
+            .lru
+            .iter()
+            .map(|(tick, key)| (*tick, key.clone()))
+            .collect();
+
+        for (tick, key) in candidates {
+            if self.pages.len() <= self.capacity {
+                break;
+            }
+
+            let evictable = match self.pages.get(&key) {
+                Some(page) => &key != keep && (!page.dirty || key.0 == keep.0),
+                None => false,
+            };
+
+            if !evictable {
+                continue;
+            }
+
+            self.lru.remove(&tick);
+            if let Some(page) = self.pages.remove(&key) {
+                self.stats.evictions += 1;
+
+                if page.dirty {
+                    self.stats.write_backs += 1;
+                    write_back.push((key.1, *page.data));
+                }
+            }
+        }
+
+        write_back
+    }
+
+    /// Remove the dirty flag from every page of `file`, returning the pages in offset order.
+    pub fn take_dirty(&mut self, file: &Path) -> Vec<(usize, [u8; PAGE_SIZE])> {
+        let mut dirty: Vec<(usize, [u8; PAGE_SIZE])> = self
+            .pages
+            .iter_mut()
+            .filter(|(key, page)| page.dirty && key.0 == file)
+            .map(|(key, page)| {
+                page.dirty = false;
+                (key.1, *page.data)
+            })
+            .collect();
+
+        self.stats.write_backs += dirty.len() as u64;
+
+        dirty.sort_by_key(|x| x.0);
+        dirty
+    }
+
+    /// Drop every cached page of a file, used when the table changed outside
+    /// of this process or the table was dropped.
+    pub fn invalidate(&mut self, file: &Path) {
+        let lru = &mut self.lru;
+        self.pages.retain(|key, page| {
+            if key.0 == file {
+                lru.remove(&page.last_used);
+                return false;
+            }
+            true
+        });
+        self.versions.remove(file);
+    }
+
+    /// Compare the current version of a file with the one this cache last saw
+    /// and invalidate its pages when they differ.
+    pub fn validate(&mut self, file: &Path, version: FileVersion) {
+        if self.versions.get(file) != Some(&version) {
+            self.invalidate(file);
+            self.versions.insert(file.to_path_buf(), version);
+        }
+    }
+
+    /// Remember the version of a file after this process wrote to it.
+    pub fn set_version(&mut self, file: &Path, version: FileVersion) {
+        self.versions.insert(file.to_path_buf(), version);
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            pages: self.pages.len(),
+            capacity: self.capacity,
+            ..self.stats.clone()
+        }
+    }
+
+    fn next_tick(&mut self) -> u64 {
+        self.tick += 1;
+        self.tick
+    }
+}
+
+pub fn lock_cache(cache: &SharedPageCache) -> Result<std::sync::MutexGuard<'_, PageCache>, Error> {
+    cache.lock().map_err(|_| Error::Lock)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PageCache;
+    use crate::engine::page_layout::PAGE_SIZE;
+    use std::path::Path;
+
+    #[test]
+    fn test_lru_eviction() {
+        let mut cache = PageCache::new(2);
+        let file = Path::new("users");
+
+        cache.put(file, 0, [0x01; PAGE_SIZE], false);
+        cache.put(file, 4096, [0x02; PAGE_SIZE], false);
+
+        // touch the first page so the second one is the least recently used.
+        assert!(cache.get(file, 0).is_some());
+
+        cache.put(file, 8192, [0x03; PAGE_SIZE], false);
+
+        assert!(cache.get(file, 4096).is_none());
+        assert_eq!(cache.get(file, 0).map(|x| x[0]), Some(0x01));
+
+        let stats = cache.stats();
+        assert_eq!(stats.hits, 2);
+        assert_eq!(stats.misses, 1);
+        assert_eq!(stats.evictions, 1);
+        assert_eq!(stats.pages, 2);
+    }
+
+    #[test]
+    fn test_dirty_pages_are_written_back() {
+        let mut cache = PageCache::new(1);
+        let (users, posts) = (Path::new("users"), Path::new("posts"));
+
+        assert!(cache.put(users, 0, [0x01; PAGE_SIZE], true).is_empty());
+
+        // the dirty page belongs to another file, so it is kept over capacity.
+        assert!(cache.put(posts, 0, [0x02; PAGE_SIZE], true).is_empty());
+
+        let write_back = cache.put(posts, 4096, [0x03; PAGE_SIZE], false);
+        assert_eq!(write_back.len(), 1);
+        assert_eq!(write_back[0].0, 0);
+
+        let dirty = cache.take_dirty(users);
+        assert_eq!(dirty.len(), 1);
+        assert!(cache.take_dirty(users).is_empty());
+    }
+}
@@ -1,39 +1,131 @@
 use std::{
-    fs::{File, OpenOptions},
-    io::{Read, Seek, SeekFrom, Write},
     path::PathBuf,
+    time::{Duration, SystemTime},
 };
 
-use super::{error::Error, page::Page, page_layout::PAGE_SIZE, structure::Offset};
+use super::{
+    error::Error,
+    file_lock::LockMode,
+    page::Page,
+    page_cache::{lock_cache, FileVersion, SharedPageCache},
+    page_layout::PAGE_SIZE,
+    structure::Offset,
+    vfs::{Vfs, VfsFile},
+};
 
 pub struct Pager {
-    file: File,
+    file: Box<dyn VfsFile>,
+    path: PathBuf,
     curser: usize,
+    cache: Option<SharedPageCache>,
 }
 
 impl Pager {
-    pub fn new(path: PathBuf) -> Result<Self, Error> {
-        let fd = OpenOptions::new()
-            .create(true)
-            .read(true)
-            .write(true)
-            //.truncate(true)
-            .open(path)?;
+    pub fn new(vfs: &dyn Vfs, path: PathBuf) -> Result<Self, Error> {
+        let file = vfs.open(&path)?;
 
         Ok(Self {
-            file: fd,
+            file,
+            path,
             curser: 0,
+            cache: None,
         })
     }
 
+    /// Read and write pages through a page cache shared with other pagers.
+    pub fn set_cache(&mut self, cache: SharedPageCache) {
+        self.cache = Some(cache);
+    }
+
+    pub fn lock(&self, mode: LockMode, timeout: Duration) -> Result<(), Error> {
+        self.file.lock(mode, timeout)
+    }
+
+    pub fn unlock(&self) -> Result<(), Error> {
+        self.file.unlock()
+    }
+
+    pub fn sync(&self) -> Result<(), Error> {
+        self.file.sync()
+    }
+
+    /// Drop cached pages of this file if the table was changed since the cache last saw it.
+    pub fn validate_cache(
+        &mut self,
+        wal_len: u64,
+        wal_modified: Option<SystemTime>,
+    ) -> Result<(), Error> {
+        if let Some(cache) = &self.cache {
+            let version = FileVersion {
+                table_len: self.file.len()?,
+                wal_len,
+                wal_modified,
+            };
+
+            lock_cache(cache)?.validate(&self.path, version);
+        }
+
+        Ok(())
+    }
+
+    /// Remember the current state of the file after this process wrote to it,
+    /// so our own writes do not invalidate the cache.
+    pub fn update_cache_version(
+        &mut self,
+        wal_len: u64,
+        wal_modified: Option<SystemTime>,
+    ) -> Result<(), Error> {
+        if let Some(cache) = &self.cache {
+            let version = FileVersion {
+                table_len: self.file.len()?,
+                wal_len,
+                wal_modified,
+            };
+
+            lock_cache(cache)?.set_version(&self.path, version);
+        }
+
+        Ok(())
+    }
+
+    /// Write every dirty cached page of this file back to disk.
+    pub fn flush(&mut self) -> Result<(), Error> {
+        let dirty = match &self.cache {
+            Some(cache) => lock_cache(cache)?.take_dirty(&self.path),
+            None => return Ok(()),
+        };
+
+        for (offset, data) in dirty {
+            self.write_to_file(&data, offset)?;
+        }
+
+        Ok(())
+    }
+
+    /// Throw away the cached pages of this file, including the ones that were not written back.
+    pub fn discard(&mut self) -> Result<(), Error> {
+        if let Some(cache) = &self.cache {
+            lock_cache(cache)?.invalidate(&self.path);
+        }
+
+        Ok(())
+    }
+
     pub fn set_cursor(&mut self, curser: usize) {
         self.curser = curser;
     }
 
-    pub fn get_schema(&mut self) -> Result<Page, Error> {
+    /// Move the cursor past the end of the file, so new pages never overwrite
+    /// pages written by other handles on the same table.
+    pub fn move_cursor_to_end(&mut self) -> Result<(), Error> {
+        let file_len = self.file.len()? as usize;
+        self.curser = self.curser.max(file_len);
+        Ok(())
+    }
+
+    pub fn get_schema(&mut self, offset: &Offset) -> Result<Page, Error> {
         let mut page: [u8; 512] = [0x00; 512];
-        self.file.seek(SeekFrom::Start(0))?;
-        self.file.read_exact(&mut page)?;
+        self.file.read_at(&mut page, offset.0 as u64)?;
 
         let mut temp: [u8; PAGE_SIZE] = [0x00; PAGE_SIZE];
 
@@ -44,24 +136,59 @@ impl Pager {
         Ok(Page::new(temp))
     }
 
+    /// The schema page overlaps the first data page, so it is never cached.
+    pub fn write_schema(&mut self, page: Page) -> Result<(), Error> {
+        self.write_to_file(&page.get_data(), 0)
+    }
+
     pub fn get_page(&mut self, offset: &Offset) -> Result<Page, Error> {
+        if let Some(cache) = &self.cache {
+            if let Some(data) = lock_cache(cache)?.get(&self.path, offset.0) {
+                return Ok(Page::new(data));
+            }
+        }
+
         let mut page: [u8; PAGE_SIZE] = [0x00; PAGE_SIZE];
-        self.file.seek(SeekFrom::Start(offset.0 as u64))?;
-        self.file.read_exact(&mut page)?;
+        self.file.read_at(&mut page, offset.0 as u64)?;
+
+        self.cache_page(page, offset.0, false)?;
+
         Ok(Page::new(page))
     }
 
     pub fn write_page(&mut self, page: Page) -> Result<Offset, Error> {
-        self.file.seek(SeekFrom::Start(self.curser as u64))?;
-        self.file.write_all(&page.get_data())?;
         let res = Offset(self.curser);
+        self.write_page_at_offset(page, &res)?;
         self.curser += PAGE_SIZE;
         Ok(res)
     }
 
     pub fn write_page_at_offset(&mut self, page: Page, offset: &Offset) -> Result<(), Error> {
-        self.file.seek(SeekFrom::Start(offset.0 as u64))?;
-        self.file.write_all(&page.get_data())?;
+        match self.cache {
+            Some(_) => self.cache_page(page.get_data(), offset.0, true),
+            None => self.write_to_file(&page.get_data(), offset.0),
+        }
+    }
+
+    fn cache_page(
+        &mut self,
+        data: [u8; PAGE_SIZE],
+        offset: usize,
+        dirty: bool,
+    ) -> Result<(), Error> {
+        let write_back = match &self.cache {
+            Some(cache) => lock_cache(cache)?.put(&self.path, offset, data, dirty),
+            None => return Ok(()),
+        };
+
+        for (offset, data) in write_back {
+            self.write_to_file(&data, offset)?;
+        }
+
         Ok(())
     }
+
+    fn write_to_file(&mut self, data: &[u8; PAGE_SIZE], offset: usize) -> Result<(), Error> {
+        self.file.write_at(data, offset as u64)
+    }
 }
@@ -0,0 +1,344 @@
+use std::ops::Bound;
+
+use super::{
+    index::{Index, Seek},
+    node_type::Schema,
+    stats::TableStats,
+    structure::{ConditionValue, Record, Value},
+};
+
+/// Rows a table is assumed to hold when nothing better is known.
+const DEFAULT_ROWS: f64 = 1_000_000.0;
+/// Rows in a page, about half of a node with the default b of 10.
+const ROWS_PER_PAGE: f64 = 10.0;
+/// Rows `=` on the first column of an index is assumed to match, like sqlite does.
+/// Every other column compared with `=` halves it.
+const EQUAL_ROWS: f64 = 10.0;
+/// Share of the rows left by a range with one bound.
+const RANGE_ONE_BOUND: f64 = 1.0 / 4.0;
+/// Share of the rows left by a range with both bounds.
+const RANGE_TWO_BOUNDS: f64 = 1.0 / 64.0;
+
+/// How the rows of a table are found.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Access {
+    /// Read every row of the table.
+    Scan,
+    /// Read the range `seek` of the index at `index`, then look up each row it points at.
+    Search { index: usize, seek: Seek },
+}
+
+/// The way a statement reads a table, with what it is estimated to cost.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Plan {
+    pub access: Access,
+    /// What the plan does, like `SEARCH users USING INDEX users_name (name=?)`.
+    pub detail: String,
+    /// Estimated rows read.
+    pub rows: f64,
+    /// Estimated pages read.
+    pub cost: f64,
+}
+
+impl Plan {
+    /// Plan reading all `rows` of `table`.
+    pub fn scan(table: &str, rows: f64) -> Self {
+        Self {
+            access: Access::Scan,
+            detail: format!("SCAN {}", table),
+            rows,
+            cost: (rows / ROWS_PER_PAGE).ceil().max(1.0),
+        }
+    }
+
+    /// Row of `EXPLAIN QUERY PLAN` output, with the columns id, parent, notused
+    /// and detail like in sqlite.
+    pub fn row(&self, id: usize, parent: usize) -> Record {
+        Record(vec![
+            Value::UInt(id),
+            Value::UInt(parent),
+            Value::UInt(0),
+            Value::String(self.detail.clone()),
+        ])
+    }
+}
+
+/// Picks the cheapest way to read the rows of a table matching a `WHERE` clause.
+///
+/// The cost of a plan is the number of pages it is estimated to read. A full scan reads
+/// every leaf, an index search reads part of the index and then one page per row it finds.
+/// Estimates come from the statistics of the last `ANALYZE` of the table, or from fixed
+/// guesses when it was never analyzed.
+/// Joins are not supported yet, so a plan is always for a single table.
+pub struct Planner<'a> {
+    schema: &'a Schema,
+    indexes: &'a [Index],
+    stats: Option<&'a TableStats>,
+}
+
+impl<'a> Planner<'a> {
+    pub fn new(schema: &'a Schema, indexes: &'a [Index]) -> Self {
+        Self {
+            schema,
+            indexes,
+            stats: None,
+        }
+    }
+
+    pub fn stats(mut self, stats: Option<&'a TableStats>) -> Self {
+        self.stats = stats;
+        self
+    }
+
+    pub fn plan(&self, selection: Option<&[ConditionValue]>) -> Plan {
+        let mut best = self.scan();
+
+        let selection = match selection {
+            Some(value) => value,
+            None => return best,
+        };
+
+        for (idx, index) in self.indexes.iter().enumerate() {
+            if let Some(seek) = index.seek(selection) {
+                let plan = self.search(idx, index, seek);
+                if plan.cost < best.cost {
+                    best = plan;
+                }
+            }
+        }
+
+        best
+    }
+
+    fn rows(&self) -> f64 {
+        self.stats.map_or(DEFAULT_ROWS, |x| x.rows as f64)
+    }
+
+    fn rows_per_page(&self) -> f64 {
+        match self.stats {
+            Some(stats) if stats.pages > 0 => (stats.rows as f64 / stats.pages as f64).max(1.0),
+            _ => ROWS_PER_PAGE,
+        }
+    }
+
+    /// Pages read to get from the root to a leaf.
+    fn depth(&self) -> f64 {
+        self.rows()
+            .max(1.0)
+            .log(self.rows_per_page().max(2.0))
+            .ceil()
+            .max(1.0)
+    }
+
+    fn scan(&self) -> Plan {
+        let mut plan = Plan::scan(&self.schema.name, self.rows());
+        if let Some(stats) = self.stats {
+            plan.cost = (stats.pages as f64).max(1.0);
+        }
+        plan
+    }
+
+    fn search(&self, idx: usize, index: &Index, seek: Seek) -> Plan {
+        let lower = Self::range_bound(&seek.lower, seek.equal, ">", ">=");
+        let upper = Self::range_bound(&seek.upper, seek.equal, "<", "<=");
+
+        let mut rows = if seek.equal == 0 {
+            self.rows()
+        } else if index.unique && seek.equal == index.columns.len() {
+            1.0
+        } else {
+            self.equal_rows(&index.columns[..seek.equal])
+        };
+
+        if let Some(column) = index.columns.get(seek.equal) {
+            rows *= self.range_share(*column, &seek, lower.is_some(), upper.is_some());
+        }
+        let rows = rows.clamp(1.0, self.rows().max(1.0));
+
+        let mut constraints = vec![];
+        for column in &index.columns[..seek.equal] {
+            constraints.push(format!("{}=?", self.column_name(*column)));
+        }
+        if let Some(column) = index.columns.get(seek.equal) {
+            for opt in [lower, upper].into_iter().flatten() {
+                constraints.push(format!("{}{}?", self.column_name(*column), opt));
+            }
+        }
+
+        Plan {
+            detail: format!(
+                "SEARCH {} USING INDEX {} ({})",
+                self.schema.name,
+                index.name,
+                constraints.join(" AND ")
+            ),
+            access: Access::Search { index: idx, seek },
+            // the index is read once, then every row found is looked up in the table.
+            cost: self.depth() + (rows / self.rows_per_page()).ceil() + rows,
+            rows,
+        }
+    }
+
+    /// Rows matching `=` on all of `columns`.
+    fn equal_rows(&self, columns: &[usize]) -> f64 {
+        let stats = match self.stats {
+            Some(stats) => stats,
+            None => return EQUAL_ROWS / 2f64.powi(columns.len() as i32 - 1),
+        };
+
+        let mut rows = stats.rows as f64;
+        for column in columns {
+            if let Some(column) = stats.column(*column) {
+                rows /= (column.distinct as f64).max(1.0);
+            }
+        }
+        rows
+    }
+
+    /// Share of the rows a range on `column` leaves. With statistics a range on a
+    /// number is compared to the smallest and largest value of the column.
+    fn range_share(&self, column: usize, seek: &Seek, lower: bool, upper: bool) -> f64 {
+        let guess = match (lower, upper) {
+            (true, true) => RANGE_TWO_BOUNDS,
+            (true, false) | (false, true) => RANGE_ONE_BOUND,
+            (false, false) => return 1.0,
+        };
+
+        let (min, max) = match self.stats.and_then(|x| x.column(column)) {
+            Some(stats) => match (Self::number(&stats.min), Self::number(&stats.max)) {
+                (Some(min), Some(max)) => (min, max),
+                _ => return guess,
+            },
+            None => return guess,
+        };
+
+        let bound = |bound: &Bound<Vec<Value>>, default: f64| match bound {
+            Bound::Included(values) | Bound::Excluded(values) if values.len() > seek.equal => {
+                Self::number(&values[seek.equal])
+            }
+            _ => Some(default),
+        };
+
+        let (low, high) = match (bound(&seek.lower, min), bound(&seek.upper, max)) {
+            (Some(low), Some(high)) => (low.max(min), high.min(max)),
+            _ => return guess,
+        };
+
+        if max <= min {
+            return if low <= min && high >= max { 1.0 } else { 0.0 };
+        }
+
+        ((high - low) / (max - min)).clamp(0.0, 1.0)
+    }
+
+    fn number(value: &Value) -> Option<f64> {
+        value.real().filter(|x| x.is_finite())
+    }
+
+    /// Operator of the range part of a seek bound, if the bound has one.
+    fn range_bound(
+        bound: &Bound<Vec<Value>>,
+        equal: usize,
+        excluded: &'static str,
+        included: &'static str,
+    ) -> Option<&'static str> {
+        match bound {
+            Bound::Excluded(values) if values.len() > equal => Some(excluded),
+            Bound::Included(values) if values.len() > equal => Some(included),
+            _ => None,
+        }
+    }
+
+    fn column_name(&self, column: usize) -> &str {
+        self.schema
+            .columns
+            .get(column)
+            .map(|x| x.name.as_str())
+            .unwrap_or("?")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::PathBuf, sync::Arc};
+
+    use super::*;
+    use crate::{
+        engine::{btree::BTreeBuilder, data_type::DataType, structure::Operation, vfs::MemoryVfs},
+        sql::{ColumnDef, Ordering},
+    };
+
+    fn index(name: &str, columns: Vec<usize>, unique: bool) -> Index {
+        let tree = BTreeBuilder::new()
+            .b_parameter(10)
+            .path(PathBuf::from(format!("db/{}/table", name)))
+            .vfs(Arc::new(MemoryVfs::new()))
+            .build()
+            .expect("Failed to build");
+
+        Index::new(name.into(), columns, unique, tree)
+    }
+
+    fn condition(idx: usize, opt: Operation, value: usize) -> ConditionValue {
+        ConditionValue::Value {
+            invert: false,
+            idx,
+            opt,
+            values: [Value::UInt(value), Value::Null],
+        }
+    }
+
+    #[test]
+    fn test_plan() {
+        let column = |name: &str| {
+            ColumnDef::new(
+                name.into(),
+                false,
+                false,
+                DataType::UInt,
+                false,
+                Ordering::Asc,
+                None,
+            )
+        };
+        let schema = Schema::new(
+            "users".into(),
+            0,
+            vec![column("id"), column("name"), column("age")],
+            None,
+        );
+        let indexes = vec![
+            index("users_age", vec![2], false),
+            index("users_name", vec![1], true),
+        ];
+        let planner = Planner::new(&schema, &indexes);
+
+        assert_eq!(planner.plan(None).detail, "SCAN users");
+
+        let plan = planner.plan(Some(&[condition(2, Operation::Equal, 20)]));
+        assert_eq!(plan.detail, "SEARCH users USING INDEX users_age (age=?)");
+
+        // both could be used, the unique index finds at most one row.
+        let plan = planner.plan(Some(&[
+            condition(2, Operation::Equal, 20),
+            ConditionValue::AND,
+            condition(1, Operation::Equal, 1),
+        ]));
+        assert!(matches!(plan.access, Access::Search { index: 1, .. }));
+        assert_eq!(plan.rows, 1.0);
+
+        // a range with one bound reads too much of a large table.
+        let plan = planner.plan(Some(&[condition(2, Operation::GT, 20)]));
+        assert_eq!(plan.access, Access::Scan);
+
+        let plan = planner.plan(Some(&[
+            condition(2, Operation::GTE, 20),
+            ConditionValue::AND,
+            condition(2, Operation::LT, 30),
+        ]));
+        assert_eq!(
+            plan.detail,
+            "SEARCH users USING INDEX users_age (age>=? AND age<?)"
+        );
+    }
+}
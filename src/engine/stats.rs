@@ -0,0 +1,174 @@
+use std::{collections::BTreeSet, fmt::Display};
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    error::Error,
+    node_type::Schema,
+    structure::{Record, Value},
+};
+
+/// Statistics of a table collected by `ANALYZE`.
+///
+/// They are only as fresh as the last `ANALYZE` of the table, writes do not update them.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TableStats {
+    pub rows: u64,
+    /// Pages the tree of the table is made of.
+    pub pages: u64,
+    /// One for each column of the table, in schema order.
+    pub columns: Vec<ColumnStats>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ColumnStats {
+    pub name: String,
+    /// Number of different values, nulls not included.
+    pub distinct: u64,
+    pub nulls: u64,
+    /// Smallest and largest value, null when the column only holds nulls.
+    pub min: Value,
+    pub max: Value,
+}
+
+impl TableStats {
+    pub fn column(&self, idx: usize) -> Option<&ColumnStats> {
+        self.columns.get(idx)
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        Ok(bincode::serde::encode_to_vec(
+            self,
+            bincode::config::standard(),
+        )?)
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self, Error> {
+        let (stats, _) = bincode::serde::decode_from_slice(data, bincode::config::standard())?;
+        Ok(stats)
+    }
+}
+
+impl Display for TableStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{} rows in {} pages", self.rows, self.pages)?;
+        for column in &self.columns {
+            writeln!(
+                f,
+                "{}: {} distinct, {} null, min {}, max {}",
+                column.name, column.distinct, column.nulls, column.min, column.max
+            )?;
+        }
+        write!(f, "")
+    }
+}
+
+/// Collects the statistics of a table one row at a time.
+pub struct StatsBuilder {
+    names: Vec<String>,
+    values: Vec<BTreeSet<Value>>,
+    nulls: Vec<u64>,
+    rows: u64,
+    pages: u64,
+}
+
+impl StatsBuilder {
+    pub fn new(schema: &Schema) -> Self {
+        Self {
+            names: schema.columns.iter().map(|x| x.name.clone()).collect(),
+            values: vec![BTreeSet::new(); schema.len()],
+            nulls: vec![0; schema.len()],
+            rows: 0,
+            pages: 0,
+        }
+    }
+
+    pub fn page(&mut self) {
+        self.pages += 1;
+    }
+
+    pub fn row(&mut self, row: &Record) {
+        self.rows += 1;
+
+        for (idx, value) in row.0.iter().enumerate().take(self.names.len()) {
+            if value == &Value::Null {
+                self.nulls[idx] += 1;
+            } else {
+                self.values[idx].insert(value.clone());
+            }
+        }
+    }
+
+    pub fn build(self) -> TableStats {
+        let columns = self
+            .names
+            .into_iter()
+            .zip(self.values)
+            .zip(self.nulls)
+            .map(|((name, values), nulls)| ColumnStats {
+                name,
+                distinct: values.len() as u64,
+                nulls,
+                min: values.first().cloned().unwrap_or(Value::Null),
+                max: values.last().cloned().unwrap_or(Value::Null),
+            })
+            .collect();
+
+        TableStats {
+            rows: self.rows,
+            pages: self.pages,
+            columns,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        engine::data_type::DataType,
+        sql::{ColumnDef, Ordering},
+    };
+
+    #[test]
+    fn test_collect_stats() {
+        let column = |name: &str| {
+            ColumnDef::new(
+                name.into(),
+                true,
+                false,
+                DataType::UInt,
+                false,
+                Ordering::Asc,
+                None,
+            )
+        };
+        let schema = Schema::new("users".into(), 0, vec![column("id"), column("age")], None);
+
+        let mut builder = StatsBuilder::new(&schema);
+        builder.page();
+        for (id, age) in [(1, Value::UInt(20)), (2, Value::Null), (3, Value::UInt(20))] {
+            builder.row(&Record(vec![Value::UInt(id), age]));
+        }
+
+        let stats = builder.build();
+        assert_eq!(stats.rows, 3);
+        assert_eq!(stats.pages, 1);
+        assert_eq!(
+            stats.column(1),
+            Some(&ColumnStats {
+                name: "age".into(),
+                distinct: 1,
+                nulls: 1,
+                min: Value::UInt(20),
+                max: Value::UInt(20),
+            })
+        );
+
+        let bytes = stats.to_bytes().expect("Failed to encode");
+        assert_eq!(
+            TableStats::from_bytes(&bytes).expect("Failed to decode"),
+            stats
+        );
+    }
+}
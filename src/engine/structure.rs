@@ -1,9 +1,21 @@
-use std::fmt::Display;
-
-use crate::sql::interperter::ColumnData;
-
-use super::{error::Error, node_type::Schema, page_layout::PTR_SIZE};
-use serde::{Deserialize, Serialize, Serializer};
+use std::{cmp::Ordering, fmt::Display};
+
+use crate::sql::{interperter::ColumnData, Arithmetic, ColumnDef};
+
+use super::{
+    data_type::DataType,
+    datetime,
+    decimal::{self, Rounding},
+    error::Error,
+    json,
+    node_type::Schema,
+    page_layout::PTR_SIZE,
+    uuid,
+};
+use serde::{
+    ser::{SerializeStructVariant, SerializeTupleVariant},
+    Deserialize, Serialize, Serializer,
+};
 
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub struct Offset(pub usize);
@@ -34,14 +46,375 @@ impl TryFrom<&[u8]> for Usize {
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Deserialize, Clone, PartialOrd, Ord)]
+/// A value of a column.
+///
+/// Values are stored by the position of their variant, so new variants are only added at the end.
+#[derive(Debug, Deserialize, Clone)]
 pub enum Value {
     String(String),
     U64(u64),
     UInt(usize),
     Null,
+    /// Several values compared in order, the key of an index entry.
+    Tuple(Vec<Value>),
+    Int(i64),
+    Real(f64),
+    Bool(bool),
+    Blob(Vec<u8>),
+    /// A blob kept in overflow pages, only ever found in the leaves of a table.
+    /// Rows are read with the blob in its place.
+    Overflow {
+        page: usize,
+        len: usize,
+    },
+    /// Days since 1970-01-01.
+    Date(i32),
+    /// Microseconds since midnight.
+    Time(i64),
+    /// Microseconds since 1970-01-01T00:00:00Z.
+    Timestamp(i64),
+    /// The digits of an exact number and how many of them are after the point.
+    Decimal(i128, u8),
+    /// A JSON document, kept without whitespace.
+    Json(String),
+    Uuid([u8; 16]),
 }
 
+impl Value {
+    /// Value of an integer of any type.
+    pub fn integer(&self) -> Option<i128> {
+        match self {
+            Value::U64(v) => Some(*v as i128),
+            Value::UInt(v) => Some(*v as i128),
+            Value::Int(v) => Some(*v as i128),
+            _ => None,
+        }
+    }
+
+    /// Value of a decimal or an integer as digits and a scale.
+    pub fn decimal(&self) -> Option<(i128, u8)> {
+        match self {
+            Value::Decimal(v, scale) => Some((*v, *scale)),
+            _ => self.integer().map(|x| (x, 0)),
+        }
+    }
+
+    /// `self op other` to be kept in a column of `data_type`. Only a real column computes
+    /// with reals, everything else is exact and the digits a decimal column can not keep are
+    /// rounded with `rounding`. Dividing into an integer column cuts off the fraction.
+    pub fn arithmetic(
+        &self,
+        op: Arithmetic,
+        other: &Value,
+        data_type: DataType,
+        rounding: Rounding,
+    ) -> Result<Value, Error> {
+        if self == &Value::Null || other == &Value::Null {
+            return Ok(Value::Null);
+        }
+        if !data_type.is_number() || self.real().is_none() || other.real().is_none() {
+            return Err(Error::Validate(format!(
+                "can not compute with {} and {}, only numbers can.",
+                self, other
+            )));
+        }
+
+        if data_type == DataType::Real {
+            let (a, b) = (
+                self.real().unwrap_or_default(),
+                other.real().unwrap_or_default(),
+            );
+            let value = match op {
+                Arithmetic::Add => a + b,
+                Arithmetic::Subtract => a - b,
+                Arithmetic::Multiply => a * b,
+                Arithmetic::Divide => a / b,
+            };
+            if !value.is_finite() {
+                return Err(Error::Validate(format!(
+                    "result does not fit {}.",
+                    data_type
+                )));
+            }
+            return Ok(Value::Real(value));
+        }
+
+        let (Some(a), Some(b)) = (self.decimal(), other.decimal()) else {
+            return Err(Error::Validate(format!(
+                "a real can not be kept in a column of type {}.",
+                data_type
+            )));
+        };
+
+        let overflow = || Error::Validate(format!("result does not fit {}.", data_type));
+        let scale = match data_type {
+            DataType::Decimal { scale, .. } => scale,
+            _ => 0,
+        };
+
+        let (value, from) = match op {
+            Arithmetic::Add => decimal::add(a, b),
+            Arithmetic::Subtract => decimal::add(a, (-b.0, b.1)),
+            Arithmetic::Multiply => decimal::multiply(a, b),
+            Arithmetic::Divide if b.0 == 0 => {
+                return Err(Error::Validate("division by zero.".into()))
+            }
+            Arithmetic::Divide => match data_type {
+                DataType::Decimal { .. } => decimal::divide(a, b, scale, rounding),
+                _ => decimal::divide(a, b, 0, Rounding::Down),
+            },
+        }
+        .ok_or_else(overflow)?;
+        let value = decimal::rescale(value, from, scale, rounding).ok_or_else(overflow)?;
+
+        Ok(match data_type {
+            DataType::Int => Value::Int(i64::try_from(value).map_err(|_| overflow())?),
+            DataType::UInt => Value::UInt(usize::try_from(value).map_err(|_| overflow())?),
+            DataType::U64 => Value::U64(u64::try_from(value).map_err(|_| overflow())?),
+            _ => Value::Decimal(value, scale),
+        })
+    }
+
+    pub fn real(&self) -> Option<f64> {
+        match self {
+            Value::Real(v) => Some(*v),
+            Value::Decimal(v, scale) => Some(decimal::to_f64(*v, *scale)),
+            _ => self.integer().map(|x| x as f64),
+        }
+    }
+
+    /// Compare like a condition does. Null is not equal to, larger or smaller than anything and
+    /// neither are values of different kinds, a string and a number or a date and a timestamp.
+    /// Numbers of any type are compared by their value.
+    pub fn compare(&self, other: &Value) -> Option<Ordering> {
+        if self == &Value::Null || other == &Value::Null || self.rank() != other.rank() {
+            return None;
+        }
+
+        Some(self.cmp(other))
+    }
+
+    /// The value converted to `data_type` by `CAST`, like sqlite. A string is read as the
+    /// number it starts with, so `"12abc"` is 12 and `"abc"` is 0, and an integer is the whole
+    /// part of a number. Null stays null.
+    pub fn cast(&self, data_type: DataType) -> Result<Value, Error> {
+        let fail = || Error::Validate(format!("can not cast {} to {}.", self, data_type));
+        let number = |value: &Value| match value {
+            Value::String(v) => numeric_prefix(v).to_owned(),
+            Value::Bool(v) => u8::from(*v).to_string(),
+            value => value.to_string(),
+        };
+
+        let result = match (self, data_type) {
+            (Value::Null, _) | (_, DataType::Null) => Value::Null,
+            (Value::Blob(v), DataType::String) => {
+                Value::String(String::from_utf8_lossy(v).into_owned())
+            }
+            (value, DataType::String) => Value::String(value.to_string()),
+            (Value::String(_) | Value::Bool(_), DataType::Real) => Value::Real(
+                number(self)
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|x| x.is_finite())
+                    .ok_or_else(fail)?,
+            ),
+            (value, DataType::Real) => Value::Real(value.real().ok_or_else(fail)?),
+            (Value::Real(v), DataType::UInt | DataType::U64 | DataType::Int) if v.is_finite() => {
+                Value::Int(v.trunc().clamp(i64::MIN as f64, i64::MAX as f64) as i64)
+                    .cast(data_type)?
+            }
+            (value, DataType::UInt | DataType::U64 | DataType::Int) => {
+                let (value, scale) = decimal::parse(&number(value)).ok_or_else(fail)?;
+                let whole = decimal::rescale(value, scale, 0, Rounding::Down).ok_or_else(fail)?;
+                let fit = || Error::Validate(format!("{} does not fit {}.", self, data_type));
+
+                match data_type {
+                    DataType::UInt => Value::UInt(usize::try_from(whole).map_err(|_| fit())?),
+                    DataType::U64 => Value::U64(u64::try_from(whole).map_err(|_| fit())?),
+                    _ => Value::Int(i64::try_from(whole).map_err(|_| fit())?),
+                }
+            }
+            (value, DataType::Decimal { precision, scale }) => {
+                let (value, from) = match value {
+                    Value::Real(v) => decimal::parse(&v.to_string()),
+                    value => decimal::parse(&number(value)),
+                }
+                .ok_or_else(fail)?;
+                let value =
+                    decimal::rescale(value, from, scale, Rounding::default()).ok_or_else(fail)?;
+
+                if decimal::digits(value) > precision as u32 {
+                    return Err(fail());
+                }
+                Value::Decimal(value, scale)
+            }
+            (Value::Bool(v), DataType::Boolean) => Value::Bool(*v),
+            (Value::String(v), DataType::Boolean) if DataType::Boolean.parse(v).is_ok() => {
+                DataType::Boolean.parse(v)?
+            }
+            (value, DataType::Boolean) => {
+                Value::Bool(number(value).parse::<f64>().map_err(|_| fail())? != 0.0)
+            }
+            (Value::String(v), DataType::Blob) => Value::Blob(v.as_bytes().to_vec()),
+            (Value::Uuid(v), DataType::Blob) => Value::Blob(v.to_vec()),
+            (Value::Blob(v), DataType::Uuid) => {
+                Value::Uuid(v.as_slice().try_into().map_err(|_| fail())?)
+            }
+            (Value::Timestamp(v), DataType::Date) => {
+                Value::Date(v.div_euclid(datetime::MICROS_PER_DAY) as i32)
+            }
+            (Value::Timestamp(v), DataType::Time) => {
+                Value::Time(v.rem_euclid(datetime::MICROS_PER_DAY))
+            }
+            (Value::Date(v), DataType::Timestamp) => {
+                Value::Timestamp(*v as i64 * datetime::MICROS_PER_DAY)
+            }
+            (value, data_type) if data_type.matches(value, false) => value.clone(),
+            // everything else is converted through its text, `CAST("2024-01-31" AS date)`.
+            (Value::Blob(_) | Value::Overflow { .. } | Value::Tuple(_), _) => return Err(fail()),
+            (value, data_type) => data_type.parse(&value.to_string()).map_err(|_| fail())?,
+        };
+
+        Ok(result)
+    }
+
+    /// Position of the value in the order of types, strings come before numbers,
+    /// then null and tuples, like they always have in the keys of a tree.
+    fn rank(&self) -> u8 {
+        match self {
+            Value::String(_) => 0,
+            Value::Bool(_) => 1,
+            Value::U64(_) | Value::UInt(_) | Value::Int(_) | Value::Real(_) => 2,
+            Value::Decimal(..) => 2,
+            Value::Null => 3,
+            Value::Tuple(_) => 4,
+            Value::Blob(_) | Value::Overflow { .. } => 5,
+            Value::Date(_) => 6,
+            Value::Time(_) => 7,
+            Value::Timestamp(_) => 8,
+            Value::Json(_) => 9,
+            Value::Uuid(_) => 10,
+        }
+    }
+}
+
+/// The start of `value` that is a number, `"0"` when it does not start with one.
+fn numeric_prefix(value: &str) -> &str {
+    let value = value.trim_start();
+    let bytes = value.as_bytes();
+    let digits = |mut at: usize| {
+        while bytes.get(at).is_some_and(|x| x.is_ascii_digit()) {
+            at += 1;
+        }
+        at
+    };
+
+    let sign = usize::from(matches!(bytes.first(), Some(b'-' | b'+')));
+    let mut end = digits(sign);
+    if end == sign {
+        return "0";
+    }
+
+    if bytes.get(end) == Some(&b'.') && digits(end + 1) > end + 1 {
+        end = digits(end + 1);
+    }
+
+    if matches!(bytes.get(end), Some(b'e' | b'E')) {
+        let sign = end + 1 + usize::from(matches!(bytes.get(end + 1), Some(b'-' | b'+')));
+        if digits(sign) > sign {
+            end = digits(sign);
+        }
+    }
+
+    &value[..end]
+}
+
+/// Compare two reals, NaN is equal to itself and larger than every other number
+/// so reals can be ordered in the keys of a tree. `-0.0` is equal to `0.0`.
+fn compare_real(a: f64, b: f64) -> Ordering {
+    match (a.is_nan(), b.is_nan()) {
+        (true, true) => Ordering::Equal,
+        (true, false) => Ordering::Greater,
+        (false, true) => Ordering::Less,
+        (false, false) => a.partial_cmp(&b).unwrap_or(Ordering::Equal),
+    }
+}
+
+/// Compare an integer with a real exactly, without rounding the integer to a real.
+fn compare_integer_real(a: i128, b: f64) -> Ordering {
+    if b.is_nan() || b >= i128::MAX as f64 {
+        return Ordering::Less;
+    }
+    if b < i128::MIN as f64 {
+        return Ordering::Greater;
+    }
+
+    let whole = b.trunc();
+    a.cmp(&(whole as i128)).then_with(|| compare_real(whole, b))
+}
+
+/// Numbers are compared by their value whatever their type, so `UInt(3) == Int(3)`,
+/// `Int(-1) < U64(0)` and `Int(1) < Real(1.5)`.
+impl Ord for Value {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Value::String(a), Value::String(b)) => a.cmp(b),
+            (Value::Tuple(a), Value::Tuple(b)) => a.cmp(b),
+            (Value::Real(a), Value::Real(b)) => compare_real(*a, *b),
+            (Value::Bool(a), Value::Bool(b)) => a.cmp(b),
+            (Value::Blob(a), Value::Blob(b)) => a.cmp(b),
+            // the bytes of an overflow are not at hand here, rows are loaded before their
+            // blobs are compared. Overflows come after blobs, in the order they are stored.
+            (Value::Overflow { page: a, len: la }, Value::Overflow { page: b, len: lb }) => {
+                (a, la).cmp(&(b, lb))
+            }
+            (Value::Blob(_), Value::Overflow { .. }) => Ordering::Less,
+            (Value::Overflow { .. }, Value::Blob(_)) => Ordering::Greater,
+            (Value::Date(a), Value::Date(b)) => a.cmp(b),
+            (Value::Time(a), Value::Time(b)) => a.cmp(b),
+            (Value::Timestamp(a), Value::Timestamp(b)) => a.cmp(b),
+            (Value::Json(a), Value::Json(b)) => a.cmp(b),
+            // by their bytes, so v7 UUIDs are in the order they were made.
+            (Value::Uuid(a), Value::Uuid(b)) => a.cmp(b),
+            // exactly, like an integer, so a decimal between two reals is not equal to either.
+            (Value::Decimal(a, sa), Value::Real(b)) => decimal::compare_f64((*a, *sa), *b),
+            (Value::Real(a), Value::Decimal(b, sb)) => {
+                decimal::compare_f64((*b, *sb), *a).reverse()
+            }
+            (Value::Decimal(a, sa), b) if b.decimal().is_some() => {
+                decimal::compare((*a, *sa), b.decimal().unwrap_or_default())
+            }
+            (a, Value::Decimal(b, sb)) if a.decimal().is_some() => {
+                decimal::compare(a.decimal().unwrap_or_default(), (*b, *sb))
+            }
+            (Value::Real(a), b) if b.integer().is_some() => {
+                compare_integer_real(b.integer().unwrap_or_default(), *a).reverse()
+            }
+            (a, Value::Real(b)) if a.integer().is_some() => {
+                compare_integer_real(a.integer().unwrap_or_default(), *b)
+            }
+            _ => match (self.integer(), other.integer()) {
+                (Some(a), Some(b)) => a.cmp(&b),
+                _ => self.rank().cmp(&other.rank()),
+            },
+        }
+    }
+}
+
+impl PartialOrd for Value {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Value {}
+
 impl Serialize for Value {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -52,6 +425,21 @@ impl Serialize for Value {
                 Value::String(v) => serializer.serialize_str(v),
                 Value::U64(v) => serializer.serialize_u64(*v),
                 Value::UInt(v) => serializer.serialize_u64(*v as u64),
+                Value::Int(v) => serializer.serialize_i64(*v),
+                Value::Real(v) => serializer.serialize_f64(*v),
+                Value::Bool(v) => serializer.serialize_bool(*v),
+                Value::Blob(v) => serializer.serialize_str(&base64(v)),
+                // a decimal is sent as a string so no digit is lost to a float.
+                Value::Date(_)
+                | Value::Time(_)
+                | Value::Timestamp(_)
+                | Value::Decimal(..)
+                | Value::Uuid(_) => serializer.serialize_str(&self.to_string()),
+                Value::Tuple(v) => v.serialize(serializer),
+                // a document is sent as it is, not as a string holding it.
+                Value::Json(v) => serde_json::from_str::<serde_json::Value>(v)
+                    .map_err(serde::ser::Error::custom)?
+                    .serialize(serializer),
                 _ => serializer.serialize_none(),
             }
         } else {
@@ -60,6 +448,32 @@ impl Serialize for Value {
                 Value::U64(v) => serializer.serialize_newtype_variant("Value", 1, "U64", v),
                 Value::UInt(v) => serializer.serialize_newtype_variant("Value", 2, "Unit", v),
                 Value::Null => serializer.serialize_unit_variant("Value", 3, "Null"),
+                Value::Tuple(v) => serializer.serialize_newtype_variant("Value", 4, "Tuple", v),
+                Value::Int(v) => serializer.serialize_newtype_variant("Value", 5, "Int", v),
+                Value::Real(v) => serializer.serialize_newtype_variant("Value", 6, "Real", v),
+                Value::Bool(v) => serializer.serialize_newtype_variant("Value", 7, "Bool", v),
+                Value::Blob(v) => serializer.serialize_newtype_variant("Value", 8, "Blob", v),
+                Value::Overflow { page, len } => {
+                    let mut state =
+                        serializer.serialize_struct_variant("Value", 9, "Overflow", 2)?;
+                    state.serialize_field("page", page)?;
+                    state.serialize_field("len", len)?;
+                    state.end()
+                }
+                Value::Date(v) => serializer.serialize_newtype_variant("Value", 10, "Date", v),
+                Value::Time(v) => serializer.serialize_newtype_variant("Value", 11, "Time", v),
+                Value::Timestamp(v) => {
+                    serializer.serialize_newtype_variant("Value", 12, "Timestamp", v)
+                }
+                Value::Decimal(v, scale) => {
+                    let mut state =
+                        serializer.serialize_tuple_variant("Value", 13, "Decimal", 2)?;
+                    state.serialize_field(v)?;
+                    state.serialize_field(scale)?;
+                    state.end()
+                }
+                Value::Json(v) => serializer.serialize_newtype_variant("Value", 14, "Json", v),
+                Value::Uuid(v) => serializer.serialize_newtype_variant("Value", 15, "Uuid", v),
             }
         }
     }
@@ -72,68 +486,51 @@ impl Display for Value {
             Value::String(v) => write!(f, "{}", v),
             Value::U64(v) => write!(f, "{}", v),
             Value::UInt(v) => write!(f, "{}", v),
+            Value::Int(v) => write!(f, "{}", v),
+            Value::Real(v) => write!(f, "{:?}", v),
+            Value::Bool(v) => write!(f, "{}", v),
+            Value::Blob(v) => {
+                let hex: String = v.iter().map(|x| format!("{:02X}", x)).collect();
+                write!(f, "X'{}'", hex)
+            }
+            Value::Overflow { len, .. } => write!(f, "<blob of {} bytes>", len),
+            Value::Date(v) => write!(f, "{}", datetime::format_date(*v as i64)),
+            Value::Time(v) => write!(f, "{}", datetime::format_time(*v)),
+            Value::Timestamp(v) => write!(f, "{}", datetime::format_timestamp(*v)),
+            Value::Decimal(v, scale) => write!(f, "{}", decimal::format(*v, *scale)),
+            Value::Json(v) => write!(f, "{}", v),
+            Value::Uuid(v) => write!(f, "{}", uuid::format(v)),
+            Value::Tuple(v) => {
+                let values: Vec<String> = v.iter().map(|x| x.to_string()).collect();
+                write!(f, "({})", values.join(", "))
+            }
         }
     }
 }
 
-impl Value {
-    pub fn print_type(value: u8) -> &'static str {
-        match value {
-            0x00 => "string",
-            0x01 => "uint",
-            0x02 => "u64",
-            _ => "null",
-        }
-    }
+/// Blobs are sent as standard base64 in json, with padding.
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
 
-    pub fn get_default(value: u8) -> Value {
-        match value {
-            0x00 => Value::String(String::default()),
-            0x01 => Value::UInt(0),
-            0x02 => Value::U64(0),
-            _ => Value::Null,
-        }
-    }
-
-    pub fn from_string(value: &String) -> u8 {
-        match value.as_str() {
-            "string" => 0x00,
-            "uint" => 0x01,
-            "u64" => 0x02,
-            _ => 0x03, // null
-        }
-    }
+    let mut output = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
 
-    pub fn is_type(&self, value: u8, nullable: bool) -> bool {
-        match &self {
-            Self::String(_) => value == 0x00,
-            Self::UInt(_) => value == 0x01,
-            Self::U64(_) => value == 0x02,
-            Self::Null => value == 0x03 || nullable,
+        for i in 0..4 {
+            if i <= chunk.len() {
+                output.push(ALPHABET[(n >> (18 - i * 6)) as usize & 0x3F] as char);
+            } else {
+                output.push('=');
+            }
         }
     }
 
-    pub fn parse(value: &String, data_type: u8) -> Result<Self, Error> {
-        let result = match data_type {
-            0x00 => Value::String(value.to_owned()),
-            0x01 => Value::UInt(value.parse::<usize>()?),
-            0x02 => Value::U64(value.parse::<u64>()?),
-            _ => Value::Null,
-        };
-
-        Ok(result)
-    }
-}
-
-impl From<&Value> for u8 {
-    fn from(value: &Value) -> Self {
-        match value {
-            Value::String(_) => 0x00,
-            Value::UInt(_) => 0x01,
-            Value::U64(_) => 0x02,
-            Value::Null => 0x03,
-        }
-    }
+    output
 }
 
 #[derive(Debug, PartialEq)]
@@ -147,6 +544,46 @@ pub enum Operation {
     LIKE,
 }
 
+/// New value of a column set by an update.
+#[derive(Debug, PartialEq)]
+pub enum Assignment {
+    Value(Value),
+    /// The value of the column at the index with the operator and value applied to it.
+    Arithmetic(usize, Arithmetic, Value),
+    Call(Call),
+}
+
+/// A function called on a column of every row. Conditions and selects read its result like one
+/// more column, after the columns of the row.
+/// `idx` is the index of the column it is called on.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Call {
+    Json {
+        function: String,
+        idx: usize,
+        args: Vec<String>,
+    },
+    Cast {
+        idx: usize,
+        data_type: DataType,
+    },
+}
+
+impl Call {
+    pub fn apply(&self, row: &Record) -> Result<Value, Error> {
+        match self {
+            Call::Json {
+                function,
+                idx,
+                args,
+            } => json::call(function, row.0.get(*idx).ok_or(Error::Unexpected)?, args),
+            Call::Cast { idx, data_type } => {
+                row.0.get(*idx).ok_or(Error::Unexpected)?.cast(*data_type)
+            }
+        }
+    }
+}
+
 #[derive(Debug, PartialEq)]
 pub enum ConditionValue {
     AND,
@@ -157,6 +594,23 @@ pub enum ConditionValue {
         opt: Operation,
         values: [super::structure::Value; 2],
     },
+    /// A function the values after it read, at the index after the columns and the calls before it.
+    Call(Call),
+}
+
+fn parse_column_data(
+    data: &ColumnData,
+    column: &ColumnDef,
+    rounding: Rounding,
+) -> Result<Value, Error> {
+    match data {
+        ColumnData::Null => Ok(Value::Null),
+        ColumnData::Value(value) => column.data_type.parse_rounded(value, rounding),
+        ColumnData::Column(..) | ColumnData::Call(_) => Err(Error::Validate(format!(
+            "Column '{}' can only be set from another column in an update.",
+            column.name
+        ))),
+    }
 }
 
 #[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Eq, PartialOrd, Ord)]
@@ -167,6 +621,7 @@ impl Record {
         cols: &Vec<String>,
         col_data: &Vec<ColumnData>,
         schema: &Schema,
+        rounding: Rounding,
     ) -> Result<Self, Error> {
         let mut data = vec![];
 
@@ -184,16 +639,16 @@ impl Record {
                     let insert_idx = cols
                         .iter()
                         .position(|x| x == &column.name)
-                        .ok_or_else(|| Error::Unexpected)?;
+                        .ok_or(Error::Unexpected)?;
 
                     // column was specified, get value and parse
-                    let column_data = match col_data
-                        .get(insert_idx)
-                        .ok_or_else(|| Error::UnexpectedWithReason("Failed to get column data"))?
-                    {
-                        ColumnData::Null => Value::Null,
-                        ColumnData::Value(data) => Value::parse(data, column.data_type)?,
-                    };
+                    let column_data = parse_column_data(
+                        col_data.get(insert_idx).ok_or_else(|| {
+                            Error::UnexpectedWithReason("Failed to get column data")
+                        })?,
+                        column,
+                        rounding,
+                    )?;
 
                     data.push(column_data);
                     continue;
@@ -203,7 +658,10 @@ impl Record {
 
                 // use default value if there
                 if let Some(value) = &column.default_value {
-                    let output = Value::parse(value, column.data_type)?;
+                    let value = value
+                        .evaluate()
+                        .map_err(|e| Error::Validate(e.to_string()))?;
+                    let output = column.data_type.parse_rounded(&value, rounding)?;
 
                     data.push(output);
                     continue;
@@ -222,12 +680,13 @@ impl Record {
                 )));
             }
 
-            let column_data = match col_data.get(idx).ok_or_else(|| {
-                Error::Validate(format!("No data was set for column '{}'.", column.name))
-            })? {
-                ColumnData::Null => Value::Null,
-                ColumnData::Value(v) => Value::parse(v, column.data_type)?,
-            };
+            let column_data = parse_column_data(
+                col_data.get(idx).ok_or_else(|| {
+                    Error::Validate(format!("No data was set for column '{}'.", column.name))
+                })?,
+                column,
+                rounding,
+            )?;
 
             data.push(column_data);
         }
@@ -251,10 +710,41 @@ impl Record {
         Record(data)
     }
 
+    /// The row with the result of every call added after its columns.
+    pub fn with_calls<'a>(
+        &self,
+        calls: impl IntoIterator<Item = &'a Call>,
+    ) -> Result<Record, Error> {
+        let mut values = self.0.clone();
+        for call in calls {
+            values.push(call.apply(self)?);
+        }
+
+        Ok(Record(values))
+    }
+
     pub fn match_condition(&self, condition: &Option<Vec<ConditionValue>>) -> Result<bool, Error> {
         match &condition {
             Some(rules) => {
-                let mut rules_iter = rules.iter().peekable();
+                let calls: Vec<&Call> = rules
+                    .iter()
+                    .filter_map(|x| match x {
+                        ConditionValue::Call(call) => Some(call),
+                        _ => None,
+                    })
+                    .collect();
+                let with_calls;
+                let row = if calls.is_empty() {
+                    self
+                } else {
+                    with_calls = self.with_calls(calls)?;
+                    &with_calls
+                };
+
+                let mut rules_iter = rules
+                    .iter()
+                    .filter(|x| !matches!(x, ConditionValue::Call(_)))
+                    .peekable();
 
                 let mut result = false;
 
@@ -267,66 +757,75 @@ impl Record {
                             values,
                         } => match opt {
                             Operation::Equal => {
-                                let column_value = self.0.get(idx.to_owned()).ok_or_else(|| {
-                                    Error::UnexpectedWithReason("Failed to get column value.")
-                                })?;
+                                let column_value = row.0.get(idx.to_owned()).ok_or(
+                                    Error::UnexpectedWithReason("Failed to get column value."),
+                                )?;
 
-                                result = column_value == &values[0];
+                                result = column_value.compare(&values[0]) == Some(Ordering::Equal);
 
                                 if *invert {
                                     result = !result;
                                 }
                             }
                             Operation::GT => {
-                                let column_value = self.0.get(idx.to_owned()).ok_or_else(|| {
-                                    Error::UnexpectedWithReason("Failed to get column value.")
-                                })?;
+                                let column_value = row.0.get(idx.to_owned()).ok_or(
+                                    Error::UnexpectedWithReason("Failed to get column value."),
+                                )?;
 
-                                result = column_value > &values[0];
+                                result =
+                                    column_value.compare(&values[0]) == Some(Ordering::Greater);
 
                                 if *invert {
                                     result = !result;
                                 }
                             }
                             Operation::LT => {
-                                let column_value = self.0.get(idx.to_owned()).ok_or_else(|| {
-                                    Error::UnexpectedWithReason("Failed to get column value.")
-                                })?;
+                                let column_value = row.0.get(idx.to_owned()).ok_or(
+                                    Error::UnexpectedWithReason("Failed to get column value."),
+                                )?;
 
-                                result = column_value < &values[0];
+                                result = column_value.compare(&values[0]) == Some(Ordering::Less);
 
                                 if *invert {
                                     result = !result;
                                 }
                             }
                             Operation::GTE => {
-                                let column_value = self.0.get(idx.to_owned()).ok_or_else(|| {
-                                    Error::UnexpectedWithReason("Failed to get column value.")
-                                })?;
+                                let column_value = row.0.get(idx.to_owned()).ok_or(
+                                    Error::UnexpectedWithReason("Failed to get column value."),
+                                )?;
 
-                                result = column_value >= &values[0];
+                                result = matches!(
+                                    column_value.compare(&values[0]),
+                                    Some(Ordering::Greater | Ordering::Equal)
+                                );
 
                                 if *invert {
                                     result = !result;
                                 }
                             }
                             Operation::LTE => {
-                                let column_value = self.0.get(idx.to_owned()).ok_or_else(|| {
-                                    Error::UnexpectedWithReason("Failed to get column value.")
-                                })?;
+                                let column_value = row.0.get(idx.to_owned()).ok_or(
+                                    Error::UnexpectedWithReason("Failed to get column value."),
+                                )?;
 
-                                result = column_value <= &values[0];
+                                result = matches!(
+                                    column_value.compare(&values[0]),
+                                    Some(Ordering::Less | Ordering::Equal)
+                                );
 
                                 if *invert {
                                     result = !result;
                                 }
                             }
                             Operation::BETWEEN => {
-                                let column_value = self.0.get(idx.to_owned()).ok_or_else(|| {
-                                    Error::UnexpectedWithReason("Failed to get column value.")
-                                })?;
+                                let column_value = row.0.get(idx.to_owned()).ok_or(
+                                    Error::UnexpectedWithReason("Failed to get column value."),
+                                )?;
 
-                                result = column_value > &values[0] && column_value < &values[1];
+                                result = column_value.compare(&values[0])
+                                    == Some(Ordering::Greater)
+                                    && column_value.compare(&values[1]) == Some(Ordering::Less);
 
                                 if *invert {
                                     result = !result;
@@ -386,6 +885,7 @@ mod tests {
     use super::ConditionValue;
     use super::Record;
     use super::Value;
+    use std::cmp::Ordering;
 
     #[test]
     fn test_match_condition() {
@@ -451,6 +951,228 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_int() {
+        assert!(Value::Int(-1) < Value::UInt(0));
+        assert!(Value::Int(5) > Value::U64(4));
+        assert_eq!(Value::UInt(3), Value::Int(3));
+        assert!(Value::String("a".into()) < Value::Int(-10));
+        assert!(Value::Int(i64::MAX) < Value::Null);
+
+        let config = bincode::config::standard();
+        let bin = bincode::serde::encode_to_vec(Value::Int(-42), config).expect("Failed to encode");
+        let (value, _): (Value, usize) =
+            bincode::serde::decode_from_slice(&bin, config).expect("Failed to decode");
+        assert!(matches!(value, Value::Int(-42)));
+
+        let json = serde_json::to_string(&Record(vec![Value::Int(-42), Value::UInt(1)]))
+            .expect("Failed to serialize");
+        assert_eq!(json, "[-42,1]");
+    }
+
+    #[test]
+    fn test_real() {
+        use super::{Arithmetic, DataType, Rounding};
+
+        assert!(Value::Int(1) < Value::Real(1.5));
+        assert!(Value::Real(1.5) < Value::UInt(2));
+        assert_eq!(Value::Real(3.0), Value::U64(3));
+        assert_eq!(Value::Real(-0.0), Value::Real(0.0));
+        assert!(Value::Int(i64::MAX) < Value::Real(i64::MAX as f64 * 2.0));
+        assert!(Value::Real(-2.5) < Value::Int(-2));
+
+        // NaN is larger than every number and equal to itself.
+        assert!(Value::Real(f64::INFINITY) < Value::Real(f64::NAN));
+        assert!(Value::Int(i64::MAX) < Value::Real(f64::NAN));
+        assert_eq!(Value::Real(f64::NAN), Value::Real(f64::NAN));
+        assert!(Value::Real(f64::NAN) < Value::Null);
+
+        let mut values = vec![Value::Real(f64::NAN), Value::Real(2.5), Value::Int(-1)];
+        values.sort();
+        assert_eq!(
+            values,
+            vec![Value::Int(-1), Value::Real(2.5), Value::Real(f64::NAN)]
+        );
+
+        // the order is transitive across every type of number.
+        let numbers = [
+            Value::Decimal(9007199254740993, 0),
+            Value::Real(9007199254740992.0),
+            Value::Int(9007199254740993),
+            Value::Decimal(90071992547409925, 1),
+            Value::Real(0.1),
+            Value::Decimal(1, 1),
+            Value::UInt(0),
+        ];
+        for a in &numbers {
+            for b in &numbers {
+                for c in &numbers {
+                    if a <= b && b <= c {
+                        assert!(a <= c, "{} <= {} <= {}", a, b, c);
+                    }
+                }
+                assert_eq!(a.cmp(b), b.cmp(a).reverse());
+            }
+        }
+        assert!(Value::Real(9007199254740992.0) < Value::Decimal(9007199254740993, 0));
+        assert!(Value::Decimal(1, 1) < Value::Real(0.1));
+
+        // a computed real is finite like a parsed one.
+        let real = |a: f64, op: Arithmetic, b: f64| {
+            Value::Real(a).arithmetic(op, &Value::Real(b), DataType::Real, Rounding::default())
+        };
+        assert_eq!(
+            real(1.5, Arithmetic::Multiply, 2.0).ok(),
+            Some(Value::Real(3.0))
+        );
+        assert!(real(f64::MAX, Arithmetic::Multiply, 2.0).is_err());
+        assert!(real(1.0, Arithmetic::Divide, 0.0).is_err());
+        assert!(real(0.0, Arithmetic::Divide, 0.0).is_err());
+
+        assert_eq!(Value::Real(2.0).to_string(), "2.0");
+        assert_eq!(
+            serde_json::to_string(&Value::Real(0.25)).expect("Failed to serialize"),
+            "0.25"
+        );
+    }
+
+    #[test]
+    fn test_bool() {
+        assert!(Value::Bool(false) < Value::Bool(true));
+        assert_ne!(Value::Bool(true), Value::UInt(1));
+
+        let config = bincode::config::standard();
+        let bin =
+            bincode::serde::encode_to_vec(Value::Bool(true), config).expect("Failed to encode");
+        assert_eq!(bin.len(), 2);
+
+        let json = serde_json::to_string(&Record(vec![Value::Bool(true), Value::Bool(false)]))
+            .expect("Failed to serialize");
+        assert_eq!(json, "[true,false]");
+    }
+
+    #[test]
+    fn test_blob() {
+        let blob = Value::Blob(vec![0xDE, 0xAD, 0xBE, 0xEF]);
+        assert_eq!(blob.to_string(), "X'DEADBEEF'");
+        assert!(Value::Blob(vec![0x01]) < Value::Blob(vec![0x01, 0x00]));
+        assert!(Value::String("a".into()) < blob);
+
+        let json = serde_json::to_string(&Record(vec![
+            blob.clone(),
+            Value::Blob(vec![0xDE]),
+            Value::Blob(vec![]),
+        ]))
+        .expect("Failed to serialize");
+        assert_eq!(json, r#"["3q2+7w==","3g==",""]"#);
+
+        let config = bincode::config::standard();
+        let bin = bincode::serde::encode_to_vec(&blob, config).expect("Failed to encode");
+        let (value, _): (Value, usize) =
+            bincode::serde::decode_from_slice(&bin, config).expect("Failed to decode");
+        assert_eq!(value, blob);
+
+        let overflow = Value::Overflow {
+            page: 8192,
+            len: 100,
+        };
+        let bin = bincode::serde::encode_to_vec(&overflow, config).expect("Failed to encode");
+        let (value, _): (Value, usize) =
+            bincode::serde::decode_from_slice(&bin, config).expect("Failed to decode");
+        assert!(matches!(
+            value,
+            Value::Overflow {
+                page: 8192,
+                len: 100
+            }
+        ));
+
+        // an overflow is never equal to a blob, or to an overflow stored somewhere else.
+        let other = Value::Overflow {
+            page: 12288,
+            len: 100,
+        };
+        assert_eq!(blob.cmp(&overflow), Ordering::Less);
+        assert_eq!(overflow.cmp(&blob), Ordering::Greater);
+        assert_eq!(overflow.cmp(&other), Ordering::Less);
+        assert_eq!(overflow.clone(), value);
+    }
+
+    #[test]
+    fn test_temporal() {
+        assert!(Value::Date(-1) < Value::Date(0));
+        assert!(Value::Timestamp(1) > Value::Timestamp(-1));
+        assert_ne!(Value::Date(0), Value::Timestamp(0));
+
+        let json = serde_json::to_string(&Record(vec![
+            Value::Date(19_753),
+            Value::Time(49_500_000_000),
+            Value::Timestamp(1_706_708_700_000_000),
+        ]))
+        .expect("Failed to serialize");
+        assert_eq!(json, r#"["2024-01-31","13:45:00","2024-01-31T13:45:00Z"]"#);
+    }
+
+    #[test]
+    fn test_cast() {
+        use super::DataType;
+
+        let cast = |value: Value, data_type: DataType| value.cast(data_type).expect("Failed");
+
+        assert_eq!(
+            cast(Value::String("12abc".into()), DataType::Int),
+            Value::Int(12)
+        );
+        assert_eq!(
+            cast(Value::String("abc".into()), DataType::UInt),
+            Value::UInt(0)
+        );
+        assert_eq!(
+            cast(Value::String(" 1.5e1x".into()), DataType::Real),
+            Value::Real(15.0)
+        );
+        assert_eq!(cast(Value::Real(-2.9), DataType::Int), Value::Int(-2));
+        assert!(Value::String("1e999".into()).cast(DataType::Real).is_err());
+        assert_eq!(cast(Value::Decimal(1299, 2), DataType::U64), Value::U64(12));
+        assert_eq!(
+            cast(Value::Int(12), DataType::String),
+            Value::String("12".into())
+        );
+        assert_eq!(cast(Value::Bool(true), DataType::Int), Value::Int(1));
+        assert_eq!(
+            cast(Value::String("0".into()), DataType::Boolean),
+            Value::Bool(false)
+        );
+        assert_eq!(
+            cast(
+                Value::Real(1.005),
+                DataType::Decimal {
+                    precision: 5,
+                    scale: 2
+                }
+            ),
+            Value::Decimal(101, 2)
+        );
+        assert_eq!(
+            cast(Value::String("hi".into()), DataType::Blob),
+            Value::Blob(b"hi".to_vec())
+        );
+        assert_eq!(
+            cast(Value::Timestamp(86_400_000_001), DataType::Date),
+            Value::Date(1)
+        );
+        assert_eq!(cast(Value::Null, DataType::Int), Value::Null);
+        assert!(Value::Int(-1).cast(DataType::UInt).is_err());
+        assert!(Value::String("May".into()).cast(DataType::Date).is_err());
+
+        assert_eq!(
+            Value::Int(2).compare(&Value::Real(2.0)),
+            Some(Ordering::Equal)
+        );
+        assert_eq!(Value::String("2".into()).compare(&Value::Int(2)), None);
+        assert_eq!(Value::Null.compare(&Value::Null), None);
+    }
+
     #[test]
     fn test_deseralize() {
         let config = bincode::config::standard();
@@ -0,0 +1,72 @@
+use std::{fmt::Display, path::Path, str::FromStr};
+
+use super::error::Error;
+
+/// Controls when written data is flushed to disk, works like sqlite's `PRAGMA synchronous`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum Synchronous {
+    /// Never fsync, the operating system decides when data reaches the disk.
+    Off,
+    /// The table file is synced before a new root is written to the wal.
+    /// A power loss can roll back the last statements but the table is never left pointing at unwritten pages.
+    Normal,
+    /// Like `Normal`, but the wal is synced after every statement and directory entries
+    /// are synced when tables are created or dropped, so acknowledged writes survive a power loss.
+    #[default]
+    Full,
+}
+
+impl FromStr for Synchronous {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "off" | "0" => Ok(Synchronous::Off),
+            "normal" | "1" => Ok(Synchronous::Normal),
+            "full" | "2" => Ok(Synchronous::Full),
+            _ => Err(Error::Validate(format!(
+                "Unknown synchronous mode '{}', expected OFF, NORMAL or FULL.",
+                value
+            ))),
+        }
+    }
+}
+
+impl Display for Synchronous {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Synchronous::Off => write!(f, "OFF"),
+            Synchronous::Normal => write!(f, "NORMAL"),
+            Synchronous::Full => write!(f, "FULL"),
+        }
+    }
+}
+
+/// Flush a directory so newly created or removed entries in it survive a power loss.
+#[cfg(unix)]
+pub fn sync_dir(path: &Path) -> Result<(), Error> {
+    std::fs::File::open(path)?.sync_all()?;
+    Ok(())
+}
+
+/// Directories can not be opened as files on this platform, so there is nothing to sync.
+#[cfg(not(unix))]
+pub fn sync_dir(_path: &Path) -> Result<(), Error> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Synchronous;
+
+    #[test]
+    fn test_parse_synchronous() {
+        assert_eq!("off".parse::<Synchronous>().ok(), Some(Synchronous::Off));
+        assert_eq!(
+            "NORMAL".parse::<Synchronous>().ok(),
+            Some(Synchronous::Normal)
+        );
+        assert_eq!("2".parse::<Synchronous>().ok(), Some(Synchronous::Full));
+        assert!("fast".parse::<Synchronous>().is_err());
+    }
+}
@@ -0,0 +1,416 @@
+use std::collections::BTreeSet;
+
+use crate::sql::{interperter::ColumnData, ColumnExpr, Condition};
+
+use super::{
+    btree::BTree,
+    decimal::Rounding,
+    error::Error,
+    index::Index,
+    node_type::Schema,
+    planner::{Access, Plan, Planner},
+    structure::{Record, Value},
+};
+
+/// A table together with its indexes, keeping the indexes up to date on every write.
+///
+/// Tables without an index are read and written exactly like before, through their tree.
+pub struct Table {
+    pub tree: BTree,
+    pub indexes: Vec<Index>,
+}
+
+impl Table {
+    pub fn new(tree: BTree, indexes: Vec<Index>) -> Self {
+        Self { tree, indexes }
+    }
+
+    /// Split the table back into the trees it is made of.
+    pub fn into_parts(self) -> (BTree, Vec<Index>) {
+        (self.tree, self.indexes)
+    }
+
+    pub fn select(
+        &mut self,
+        keep: &Vec<ColumnExpr>,
+        target: &Option<Vec<Condition>>,
+    ) -> Result<Vec<Record>, Error> {
+        let mut rows = vec![];
+        self.select_each(keep, target, |row| {
+            rows.push(row);
+            Ok::<(), Error>(())
+        })?;
+
+        Ok(rows)
+    }
+
+    /// Like `select`, but every row is handed to `output` as soon as it is read
+    /// instead of being collected first.
+    pub fn select_each<E: From<Error>>(
+        &mut self,
+        keep: &Vec<ColumnExpr>,
+        target: &Option<Vec<Condition>>,
+        mut output: impl FnMut(Record) -> Result<(), E>,
+    ) -> Result<(), E> {
+        let schema = self.tree.get_table()?;
+        let mut calls = vec![];
+        let indexs = schema.get_indexs_from_names(keep, &mut calls)?;
+
+        self.each_matching(&schema, target, |row| {
+            if keep.is_empty() {
+                output(row)
+            } else {
+                output(row.with_calls(&calls)?.select_only(&indexs))
+            }
+        })
+    }
+
+    /// How the rows matching `target` would be read.
+    pub fn plan(&mut self, target: &Option<Vec<Condition>>) -> Result<Plan, Error> {
+        let schema = self.tree.get_table()?;
+        let selection = match target {
+            Some(cond) => Some(schema.parse_conditions(cond)?),
+            None => None,
+        };
+
+        let stats = self.tree.get_stats()?;
+
+        Ok(Planner::new(&schema, &self.indexes)
+            .stats(stats.as_ref())
+            .plan(selection.as_deref()))
+    }
+
+    /// Full rows matching `target`.
+    fn matching(
+        &mut self,
+        schema: &Schema,
+        target: &Option<Vec<Condition>>,
+    ) -> Result<Vec<Record>, Error> {
+        let mut rows = vec![];
+        self.each_matching(schema, target, |row| {
+            rows.push(row);
+            Ok::<(), Error>(())
+        })?;
+
+        Ok(rows)
+    }
+
+    /// Hand every full row matching `target` to `output`, read the way the planner picks.
+    fn each_matching<E: From<Error>>(
+        &mut self,
+        schema: &Schema,
+        target: &Option<Vec<Condition>>,
+        mut output: impl FnMut(Record) -> Result<(), E>,
+    ) -> Result<(), E> {
+        let selection = match target {
+            Some(cond) => Some(schema.parse_conditions(cond)?),
+            None => None,
+        };
+
+        // without an index there is nothing to choose from.
+        let access = if self.indexes.is_empty() {
+            Access::Scan
+        } else {
+            let stats = self.tree.get_stats()?;
+            Planner::new(schema, &self.indexes)
+                .stats(stats.as_ref())
+                .plan(selection.as_deref())
+                .access
+        };
+
+        match access {
+            Access::Scan => {
+                for row in self.tree.cursor()? {
+                    let row = row?;
+                    if row.match_condition(&selection)? {
+                        output(row)?;
+                    }
+                }
+            }
+            Access::Search { index, seek } => {
+                let Index { tree, columns, .. } = &mut self.indexes[index];
+
+                // the index is read while the rows it points at are looked up.
+                for entry in tree.range_cursor(seek.lower, seek.upper)? {
+                    let key = Index::entry_primary_key(columns.len(), &entry?)?;
+                    let row = match self.tree.get(&key)? {
+                        Some(row) => row,
+                        None => continue,
+                    };
+
+                    if row.match_condition(&selection)? {
+                        output(row)?;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn insert(&mut self, row: Record) -> Result<(), Error> {
+        if self.indexes.is_empty() {
+            return self.tree.insert(row);
+        }
+
+        let schema = self.tree.get_table()?;
+
+        for index in self.indexes.iter_mut().filter(|x| x.unique) {
+            let values = index.values(&row)?;
+            if !index.find(values.clone())?.is_empty() {
+                return Err(index.unique_error(&values));
+            }
+        }
+
+        self.tree.insert(row.clone())?;
+
+        let mut inserted = 0;
+        let result = self.indexes.iter_mut().try_for_each(|index| {
+            index.tree.insert(index.entry(&schema, &row)?)?;
+            inserted += 1;
+            Ok(())
+        });
+
+        // an index the row could not be added to, a value too large for it, takes the
+        // row out of the table and the other indexes again so they all still agree.
+        if let Err(err) = result {
+            for index in self.indexes.iter_mut().take(inserted) {
+                let key = index.key(&schema, &row)?;
+                index.tree.remove(&[key])?;
+            }
+            self.tree.remove(&[schema.key(&row)?])?;
+
+            return Err(err);
+        }
+
+        Ok(())
+    }
+
+    pub fn delete(&mut self, target: &Vec<Condition>) -> Result<(), Error> {
+        if self.indexes.is_empty() {
+            return self.tree.delete(Some(target));
+        }
+
+        let schema = self.tree.get_table()?;
+        let rows = self.matching(&schema, &Some(target.to_vec()))?;
+
+        let keys = rows
+            .iter()
+            .map(|row| schema.key(row))
+            .collect::<Result<Vec<Value>, Error>>()?;
+        self.tree.remove(&keys)?;
+
+        for index in self.indexes.iter_mut() {
+            let keys = rows
+                .iter()
+                .map(|row| index.key(&schema, row))
+                .collect::<Result<Vec<Value>, Error>>()?;
+            index.tree.remove(&keys)?;
+        }
+
+        Ok(())
+    }
+
+    /// Updated rows are removed and inserted again when their primary key or
+    /// indexed values may have changed, so the trees stay in key order.
+    pub fn update(
+        &mut self,
+        columns: &Vec<(String, ColumnData)>,
+        target: &Option<Vec<Condition>>,
+        rounding: Rounding,
+    ) -> Result<(), Error> {
+        let schema = self.tree.get_table()?;
+        let update = schema.parse_assignments(columns, rounding)?;
+
+        // only columns outside of the key can be changed where the rows are.
+        let key_columns = schema.key_columns();
+        if self.indexes.is_empty() && !update.iter().any(|(_, idx)| key_columns.contains(idx)) {
+            return self.tree.update(columns, target, rounding);
+        }
+
+        let rows = self.matching(&schema, target)?;
+
+        let mut updated = rows.clone();
+        for row in updated.iter_mut() {
+            schema.assign(row, &update, rounding)?;
+        }
+
+        let old_keys = rows
+            .iter()
+            .map(|row| schema.key(row))
+            .collect::<Result<Vec<Value>, Error>>()?;
+        let replaced: BTreeSet<&Value> = old_keys.iter().collect();
+
+        // check every constraint before anything is written.
+        let mut new_keys = BTreeSet::new();
+        for row in &updated {
+            self.tree.check_row(row)?;
+
+            let key = schema.key(row)?;
+            if !new_keys.insert(key.clone())
+                || (!replaced.contains(&key) && self.tree.get(&key)?.is_some())
+            {
+                return Err(Error::Validate(format!(
+                    "UNIQUE constraint failed: primary key {}",
+                    key
+                )));
+            }
+        }
+
+        for index in self.indexes.iter_mut() {
+            for row in &updated {
+                index.tree.check_row(&index.entry(&schema, row)?)?;
+            }
+        }
+
+        for index in self.indexes.iter_mut().filter(|x| x.unique) {
+            let mut seen = BTreeSet::new();
+            for row in &updated {
+                let values = index.values(row)?;
+                if values.contains(&Value::Null) {
+                    continue;
+                }
+
+                let taken = index.find(values.clone())?.iter().any(|entry| {
+                    index
+                        .primary_key(entry)
+                        .is_ok_and(|x| !replaced.contains(&x))
+                });
+
+                if taken || !seen.insert(values.clone()) {
+                    return Err(index.unique_error(&values));
+                }
+            }
+        }
+
+        let mut changes = vec![Change {
+            old_keys,
+            old_rows: rows.clone(),
+            new_keys: new_keys.into_iter().collect(),
+            new_rows: updated.clone(),
+        }];
+        for index in &self.indexes {
+            let keys = |rows: &[Record]| {
+                rows.iter()
+                    .map(|row| index.key(&schema, row))
+                    .collect::<Result<Vec<Value>, Error>>()
+            };
+            let entries = |rows: &[Record]| {
+                rows.iter()
+                    .map(|row| index.entry(&schema, row))
+                    .collect::<Result<Vec<Record>, Error>>()
+            };
+
+            changes.push(Change {
+                old_keys: keys(&rows)?,
+                old_rows: entries(&rows)?,
+                new_keys: keys(&updated)?,
+                new_rows: entries(&updated)?,
+            });
+        }
+
+        let mut trees: Vec<&mut BTree> = std::iter::once(&mut self.tree)
+            .chain(self.indexes.iter_mut().map(|index| &mut index.tree))
+            .collect();
+
+        // a row that still does not fit changes the trees that were already written back,
+        // so a failed update leaves the table and its indexes as they were.
+        for idx in 0..changes.len() {
+            if let Err(err) = changes[idx].apply(trees[idx]) {
+                for done in (0..idx).rev() {
+                    changes[done].undo(trees[done])?;
+                }
+                return Err(err);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Create an index named `name` on `columns` in the new tree `tree`, and fill it
+    /// with the rows of the table.
+    pub fn create_index(
+        &mut self,
+        tree: BTree,
+        name: &str,
+        columns: &[String],
+        unique: bool,
+    ) -> Result<(), Error> {
+        let schema = self.tree.get_table()?;
+        let index_schema = Index::schema(name, &schema, columns, unique)?;
+
+        let mut rows = self.tree.select(&vec![], &None, None)?;
+
+        // tables written before rows were kept in key order are written again,
+        // so the rows an index points at can be found by their primary key.
+        let ordered = self.tree.is_ordered()?;
+        if !ordered {
+            rows.sort_by_key(|row| schema.key(row).ok());
+
+            for pair in rows.windows(2) {
+                let key = schema.key(&pair[0])?;
+                if key == schema.key(&pair[1])? {
+                    return Err(Error::Validate(format!(
+                        "Can not index table '{}', primary key {} is used more then once.",
+                        schema.name, key
+                    )));
+                }
+            }
+        }
+
+        let columns = Index::column_indexes(&schema, columns)?;
+        let mut index = Index::new(name.to_owned(), columns, unique, tree);
+
+        let mut entries = rows
+            .iter()
+            .map(|row| index.entry(&schema, row))
+            .collect::<Result<Vec<Record>, Error>>()?;
+        entries.sort();
+
+        if unique {
+            for pair in entries.windows(2) {
+                let values = &pair[0].0[..index.columns.len()];
+                if !values.contains(&Value::Null) && values == &pair[1].0[..index.columns.len()] {
+                    return Err(index.unique_error(values));
+                }
+            }
+        }
+
+        if !ordered {
+            self.tree.rebuild(rows)?;
+        }
+
+        index.tree.create_table(index_schema)?;
+        index.tree.insert_many(entries)?;
+        self.indexes.push(index);
+
+        Ok(())
+    }
+}
+
+/// Rows of one tree that are replaced by an update.
+struct Change {
+    old_keys: Vec<Value>,
+    old_rows: Vec<Record>,
+    new_keys: Vec<Value>,
+    new_rows: Vec<Record>,
+}
+
+impl Change {
+    /// Replace the old rows by the new ones, putting the old ones back when that fails.
+    fn apply(&self, tree: &mut BTree) -> Result<(), Error> {
+        tree.remove(&self.old_keys)?;
+
+        if let Err(err) = tree.insert_many(self.new_rows.clone()) {
+            tree.insert_many(self.old_rows.clone())?;
+            return Err(err);
+        }
+
+        Ok(())
+    }
+
+    fn undo(&self, tree: &mut BTree) -> Result<(), Error> {
+        tree.remove(&self.new_keys)?;
+        tree.insert_many(self.old_rows.clone())
+    }
+}
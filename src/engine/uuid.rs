@@ -0,0 +1,138 @@
+//! 16 byte UUIDs for `UUID` columns, written in the canonical form
+//! `0190b3c4-5e6f-7a8b-9c0d-1e2f3a4b5c6d`.
+
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Milliseconds and counter of the last v7 UUID made.
+static LAST_V7: Mutex<(u64, u16)> = Mutex::new((0, 0));
+static CALLS: AtomicU64 = AtomicU64::new(0);
+
+/// Parse `value` in the canonical form, upper or lower case. The 32 hex digits without the
+/// hyphens are taken as well.
+pub fn parse(value: &str) -> Option<[u8; 16]> {
+    let digits: String = match value.len() {
+        32 => value.to_owned(),
+        36 => {
+            let groups: Vec<&str> = value.split('-').collect();
+            let lengths: Vec<usize> = groups.iter().map(|x| x.len()).collect();
+            if lengths != [8, 4, 4, 4, 12] {
+                return None;
+            }
+            groups.concat()
+        }
+        _ => return None,
+    };
+
+    if !digits.bytes().all(|x| x.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    u128::from_str_radix(&digits, 16)
+        .ok()
+        .map(|x| x.to_be_bytes())
+}
+
+pub fn format(uuid: &[u8; 16]) -> String {
+    let hex = format!("{:032x}", u128::from_be_bytes(*uuid));
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+/// 128 bits no one can guess. Every `RandomState` gets new random keys, so hashing with one
+/// is enough without a crate for random numbers.
+fn random() -> u128 {
+    let mut bits = 0;
+    for _ in 0..2 {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u64(CALLS.fetch_add(1, Ordering::Relaxed));
+        bits = (bits << 64) | hasher.finish() as u128;
+    }
+    bits
+}
+
+/// Set the version and the variant of the RFC 9562 layout.
+fn with_version(value: u128, version: u128) -> [u8; 16] {
+    let value = (value & !(0xF << 76)) | (version << 76);
+    ((value & !(0b11 << 62)) | (0b10 << 62)).to_be_bytes()
+}
+
+/// A random UUID.
+pub fn v4() -> [u8; 16] {
+    with_version(random(), 4)
+}
+
+/// A UUID starting with the current time in milliseconds, so new ones are added at the end of
+/// a tree. The 12 bits after the version count up within a millisecond, so UUIDs made one after
+/// another are ordered even when the clock did not move or went back.
+pub fn v7() -> [u8; 16] {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |x| x.as_millis() as u64);
+    let random = random();
+
+    let (millis, counter) = {
+        let mut last = LAST_V7.lock().unwrap_or_else(|x| x.into_inner());
+        *last = match *last {
+            // the counter starts in its lower half, so there is room to count up.
+            (millis, _) if now > millis => (now, (random >> 64) as u16 & 0x7FF),
+            (millis, counter) if counter < 0xFFF => (millis, counter + 1),
+            (millis, _) => (millis + 1, 0),
+        };
+        *last
+    };
+
+    let value = ((millis as u128 & 0xFFFF_FFFF_FFFF) << 80)
+        | ((counter as u128) << 64)
+        | (random & u64::MAX as u128);
+    with_version(value, 7)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_format() {
+        let uuid = parse("0190B3C4-5E6F-7A8B-9C0D-1E2F3A4B5C6D").expect("Failed to parse");
+
+        assert_eq!(uuid[0], 0x01);
+        assert_eq!(uuid[15], 0x6D);
+        assert_eq!(format(&uuid), "0190b3c4-5e6f-7a8b-9c0d-1e2f3a4b5c6d");
+        assert_eq!(parse("0190b3c45e6f7a8b9c0d1e2f3a4b5c6d"), Some(uuid));
+
+        assert_eq!(parse("0190b3c4-5e6f7a8b-9c0d-1e2f3a4b5c6d-"), None);
+        assert_eq!(parse("0190b3c4-5e6f-7a8b-9c0d-1e2f3a4b5c6g"), None);
+        assert_eq!(parse("+190b3c45e6f7a8b9c0d1e2f3a4b5c6d"), None);
+    }
+
+    #[test]
+    fn test_generate() {
+        let random = v4();
+        assert_eq!(random[6] >> 4, 4);
+        assert_eq!(random[8] >> 6, 0b10);
+        assert_ne!(v4(), random);
+
+        let mut last = v7();
+        assert_eq!(last[6] >> 4, 7);
+        assert_eq!(last[8] >> 6, 0b10);
+        for _ in 0..5000 {
+            let next = v7();
+            assert!(next > last);
+            last = next;
+        }
+    }
+}
@@ -0,0 +1,343 @@
+use std::{
+    cell::Cell,
+    collections::{BTreeSet, HashMap},
+    fmt::Debug,
+    fs::{self, File, OpenOptions},
+    io::{Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, SystemTime},
+};
+
+use super::{
+    error::Error,
+    file_lock::{lock_file, retry_lock, unlock_file, LockMode},
+    synchronous::sync_dir,
+};
+
+/// Everything the engine does with the file system goes through a `Vfs`,
+/// so tables can be kept on disk, in memory or anywhere else.
+pub trait Vfs: Debug + Send + Sync {
+    /// Open the file at `path`, creating it when it does not exist.
+    fn open(&self, path: &Path) -> Result<Box<dyn VfsFile>, Error>;
+    fn exists(&self, path: &Path) -> bool;
+    /// Entries directly inside the directory at `path`.
+    fn list_dir(&self, path: &Path) -> Result<Vec<PathBuf>, Error>;
+    /// Create the directory at `path` and its parents.
+    fn create_dir(&self, path: &Path) -> Result<(), Error>;
+    /// Remove the directory at `path` and everything in it.
+    fn remove_dir(&self, path: &Path) -> Result<(), Error>;
+    /// Make sure files created in or removed from the directory survive a power loss.
+    fn sync_dir(&self, path: &Path) -> Result<(), Error>;
+}
+
+/// A file opened by a `Vfs`.
+pub trait VfsFile: Send {
+    /// Fill `buf` with the bytes starting at `offset`.
+    fn read_at(&mut self, buf: &mut [u8], offset: u64) -> Result<(), Error>;
+    fn write_at(&mut self, buf: &[u8], offset: u64) -> Result<(), Error>;
+    fn len(&self) -> Result<u64, Error>;
+    fn modified(&self) -> Option<SystemTime>;
+    fn sync(&self) -> Result<(), Error>;
+    fn truncate(&mut self, len: u64) -> Result<(), Error>;
+    /// Wait up to `timeout` for the lock, returning `Error::Busy` when it could not be taken.
+    fn lock(&self, mode: LockMode, timeout: Duration) -> Result<(), Error>;
+    fn unlock(&self) -> Result<(), Error>;
+}
+
+/// Files on disk.
+#[derive(Debug, Default)]
+pub struct OsVfs;
+
+impl Vfs for OsVfs {
+    fn open(&self, path: &Path) -> Result<Box<dyn VfsFile>, Error> {
+        let fd = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .write(true)
+            .truncate(false)
+            .open(path)?;
+
+        Ok(Box::new(fd))
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        path.exists()
+    }
+
+    fn list_dir(&self, path: &Path) -> Result<Vec<PathBuf>, Error> {
+        let mut entries = vec![];
+        for entry in path.read_dir()? {
+            entries.push(entry?.path());
+        }
+        Ok(entries)
+    }
+
+    fn create_dir(&self, path: &Path) -> Result<(), Error> {
+        fs::create_dir_all(path)?;
+        Ok(())
+    }
+
+    fn remove_dir(&self, path: &Path) -> Result<(), Error> {
+        fs::remove_dir_all(path)?;
+        Ok(())
+    }
+
+    fn sync_dir(&self, path: &Path) -> Result<(), Error> {
+        sync_dir(path)
+    }
+}
+
+impl VfsFile for File {
+    fn read_at(&mut self, buf: &mut [u8], offset: u64) -> Result<(), Error> {
+        self.seek(SeekFrom::Start(offset))?;
+        self.read_exact(buf)?;
+        Ok(())
+    }
+
+    fn write_at(&mut self, buf: &[u8], offset: u64) -> Result<(), Error> {
+        self.seek(SeekFrom::Start(offset))?;
+        self.write_all(buf)?;
+        Ok(())
+    }
+
+    fn len(&self) -> Result<u64, Error> {
+        Ok(self.metadata()?.len())
+    }
+
+    fn modified(&self) -> Option<SystemTime> {
+        self.metadata().and_then(|x| x.modified()).ok()
+    }
+
+    fn sync(&self) -> Result<(), Error> {
+        self.sync_data()?;
+        Ok(())
+    }
+
+    fn truncate(&mut self, len: u64) -> Result<(), Error> {
+        self.set_len(len)?;
+        Ok(())
+    }
+
+    fn lock(&self, mode: LockMode, timeout: Duration) -> Result<(), Error> {
+        lock_file(self, mode, timeout)
+    }
+
+    fn unlock(&self) -> Result<(), Error> {
+        unlock_file(self)
+    }
+}
+
+#[derive(Default)]
+struct MemoryBuffer {
+    data: Vec<u8>,
+    readers: usize,
+    writer: bool,
+}
+
+type MemoryFiles = HashMap<PathBuf, Arc<Mutex<MemoryBuffer>>>;
+
+/// Files kept in memory, shared by every handle opened from the same vfs.
+#[derive(Clone, Default)]
+pub struct MemoryVfs {
+    files: Arc<Mutex<MemoryFiles>>,
+}
+
+impl MemoryVfs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn files(&self) -> Result<MutexGuard<'_, MemoryFiles>, Error> {
+        self.files.lock().map_err(|_| Error::Lock)
+    }
+}
+
+impl Debug for MemoryVfs {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("MemoryVfs")
+    }
+}
+
+impl Vfs for MemoryVfs {
+    fn open(&self, path: &Path) -> Result<Box<dyn VfsFile>, Error> {
+        let buffer = self.files()?.entry(path.to_path_buf()).or_default().clone();
+
+        Ok(Box::new(MemoryFile {
+            buffer,
+            held: Cell::new(None),
+        }))
+    }
+
+    /// Directories only exist through the files in them.
+    fn exists(&self, path: &Path) -> bool {
+        self.files()
+            .map(|files| files.keys().any(|x| x.starts_with(path)))
+            .unwrap_or(false)
+    }
+
+    fn list_dir(&self, path: &Path) -> Result<Vec<PathBuf>, Error> {
+        let entries: BTreeSet<PathBuf> = self
+            .files()?
+            .keys()
+            .filter_map(|x| x.strip_prefix(path).ok())
+            .filter_map(|x| x.iter().next())
+            .map(|x| path.join(x))
+            .collect();
+
+        Ok(entries.into_iter().collect())
+    }
+
+    fn create_dir(&self, _path: &Path) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn remove_dir(&self, path: &Path) -> Result<(), Error> {
+        self.files()?.retain(|x, _| !x.starts_with(path));
+        Ok(())
+    }
+
+    fn sync_dir(&self, _path: &Path) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// Handle to a file of a `MemoryVfs`.
+pub struct MemoryFile {
+    buffer: Arc<Mutex<MemoryBuffer>>,
+    /// Lock held through this handle, so `unlock` knows what to release.
+    held: Cell<Option<LockMode>>,
+}
+
+impl MemoryFile {
+    fn buffer(&self) -> Result<MutexGuard<'_, MemoryBuffer>, Error> {
+        self.buffer.lock().map_err(|_| Error::Lock)
+    }
+}
+
+impl VfsFile for MemoryFile {
+    fn read_at(&mut self, buf: &mut [u8], offset: u64) -> Result<(), Error> {
+        let buffer = self.buffer()?;
+        let start = offset as usize;
+
+        let data = buffer
+            .data
+            .get(start..start + buf.len())
+            .ok_or_else(|| std::io::Error::from(std::io::ErrorKind::UnexpectedEof))?;
+
+        buf.copy_from_slice(data);
+        Ok(())
+    }
+
+    fn write_at(&mut self, buf: &[u8], offset: u64) -> Result<(), Error> {
+        let mut buffer = self.buffer()?;
+        let start = offset as usize;
+
+        if buffer.data.len() < start + buf.len() {
+            buffer.data.resize(start + buf.len(), 0x00);
+        }
+
+        buffer.data[start..start + buf.len()].copy_from_slice(buf);
+        Ok(())
+    }
+
+    fn len(&self) -> Result<u64, Error> {
+        Ok(self.buffer()?.data.len() as u64)
+    }
+
+    fn modified(&self) -> Option<SystemTime> {
+        None
+    }
+
+    fn sync(&self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn truncate(&mut self, len: u64) -> Result<(), Error> {
+        self.buffer()?.data.resize(len as usize, 0x00);
+        Ok(())
+    }
+
+    fn lock(&self, mode: LockMode, timeout: Duration) -> Result<(), Error> {
+        retry_lock(timeout, || {
+            let mut buffer = self.buffer()?;
+
+            match mode {
+                LockMode::Shared if !buffer.writer => buffer.readers += 1,
+                LockMode::Exclusive if !buffer.writer && buffer.readers == 0 => {
+                    buffer.writer = true
+                }
+                _ => return Ok(false),
+            }
+
+            Ok(true)
+        })?;
+
+        self.held.set(Some(mode));
+        Ok(())
+    }
+
+    fn unlock(&self) -> Result<(), Error> {
+        let mut buffer = self.buffer()?;
+
+        match self.held.take() {
+            Some(LockMode::Shared) => buffer.readers = buffer.readers.saturating_sub(1),
+            Some(LockMode::Exclusive) => buffer.writer = false,
+            None => {}
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for MemoryFile {
+    // a closed file releases its lock, like it does on disk.
+    fn drop(&mut self) {
+        let _ = self.unlock();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MemoryVfs, Vfs};
+    use crate::engine::{error::Error, file_lock::LockMode};
+    use std::{path::Path, time::Duration};
+
+    #[test]
+    fn test_memory_vfs() {
+        let vfs = MemoryVfs::new();
+        let path = Path::new("db/users/table");
+
+        let mut first = vfs.open(path).expect("Failed to open");
+        first.write_at(&[1, 2, 3], 4).expect("Failed to write");
+
+        let mut second = vfs.open(path).expect("Failed to open");
+        let mut buf = [0x00; 3];
+        second.read_at(&mut buf, 4).expect("Failed to read");
+        assert_eq!(buf, [1, 2, 3]);
+
+        second.truncate(5).expect("Failed to truncate");
+        assert_eq!(first.len().expect("Failed to get len"), 5);
+
+        assert_eq!(
+            vfs.list_dir(Path::new("db")).expect("Failed to list"),
+            vec![Path::new("db/users").to_path_buf()]
+        );
+
+        first
+            .lock(LockMode::Shared, Duration::ZERO)
+            .expect("Failed to lock");
+        match second.lock(LockMode::Exclusive, Duration::from_millis(20)) {
+            Err(Error::Busy) => {}
+            other => panic!("Expected busy error, got {:?}", other.map(|_| ())),
+        }
+        drop(first);
+        second
+            .lock(LockMode::Exclusive, Duration::ZERO)
+            .expect("Failed to lock");
+
+        vfs.remove_dir(Path::new("db/users"))
+            .expect("Failed to remove");
+        assert!(!vfs.exists(path));
+    }
+}
@@ -1,29 +1,103 @@
 use std::{
-    fs::{File, OpenOptions},
-    io::{Read, Seek, SeekFrom, Write},
     path::PathBuf,
+    time::{Duration, SystemTime},
 };
 
-use super::{error::Error, page_layout::PTR_SIZE, structure::Offset};
+use super::{
+    catalog::{Catalog, CatalogEntry},
+    error::Error,
+    file_lock::LockMode,
+    page_layout::{PAGE_SIZE, PTR_SIZE},
+    structure::Offset,
+    vfs::{Vfs, VfsFile},
+};
 
+/// Keeps track of the root of a table.
+///
+/// In the directory layout this is an append only list of roots next to the
+/// table file. In a single file database the root lives in the catalog at the
+/// start of the database file, and `table` is the name of the entry.
+///
+/// The statistics of the table are kept here as well, in a stats file next to the
+/// wal or in pages of the database file the catalog entry points at.
 pub struct Wal {
-    file: File,
+    file: Box<dyn VfsFile>,
+    table: Option<String>,
+    stats: Option<Box<dyn VfsFile>>,
 }
 
 impl Wal {
-    pub fn new(parent_directory: PathBuf) -> Result<Self, Error> {
-        let fd = OpenOptions::new()
-            .create(true)
-            .read(true)
-            .write(true)
-            .truncate(false)
-            .open(parent_directory.join("wal"))?;
-        Ok(Self { file: fd })
+    pub fn new(vfs: &dyn Vfs, parent_directory: PathBuf) -> Result<Self, Error> {
+        Ok(Self {
+            file: vfs.open(&parent_directory.join("wal"))?,
+            table: None,
+            stats: Some(vfs.open(&parent_directory.join("stats"))?),
+        })
+    }
+
+    /// Use the catalog of the single file database at `path` for the roots of `table`.
+    pub fn catalog(vfs: &dyn Vfs, path: PathBuf, table: String) -> Result<Self, Error> {
+        Ok(Self {
+            file: vfs.open(&path)?,
+            table: Some(table),
+            stats: None,
+        })
+    }
+
+    pub fn is_catalog(&self) -> bool {
+        self.table.is_some()
+    }
+
+    /// In a single file database the pager already holds the lock on the file.
+    pub fn lock(&self, mode: LockMode, timeout: Duration) -> Result<(), Error> {
+        if self.is_catalog() {
+            return Ok(());
+        }
+        self.file.lock(mode, timeout)
+    }
+
+    pub fn unlock(&self) -> Result<(), Error> {
+        if self.is_catalog() {
+            return Ok(());
+        }
+        self.file.unlock()
+    }
+
+    pub fn sync(&self) -> Result<(), Error> {
+        self.file.sync()
+    }
+
+    /// Length and modification time of the wal. Every write statement appends
+    /// a root to the wal, so a change means the table was written to.
+    /// For a single file database the change counter of the catalog is used instead.
+    pub fn version(&mut self) -> Result<(u64, Option<SystemTime>), Error> {
+        if self.is_catalog() {
+            let catalog = Catalog::read(self.file.as_mut())?;
+            return Ok((catalog.change_counter, None));
+        }
+
+        Ok((self.file.len()?, self.file.modified()))
+    }
+
+    /// Offset of the schema page of the table.
+    pub fn get_schema(&mut self) -> Result<Offset, Error> {
+        match &self.table {
+            Some(table) => Ok(Offset(
+                Self::get_entry(self.file.as_mut(), table)?.schema_page,
+            )),
+            None => Ok(Offset(0)),
+        }
     }
 
     pub fn get_root(&mut self) -> Result<Offset, Error> {
+        if let Some(table) = &self.table {
+            return Ok(Offset(
+                Self::get_entry(self.file.as_mut(), table)?.root_page,
+            ));
+        }
+
         let mut buff: [u8; PTR_SIZE] = [0x00; PTR_SIZE];
-        let file_len = self.file.seek(SeekFrom::End(0))? as usize;
+        let file_len = self.file.len()? as usize;
 
         let root_offset: usize = if file_len > 0 {
             (file_len / PTR_SIZE - 1) * PTR_SIZE
@@ -31,14 +105,162 @@ impl Wal {
             0
         };
 
-        self.file.seek(SeekFrom::Start(root_offset as u64))?;
-        self.file.read_exact(&mut buff)?;
+        self.file.read_at(&mut buff, root_offset as u64)?;
         Offset::try_from(buff)
     }
 
     pub fn set_root(&mut self, offset: &Offset) -> Result<(), Error> {
-        self.file.seek(SeekFrom::End(0))?;
-        self.file.write_all(&offset.0.to_be_bytes())?;
+        if let Some(table) = &self.table {
+            let mut catalog = Catalog::read(self.file.as_mut())?;
+            let entry = catalog.get_mut(table).ok_or(Error::NotFound)?;
+            entry.root_page = offset.0;
+            catalog.change_counter += 1;
+            return catalog.write(self.file.as_mut());
+        }
+
+        // drop a root that was only partly written before a crash,
+        // so the new root is not appended at a misaligned offset.
+        let len = self.file.len()?;
+        let end = len - len % PTR_SIZE as u64;
+        if end != len {
+            self.file.truncate(end)?;
+        }
+
+        self.file.write_at(&offset.0.to_be_bytes(), end)
+    }
+
+    /// Write an empty catalog if the database file is new.
+    pub fn init_catalog(&mut self) -> Result<(), Error> {
+        if self.file.len()? == 0 {
+            Catalog::default().write(self.file.as_mut())?;
+        }
         Ok(())
     }
+
+    /// Add the table to the catalog of a single file database,
+    /// as an index when it is `index_of` another table.
+    pub fn register(
+        &mut self,
+        schema: &Offset,
+        root: &Offset,
+        index_of: Option<&str>,
+    ) -> Result<(), Error> {
+        let table = self
+            .table
+            .as_ref()
+            .ok_or(Error::UnexpectedWithReason("Table is not in a catalog."))?;
+
+        let mut catalog = Catalog::read(self.file.as_mut())?;
+
+        if let Some(entry) = catalog.get(table) {
+            return Err(Error::Validate(format!(
+                "{} '{}' already exists.",
+                if entry.kind == "index" {
+                    "Index"
+                } else {
+                    "Table"
+                },
+                table
+            )));
+        }
+
+        catalog.entries.push(CatalogEntry {
+            kind: if index_of.is_some() { "index" } else { "table" }.into(),
+            name: table.clone(),
+            table: index_of.unwrap_or(table).to_owned(),
+            schema_page: schema.0,
+            root_page: root.0,
+            stats_page: 0,
+        });
+        catalog.change_counter += 1;
+        catalog.write(self.file.as_mut())
+    }
+
+    /// Remove the table and its indexes from the catalog of a single file database.
+    /// Their pages are left in the file.
+    pub fn unregister(&mut self) -> Result<(), Error> {
+        let table = self
+            .table
+            .as_ref()
+            .ok_or(Error::UnexpectedWithReason("Table is not in a catalog."))?;
+
+        let mut catalog = Catalog::read(self.file.as_mut())?;
+        catalog
+            .entries
+            .retain(|entry| &entry.name != table && &entry.table != table);
+        catalog.change_counter += 1;
+        catalog.write(self.file.as_mut())
+    }
+
+    /// Encoded statistics of the table, none until it is analyzed.
+    /// They are stored with their length in front.
+    pub fn read_stats(&mut self) -> Result<Option<Vec<u8>>, Error> {
+        let (file, offset): (&mut dyn VfsFile, u64) = match (&self.table, &mut self.stats) {
+            (Some(table), _) => {
+                let page = Self::get_entry(self.file.as_mut(), table)?.stats_page;
+                if page == 0 {
+                    return Ok(None);
+                }
+                (self.file.as_mut(), page as u64)
+            }
+            (None, Some(stats)) => {
+                if stats.len()? == 0 {
+                    return Ok(None);
+                }
+                (stats.as_mut(), 0)
+            }
+            (None, None) => return Ok(None),
+        };
+
+        let mut len: [u8; PTR_SIZE] = [0x00; PTR_SIZE];
+        file.read_at(&mut len, offset)?;
+
+        let mut data = vec![0x00; usize::from_be_bytes(len)];
+        file.read_at(&mut data, offset + PTR_SIZE as u64)?;
+
+        Ok(Some(data))
+    }
+
+    /// Replace the statistics of the table. In a single file database they are
+    /// written to new pages at the end of the file, the old pages are not reused.
+    pub fn write_stats(&mut self, data: &[u8], sync: bool) -> Result<(), Error> {
+        let mut buf = data.len().to_be_bytes().to_vec();
+        buf.extend_from_slice(data);
+
+        if let Some(table) = &self.table {
+            let end = self.file.len()?.next_multiple_of(PAGE_SIZE as u64);
+
+            // keep the pages after the stats aligned.
+            buf.resize(buf.len().next_multiple_of(PAGE_SIZE), 0x00);
+            self.file.write_at(&buf, end)?;
+            if sync {
+                self.file.sync()?;
+            }
+
+            let mut catalog = Catalog::read(self.file.as_mut())?;
+            let entry = catalog.get_mut(table).ok_or(Error::NotFound)?;
+            entry.stats_page = end as usize;
+            catalog.change_counter += 1;
+            return catalog.write(self.file.as_mut());
+        }
+
+        let stats = self
+            .stats
+            .as_mut()
+            .ok_or(Error::UnexpectedWithReason("Table has no stats file."))?;
+        stats.write_at(&buf, 0)?;
+        stats.truncate(buf.len() as u64)?;
+        if sync {
+            stats.sync()?;
+        }
+
+        Ok(())
+    }
+
+    fn get_entry(file: &mut dyn VfsFile, table: &str) -> Result<CatalogEntry, Error> {
+        Catalog::read(file)?
+            .get(table)
+            .cloned()
+            .ok_or(Error::NotFound)
+    }
 }
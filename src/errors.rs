@@ -14,13 +14,24 @@ pub enum Error {
     Utf8(#[from] std::str::Utf8Error),
     #[error("Invaild Argument")]
     Argument,
+    #[error("{0} can not be used together with {1}")]
+    ArgumentConflict(&'static str, &'static str),
     #[error("Unexpexted Error: {0}")]
     Unexpexted(&'static str),
     #[error("Serde Error: {0}")]
     Serde(#[from] serde_json::Error),
     #[error("Failed to lock: {0}")]
     Lock(String),
+    #[error("database is locked: {0}")]
+    Busy(String),
 
     #[error("{0}")]
     Logger(&'static str),
 }
+
+impl Error {
+    /// Is the error caused by another connection or process holding a lock for too long.
+    pub fn is_busy(&self) -> bool {
+        matches!(self, Error::Busy(_) | Error::Engine(EngineError::Busy))
+    }
+}
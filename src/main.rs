@@ -5,7 +5,7 @@ mod errors;
 #[macro_use]
 mod sql;
 
-use commands::args;
+use commands::{args, session::Session};
 use controllers::{cli_handler::handle_cli, tcp_handler::hanlde_tcp};
 use std::io;
 
@@ -19,6 +19,9 @@ fn main() -> io::Result<()> {
         return hanlde_tcp(
             config.address.to_str().expect("Failed to convert cow"),
             config.port.to_str().expect("Failed to convert cow"),
+            Session::from(&config),
+            config.storage.clone(),
+            config.cache_size,
         )
         .map_err(|x| {
             eprintln!("{}", x);
@@ -26,7 +29,12 @@ fn main() -> io::Result<()> {
         });
     }
 
-    handle_cli().map_err(|x| {
+    handle_cli(
+        Session::from(&config),
+        config.storage.clone(),
+        config.cache_size,
+    )
+    .map_err(|x| {
         eprintln!("{}", x);
         io::Error::new(io::ErrorKind::Other, x.to_string())
     })
@@ -6,4 +6,8 @@ pub enum Error {
     UnknownChar(String),
     #[error("{0}")]
     Systax(&'static str),
+    #[error("Unknown data type '{0}'")]
+    UnknownType(String),
+    #[error("{0}")]
+    Function(String),
 }
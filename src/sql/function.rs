@@ -0,0 +1,98 @@
+//! Functions are evaluated while the statement is parsed, their result is used like a literal.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::error::Error;
+use crate::engine::{
+    data_type::DataType,
+    datetime::{self, MICROS_PER_DAY, MICROS_PER_SECOND},
+    error::Error as EngineError,
+    json,
+    structure::Value,
+    uuid,
+};
+
+pub fn call(name: &str, args: &[String]) -> Result<String, Error> {
+    let result = match name.to_lowercase().as_str() {
+        "now" if args.is_empty() => datetime::format_timestamp(now()?),
+        "date" => datetime::format_date(time_value(args)?.div_euclid(MICROS_PER_DAY)),
+        "time" => datetime::format_time(time_value(args)?.rem_euclid(MICROS_PER_DAY)),
+        "datetime" => datetime::format_timestamp(time_value(args)?),
+        "unixepoch" => time_value(args)?.div_euclid(MICROS_PER_SECOND).to_string(),
+        "strftime" => {
+            let (format, args) = args
+                .split_first()
+                .ok_or_else(|| Error::Function("strftime expects a format.".into()))?;
+
+            datetime::strftime(format, time_value(args)?)
+                .ok_or_else(|| Error::Function(format!("Invalid strftime format '{}'.", format)))?
+        }
+        // a document written as a literal, `json_extract('{"a": 1}', '$.a')`.
+        name if json::is_function(name) => {
+            let (document, args) = args
+                .split_first()
+                .ok_or_else(|| Error::Function(format!("{} expects a document.", name)))?;
+
+            json::call(name, &Value::String(document.to_owned()), args)
+                .map_err(engine_error)?
+                .to_string()
+        }
+        "uuid_v4" if args.is_empty() => uuid::format(&uuid::v4()),
+        "uuid_v7" if args.is_empty() => uuid::format(&uuid::v7()),
+        "now" | "uuid_v4" | "uuid_v7" => {
+            return Err(Error::Function(format!("{} expects no arguments.", name)))
+        }
+        _ => return Err(Error::Function(format!("Unknown function '{}'.", name))),
+    };
+
+    Ok(result)
+}
+
+/// `CAST(value AS type)` of a literal, given as the literal of the result. A blob is given as
+/// its hex digits, like it is written.
+pub fn cast(value: &Value, data_type: DataType) -> Result<String, Error> {
+    let result = match value.cast(data_type).map_err(engine_error)? {
+        Value::Blob(v) => v.iter().map(|x| format!("{:02X}", x)).collect(),
+        v => v.to_string(),
+    };
+
+    Ok(result)
+}
+
+pub fn engine_error(error: EngineError) -> Error {
+    match error {
+        EngineError::Validate(message) => Error::Function(message),
+        e => Error::Function(e.to_string()),
+    }
+}
+
+/// A time value followed by modifiers, like sqlite. The time value is `now`, a date and time
+/// or seconds since 1970-01-01. Without any arguments it is the current time.
+fn time_value(args: &[String]) -> Result<i64, Error> {
+    let Some((value, modifiers)) = args.split_first() else {
+        return now();
+    };
+
+    let mut micros = if value.eq_ignore_ascii_case("now") {
+        now()?
+    } else {
+        datetime::parse_timestamp(value)
+            .or_else(|| datetime::parse_time(value))
+            .ok_or_else(|| Error::Function(format!("'{}' is not a date or time.", value)))?
+    };
+
+    for modifier in modifiers {
+        micros = datetime::apply_modifier(micros, modifier)
+            .ok_or_else(|| Error::Function(format!("Invalid modifier '{}'.", modifier)))?;
+    }
+
+    Ok(micros)
+}
+
+fn now() -> Result<i64, Error> {
+    let elapsed = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|_| Error::Function("The system clock is before 1970.".into()))?;
+
+    Ok(elapsed.as_micros() as i64)
+}
@@ -1,7 +1,8 @@
 use super::error::Error;
+use super::function;
 use super::tokenizer::Token;
-use super::{ColumnDef, Condition, Ordering};
-use crate::engine::structure::Value;
+use super::{Arithmetic, ColumnDef, ColumnExpr, Condition, DefaultValue, Ordering};
+use crate::engine::{data_type::DataType, decimal, structure::Value};
 use crate::sql::Statement;
 
 type TokenIter<'a> = std::iter::Peekable<std::slice::Iter<'a, Token>>;
@@ -25,6 +26,18 @@ macro_rules! peek_next {
 pub fn interpect(buffer: Vec<Token>) -> Result<Statement, Error> {
     let mut list = buffer.iter().peekable();
 
+    if list.next_if(|x| x.is_keyword("explain")).is_some() {
+        if !next_token!(list).is_keyword("query") || !next_token!(list).is_keyword("plan") {
+            return Err(Error::Systax("Expected 'QUERY PLAN' after 'EXPLAIN'."));
+        }
+
+        return Ok(Statement::Explain(Box::new(parse_statement(&mut list)?)));
+    }
+
+    parse_statement(&mut list)
+}
+
+fn parse_statement(list: &mut TokenIter<'_>) -> Result<Statement, Error> {
     let index = match next_token!(list).get_identifer() {
         Some(value) => value,
         None => return Err(Error::Systax("Invaild systax.")),
@@ -33,7 +46,14 @@ pub fn interpect(buffer: Vec<Token>) -> Result<Statement, Error> {
     match index.as_str() {
         "create" => match next_token!(list).get_identifer() {
             Some(value) => match value.as_str() {
-                "table" => parse_create_table(&mut list),
+                "table" => parse_create_table(list),
+                "index" => parse_create_index(list, false),
+                "unique" => {
+                    if !next_token!(list).is_keyword("index") {
+                        return Err(Error::Systax("Expected keyword 'index' after 'unique'."));
+                    }
+                    parse_create_index(list, true)
+                }
                 _ => Err(Error::Systax(
                     "Expected 'INDEX|TABLE|TRIGGER|VIEW|VIRTUAL' after 'CREATE'.",
                 )),
@@ -44,7 +64,8 @@ pub fn interpect(buffer: Vec<Token>) -> Result<Statement, Error> {
         },
         "drop" => match next_token!(list).get_identifer() {
             Some(value) => match value.as_str() {
-                "table" => parse_drop_table(&mut list),
+                "table" => parse_drop_table(list),
+                "index" => parse_drop_index(list),
                 _ => Err(Error::Systax(
                     "Expected 'INDEX|TABLE|TRIGGER|VIEW' after 'DROP'.",
                 )),
@@ -53,12 +74,14 @@ pub fn interpect(buffer: Vec<Token>) -> Result<Statement, Error> {
                 "Expected 'INDEX|TABLE|TRIGGER|VIEW' after 'DROP'.",
             )),
         },
-        "insert" => parse_insert(&mut list),
-        "select" => parse_select(&mut list),
-        "delete" => parse_delete(&mut list),
-        "update" => parse_update(&mut list),
+        "insert" => parse_insert(list),
+        "select" => parse_select(list),
+        "delete" => parse_delete(list),
+        "update" => parse_update(list),
+        "pragma" => parse_pragma(list),
+        "analyze" => parse_analyze(list),
         _ => Err(Error::Systax(
-            "Expected 'CREATE|SELECT|DELETE|DROP|UPDATE|INSERT'.",
+            "Expected 'CREATE|SELECT|DELETE|DROP|UPDATE|INSERT|PRAGMA|ANALYZE|EXPLAIN'.",
         )),
     }
 }
@@ -66,7 +89,7 @@ pub fn interpect(buffer: Vec<Token>) -> Result<Statement, Error> {
 fn parse_create_table(tokens: &mut TokenIter<'_>) -> Result<Statement, Error> {
     let mut table_cols = vec![];
 
-    let table_name = match next_token!(tokens).get_identifer() {
+    let table_name = match next_token!(tokens).get_name() {
         Some(ident) => ident,
         None => return Err(Error::Systax("Expected a table name")),
     };
@@ -75,11 +98,31 @@ fn parse_create_table(tokens: &mut TokenIter<'_>) -> Result<Statement, Error> {
         return Err(Error::Systax("Invaild token"));
     }
 
-    let mut primary_key_idx: usize = 0;
+    let mut primary_key: Vec<usize> = vec![0];
     let mut has_primary_key = false;
     let mut commas = 0;
     while let Some(value) = tokens.next() {
         match value {
+            // table constraint, PRIMARY KEY (column, ...)
+            Token::Ident(_) if value.is_keyword("primary") => {
+                if !next_token!(tokens).is_keyword("key") {
+                    return Err(Error::Systax("Expected keyword 'key' after 'primary'."));
+                }
+                if has_primary_key {
+                    return Err(Error::Systax("Can not have more then one primary key."));
+                }
+
+                primary_key = parse_key_columns(tokens, &mut table_cols)?;
+                has_primary_key = true;
+
+                for idx in &primary_key {
+                    table_cols[*idx].nullable = false;
+                }
+                // a single column key is the same as a column constraint.
+                if let [idx] = primary_key[..] {
+                    table_cols[idx].unique = true;
+                }
+            }
             Token::Ident(ident) => {
                 if let Some(data_type) = tokens.next() {
                     let token = match data_type.get_identifer() {
@@ -95,12 +138,13 @@ fn parse_create_table(tokens: &mut TokenIter<'_>) -> Result<Statement, Error> {
                         return Err(Error::Systax("Expected a ','"));
                     }
 
-                    let c = Value::from_string(&token.to_lowercase());
+                    let c = parse_data_type(tokens, &token)?;
 
                     let mut autoincrement = false;
                     let mut ordering = Ordering::default();
                     let mut unique = false;
                     let mut nullable = true;
+                    let mut default_value = None;
                     while let Some(v) = tokens.peek() {
                         if v == &&Token::Comma || v == &&Token::RightPren || v == &&Token::EOL {
                             break;
@@ -116,7 +160,7 @@ fn parse_create_table(tokens: &mut TokenIter<'_>) -> Result<Statement, Error> {
                                 nullable = false;
                                 unique = true;
                                 has_primary_key = true;
-                                primary_key_idx = commas;
+                                primary_key = vec![commas];
                                 ordering = row_ordering;
                                 autoincrement = autointer;
                             }
@@ -126,7 +170,9 @@ fn parse_create_table(tokens: &mut TokenIter<'_>) -> Result<Statement, Error> {
                             ColumnConstraint::Unique => {
                                 unique = true;
                             }
-                            ColumnConstraint::Default(_) => todo!(),
+                            ColumnConstraint::Default(value) => {
+                                default_value = Some(value);
+                            }
                             ColumnConstraint::None => {}
                         };
                     }
@@ -138,7 +184,7 @@ fn parse_create_table(tokens: &mut TokenIter<'_>) -> Result<Statement, Error> {
                         c,
                         autoincrement,
                         ordering,
-                        None,
+                        default_value,
                     ));
                 }
             }
@@ -161,16 +207,154 @@ fn parse_create_table(tokens: &mut TokenIter<'_>) -> Result<Statement, Error> {
     }
 
     Ok(Statement::Create {
-        primary_key: primary_key_idx,
+        primary_key,
         table: table_name,
         cols: table_cols,
     })
 }
 
+/// Type of a column declared as `name`, with its arguments if it has any, `VARCHAR(255)`.
+fn parse_data_type(tokens: &mut TokenIter<'_>, name: &str) -> Result<DataType, Error> {
+    let mut args = vec![];
+    if peek_next!(tokens).is_token(&Token::LeftPren) {
+        tokens.next();
+        loop {
+            match next_token!(tokens) {
+                Token::Number(value) => args.push(
+                    value
+                        .parse::<usize>()
+                        .map_err(|_| Error::Systax("Invaild data type argument."))?,
+                ),
+                _ => return Err(Error::Systax("Expected a number as data type argument.")),
+            }
+
+            match next_token!(tokens) {
+                Token::Comma => {}
+                Token::RightPren => break,
+                _ => {
+                    return Err(Error::Systax(
+                        "Expected ',' or ')' after data type argument.",
+                    ))
+                }
+            }
+        }
+    }
+
+    DataType::from_declaration(name, &args).ok_or_else(|| {
+        if args.is_empty() {
+            return Error::UnknownType(name.to_owned());
+        }
+        let args: Vec<String> = args.iter().map(|x| x.to_string()).collect();
+        Error::UnknownType(format!("{}({})", name, args.join(", ")))
+    })
+}
+
+/// Columns of a `PRIMARY KEY (column [ASC|DESC], ...)` table constraint.
+fn parse_key_columns(
+    tokens: &mut TokenIter<'_>,
+    table_cols: &mut [ColumnDef],
+) -> Result<Vec<usize>, Error> {
+    if !next_token!(tokens).is_token(&Token::LeftPren) {
+        return Err(Error::Systax("Expected '(' after 'primary key'."));
+    }
+
+    let mut columns = vec![];
+    loop {
+        let name = next_token!(tokens)
+            .get_identifer()
+            .ok_or(Error::Systax("Expected a primary key column name."))?;
+
+        let idx = table_cols
+            .iter()
+            .position(|x| x.name.to_lowercase() == name)
+            .ok_or(Error::Systax(
+                "Primary key column is not a column of the table.",
+            ))?;
+
+        if columns.contains(&idx) {
+            return Err(Error::Systax("Primary key column is used more then once."));
+        }
+        columns.push(idx);
+
+        let mut token = next_token!(tokens);
+        if token.is_keyword("asc") || token.is_keyword("desc") {
+            table_cols[idx].ordering = match token.is_keyword("desc") {
+                true => Ordering::Desc,
+                false => Ordering::Asc,
+            };
+            token = next_token!(tokens);
+        }
+
+        match token {
+            Token::Comma => {}
+            Token::RightPren => return Ok(columns),
+            _ => return Err(Error::Systax("Expected ',' or ')' in primary key.")),
+        }
+    }
+}
+
+fn parse_create_index(tokens: &mut TokenIter<'_>, unique: bool) -> Result<Statement, Error> {
+    let name = match next_token!(tokens).get_name() {
+        Some(ident) => ident,
+        None => return Err(Error::Systax("Expected an index name")),
+    };
+
+    if !next_token!(tokens).is_keyword("on") {
+        return Err(Error::Systax("Expected keyword 'on' after index name."));
+    }
+
+    let table = match next_token!(tokens).get_name() {
+        Some(ident) => ident,
+        None => return Err(Error::Systax("Expected a table name")),
+    };
+
+    if !next_token!(tokens).is_token(&Token::LeftPren) {
+        return Err(Error::Systax("Expected '(' after table name."));
+    }
+
+    let mut columns = vec![];
+    let mut commas = 0;
+    for value in tokens.by_ref() {
+        match value {
+            Token::Ident(ident) => {
+                if columns.len() != commas {
+                    return Err(Error::Systax("Expected an comma"));
+                }
+
+                columns.push(ident.to_owned());
+            }
+            Token::Comma => commas += 1,
+            Token::RightPren => break,
+            _ => return Err(Error::Systax("Expected an column name, ')' or ','.")),
+        }
+    }
+
+    if columns.is_empty() {
+        return Err(Error::Systax("Expected at least one column to index."));
+    }
+
+    if !next_token!(tokens).is_token(&Token::SemiComma) {
+        return Err(Error::Systax("Expected ';'"));
+    }
+
+    Ok(Statement::CreateIndex {
+        name,
+        table,
+        columns,
+        unique,
+    })
+}
+
 #[derive(Debug, PartialEq)]
 pub enum ColumnData {
     Null,
     Value(String),
+    /// A column of the row with a value applied to it, `balance - 10.25`.
+    /// Only the `SET` of an update can have one.
+    Column(String, Arithmetic, String),
+    /// A function called on a column of the row, `json_set(attrs, "$.a", 1)`.
+    /// Only the `SET` of an update can have one.
+    Call(ColumnExpr),
 }
 
 fn parse_insert(tokens: &mut TokenIter<'_>) -> Result<Statement, Error> {
@@ -181,7 +365,7 @@ fn parse_insert(tokens: &mut TokenIter<'_>) -> Result<Statement, Error> {
         return Err(Error::Systax("Expected keyword 'into'."));
     }
 
-    let table_name = match next_token!(tokens).get_identifer() {
+    let table_name = match next_token!(tokens).get_name() {
         Some(i) => i,
         _ => return Err(Error::Systax("Invaild token")),
     };
@@ -226,32 +410,26 @@ fn parse_insert(tokens: &mut TokenIter<'_>) -> Result<Statement, Error> {
             let mut commas: usize = 0;
             while let Some(value) = tokens.next() {
                 match value {
-                    Token::Ident(value) => {
+                    Token::Comma => commas += 1,
+                    Token::RightPren => break,
+                    value => {
                         if data.len() != commas {
                             return Err(Error::Systax("Expected an comma"));
                         }
 
-                        if value.to_lowercase().as_str() != "null" {
-                            return Err(Error::Systax("did not expected identifier"));
-                        }
-
-                        data.push(ColumnData::Null);
-                    }
-                    Token::Number(value) => {
-                        if data.len() != commas {
-                            return Err(Error::Systax("Expected an comma"));
-                        }
-                        data.push(ColumnData::Value(value.to_owned()));
-                    }
-                    Token::String(value) => {
-                        if data.len() != commas {
-                            return Err(Error::Systax("Expected an comma"));
+                        match value {
+                            Token::Ident(ident) if ident.eq_ignore_ascii_case("null") => {
+                                data.push(ColumnData::Null)
+                            }
+                            value => match parse_literal(value, tokens)? {
+                                Some(literal) => data.push(ColumnData::Value(literal)),
+                                None if matches!(value, Token::Ident(_)) => {
+                                    return Err(Error::Systax("did not expected identifier"))
+                                }
+                                None => return Err(Error::Systax("Invaild token")),
+                            },
                         }
-                        data.push(ColumnData::Value(value.to_owned()));
                     }
-                    Token::Comma => commas += 1,
-                    Token::RightPren => break,
-                    _ => return Err(Error::Systax("Invaild token")),
                 }
             }
         }
@@ -282,7 +460,11 @@ fn parse_select(tokens: &mut TokenIter<'_>) -> Result<Statement, Error> {
                             return Err(Error::Systax("Expected an comma"));
                         }
 
-                        cols.push(ident.to_owned());
+                        if is_column_call(tokens) {
+                            cols.push(parse_column_call(ident, tokens)?);
+                        } else {
+                            cols.push(ident.to_owned().into());
+                        }
                     }
                     Token::Comma => {
                         commas += 1;
@@ -300,7 +482,7 @@ fn parse_select(tokens: &mut TokenIter<'_>) -> Result<Statement, Error> {
         return Err(Error::Systax("Expected keyword 'from'"));
     }
 
-    let table_name = match next_token!(tokens).get_identifer() {
+    let table_name = match next_token!(tokens).get_name() {
         Some(i) => i,
         None => return Err(Error::Systax("Invaild token")),
     };
@@ -328,7 +510,7 @@ pub enum ColumnConstraint {
     PrimaryKey(Ordering, bool),
     NotNull,
     Unique,
-    Default(String),
+    Default(DefaultValue),
     None,
 }
 /// Handle parseing column constraints
@@ -338,7 +520,7 @@ pub enum ColumnConstraint {
 ///
 /// PRIMARY KEY  (ACS|DESC)? conflict-clause (AUTOINCREMENT)?
 /// NOT NULL conflict-clause
-/// DEFAULT literal-value|signed-number
+/// DEFAULT literal-value|signed-number|function-call
 fn parse_column_constraint(tokens: &mut TokenIter<'_>) -> Result<ColumnConstraint, Error> {
     match peek_next!(tokens) {
         Token::Ident(item) => match item.to_lowercase().as_str() {
@@ -391,11 +573,24 @@ fn parse_column_constraint(tokens: &mut TokenIter<'_>) -> Result<ColumnConstrain
             }
             "default" => {
                 tokens.next();
-                match next_token!(tokens) {
-                    Token::Number(num) => Ok(ColumnConstraint::Default(num.to_owned())),
-                    Token::String(string) => Ok(ColumnConstraint::Default(string.to_owned())),
-                    _ => Err(Error::Systax("Invaild data")),
-                }
+                let value = match next_token!(tokens) {
+                    Token::Number(value) | Token::String(value) | Token::Blob(value) => {
+                        DefaultValue::Value(value.to_owned())
+                    }
+                    Token::Boolean(value) => DefaultValue::Value(value.to_string()),
+                    // the function is called for every row, not once when the table is created.
+                    Token::Ident(name)
+                        if tokens.next_if(|x| x.is_token(&Token::LeftPren)).is_some() =>
+                    {
+                        let value = DefaultValue::Call(name.to_lowercase(), parse_args(tokens)?);
+                        // called once here, so a typo fails when the table is created.
+                        value.evaluate()?;
+                        value
+                    }
+                    _ => return Err(Error::Systax("Invaild data")),
+                };
+
+                Ok(ColumnConstraint::Default(value))
             }
             _ => Ok(ColumnConstraint::None),
         },
@@ -403,6 +598,132 @@ fn parse_column_constraint(tokens: &mut TokenIter<'_>) -> Result<ColumnConstrain
     }
 }
 
+/// A literal value, or a call of a function that is evaluated to one, `date("now", "-7 days")`.
+/// `None` when the token does not start a value.
+fn parse_literal(token: &Token, tokens: &mut TokenIter<'_>) -> Result<Option<String>, Error> {
+    let value = match token {
+        Token::String(a) | Token::Number(a) | Token::Blob(a) => a.to_owned(),
+        Token::Boolean(a) => a.to_string(),
+        Token::Ident(name)
+            if name.eq_ignore_ascii_case("cast")
+                && tokens.next_if(|x| x.is_token(&Token::LeftPren)).is_some() =>
+        {
+            parse_cast(tokens)?
+        }
+        Token::Ident(name) if tokens.next_if(|x| x.is_token(&Token::LeftPren)).is_some() => {
+            function::call(name, &parse_args(tokens)?)?
+        }
+        _ => return Ok(None),
+    };
+
+    Ok(Some(value))
+}
+
+/// `CAST(literal AS type)` evaluated to the literal of the result, the '(' was just read.
+/// A number is cast as the number it is, anything else as text.
+fn parse_cast(tokens: &mut TokenIter<'_>) -> Result<String, Error> {
+    let value = match next_token!(tokens) {
+        Token::Number(a) => match decimal::parse(a) {
+            Some((value, scale)) => Value::Decimal(value, scale),
+            None => Value::Real(
+                a.parse()
+                    .map_err(|_| Error::Systax("Invaild number in CAST."))?,
+            ),
+        },
+        Token::Boolean(a) => Value::Bool(*a),
+        Token::Blob(a) => DataType::Blob.parse(a).map_err(function::engine_error)?,
+        token => Value::String(
+            parse_literal(token, tokens)?.ok_or(Error::Systax("Invalid CAST value."))?,
+        ),
+    };
+
+    function::cast(&value, parse_cast_type(tokens)?)
+}
+
+/// The `AS type)` ending a cast.
+fn parse_cast_type(tokens: &mut TokenIter<'_>) -> Result<DataType, Error> {
+    if !next_token!(tokens).is_keyword("as") {
+        return Err(Error::Systax("Expected AS in CAST."));
+    }
+
+    let name = next_token!(tokens)
+        .get_identifer()
+        .ok_or(Error::Systax("Expected a type after AS."))?;
+    let data_type = parse_data_type(tokens, &name)?;
+
+    if !next_token!(tokens).is_token(&Token::RightPren) {
+        return Err(Error::Systax("Expected ')' after the type of CAST."));
+    }
+
+    Ok(data_type)
+}
+
+/// Literal arguments of a function up to its ')', the '(' was just read.
+fn parse_args(tokens: &mut TokenIter<'_>) -> Result<Vec<String>, Error> {
+    let mut args = vec![];
+
+    if tokens.next_if(|x| x.is_token(&Token::RightPren)).is_none() {
+        loop {
+            match parse_literal(next_token!(tokens), tokens)? {
+                Some(arg) => args.push(arg),
+                None => return Err(Error::Systax("Invalid function argument.")),
+            }
+
+            match next_token!(tokens) {
+                Token::Comma => continue,
+                Token::RightPren => break,
+                _ => return Err(Error::Systax("Expected ',' or ')' after an argument.")),
+            }
+        }
+    }
+
+    Ok(args)
+}
+
+/// Is the function whose name was just read called on a column, `json_extract(attrs, "$.a")`,
+/// instead of evaluated to a literal.
+fn is_column_call(tokens: &TokenIter<'_>) -> bool {
+    let mut ahead = tokens.clone();
+
+    ahead.next().is_some_and(|x| x.is_token(&Token::LeftPren))
+        && matches!(ahead.next(), Some(Token::Ident(_)))
+        && !ahead.next().is_some_and(|x| x.is_token(&Token::LeftPren))
+}
+
+/// A function called on a column, its name was just read.
+fn parse_column_call(function: &str, tokens: &mut TokenIter<'_>) -> Result<ColumnExpr, Error> {
+    if !next_token!(tokens).is_token(&Token::LeftPren) {
+        return Err(Error::Systax("Expected '(' after function name."));
+    }
+
+    let column = next_token!(tokens)
+        .get_identifer()
+        .ok_or(Error::Systax("Expected a column as the first argument."))?;
+
+    if function.eq_ignore_ascii_case("cast") {
+        let data_type = parse_cast_type(tokens)?;
+        return Ok(ColumnExpr::Cast { column, data_type });
+    }
+
+    let mut args = vec![];
+    loop {
+        match next_token!(tokens) {
+            Token::Comma => match parse_literal(next_token!(tokens), tokens)? {
+                Some(arg) => args.push(arg),
+                None => return Err(Error::Systax("Invalid function argument.")),
+            },
+            Token::RightPren => break,
+            _ => return Err(Error::Systax("Expected ',' or ')' after an argument.")),
+        }
+    }
+
+    Ok(ColumnExpr::Call {
+        function: function.to_lowercase(),
+        column,
+        args,
+    })
+}
+
 fn parse_expr(tokens: &mut TokenIter<'_>) -> Result<Vec<Condition>, Error> {
     let mut out = vec![];
 
@@ -423,49 +744,58 @@ fn parse_expr(tokens: &mut TokenIter<'_>) -> Result<Vec<Condition>, Error> {
             "OR" | "or" => out.push(Condition::OR),
             "NOT" | "not" => out.push(Condition::NOT),
             _ => {
+                let column = if is_column_call(tokens) {
+                    parse_column_call(&ident, tokens)?
+                } else {
+                    ColumnExpr::Column(ident)
+                };
+
+                // a column on its own, `WHERE active`, is true when the column is.
+                if tokens.peek().is_none_or(|x| {
+                    x.is_token(&Token::SemiComma)
+                        || x.is_token(&Token::EOL)
+                        || x.is_keyword("and")
+                        || x.is_keyword("or")
+                }) {
+                    out.push(Condition::E(column, true.to_string()));
+                    continue;
+                }
+
                 let opt = next_token!(tokens);
-                let value = match next_token!(tokens) {
-                    Token::String(a) => a,
-                    Token::Number(a) => a,
-                    _ => return Err(Error::Systax("Invalid value.")),
+                let value = match parse_literal(next_token!(tokens), tokens)? {
+                    Some(value) => value,
+                    None => return Err(Error::Systax("Invalid value.")),
                 };
 
                 match opt {
-                    Token::Ident(ident) => match ident.to_lowercase().as_str() {
+                    Token::Ident(keyword) => match keyword.to_lowercase().as_str() {
                         "between" => {
                             if !next_token!(tokens).is_keyword("and") {
                                 return Err(Error::Systax("Expected keyword 'where'"));
                             }
 
-                            let range_end = match next_token!(tokens) {
-                                Token::String(a) => a,
-                                Token::Number(a) => a,
-                                _ => return Err(Error::Systax("Invalid value.")),
+                            let range_end = match parse_literal(next_token!(tokens), tokens)? {
+                                Some(value) => value,
+                                None => return Err(Error::Systax("Invalid value.")),
                             };
 
                             out.push(Condition::BETWEEN(
-                                ident.to_string(),
+                                column,
                                 value.to_owned(),
                                 range_end.to_owned(),
                             ))
                         }
-                        "like" => out.push(Condition::LIKE(ident.to_string(), value.to_owned())),
+                        "like" => out.push(Condition::LIKE(column, value.to_owned())),
                         _ => return Err(Error::Systax("Invalid value.")),
                     },
-                    Token::Equal => out.push(Condition::E(ident.to_string(), value.to_owned())),
-                    Token::GreaterThan => {
-                        out.push(Condition::GT(ident.to_string(), value.to_owned()))
-                    }
-                    Token::GreaterThanOrEqual => {
-                        out.push(Condition::GTE(ident.to_string(), value.to_owned()))
-                    }
+                    Token::Equal => out.push(Condition::E(column, value.to_owned())),
+                    Token::GreaterThan => out.push(Condition::GT(column, value.to_owned())),
+                    Token::GreaterThanOrEqual => out.push(Condition::GTE(column, value.to_owned())),
 
-                    Token::LessThan => out.push(Condition::LT(ident.to_string(), value.to_owned())),
-                    Token::LessThanOrEqual => {
-                        out.push(Condition::LTE(ident.to_string(), value.to_owned()))
-                    }
+                    Token::LessThan => out.push(Condition::LT(column, value.to_owned())),
+                    Token::LessThanOrEqual => out.push(Condition::LTE(column, value.to_owned())),
 
-                    Token::NotEqual => out.push(Condition::NE(ident.to_string(), value.to_owned())),
+                    Token::NotEqual => out.push(Condition::NE(column, value.to_owned())),
                     _ => return Err(Error::Systax("Expexted token '='|'<'|'>'|'<='|'>='|'!='")),
                 }
             }
@@ -489,7 +819,7 @@ pub fn parse_delete(tokens: &mut TokenIter<'_>) -> Result<Statement, Error> {
         return Err(Error::Systax("Expected keyword 'from' after 'delete'."));
     }
 
-    let table_name = match next_token!(tokens).get_identifer() {
+    let table_name = match next_token!(tokens).get_name() {
         Some(i) => i,
         None => return Err(Error::Systax("Invaild token")),
     };
@@ -511,7 +841,7 @@ pub fn parse_delete(tokens: &mut TokenIter<'_>) -> Result<Statement, Error> {
 }
 
 pub fn parse_update(tokens: &mut TokenIter<'_>) -> Result<Statement, Error> {
-    let table_name = match next_token!(tokens).get_identifer() {
+    let table_name = match next_token!(tokens).get_name() {
         Some(i) => i,
         None => return Err(Error::Systax("Invaild token")),
     };
@@ -535,15 +865,37 @@ pub fn parse_update(tokens: &mut TokenIter<'_>) -> Result<Statement, Error> {
         }
 
         let value = match next_token!(tokens) {
-            Token::Number(v) => ColumnData::Value(v.to_owned()),
-            Token::String(v) => ColumnData::Value(v.to_owned()),
-            Token::Ident(v) => {
-                if v != "null" {
-                    return Err(Error::Systax("Invaild data"));
-                }
-                ColumnData::Null
+            Token::Ident(v) if v == "null" => ColumnData::Null,
+            Token::Ident(function) if is_column_call(tokens) => {
+                ColumnData::Call(parse_column_call(function, tokens)?)
             }
-            _ => return Err(Error::Systax("Invaild data")),
+            Token::Ident(column) if !peek_next!(tokens).is_token(&Token::LeftPren) => {
+                let (operator, value) = match next_token!(tokens) {
+                    Token::Plus => (Arithmetic::Add, None),
+                    Token::Minus => (Arithmetic::Subtract, None),
+                    Token::Star => (Arithmetic::Multiply, None),
+                    Token::Slash => (Arithmetic::Divide, None),
+                    // `balance-10` is read as the column and a negative number.
+                    Token::Number(value) if value.starts_with('-') => {
+                        (Arithmetic::Add, Some(value.to_owned()))
+                    }
+                    _ => return Err(Error::Systax("Expected '+', '-', '*' or '/' after column.")),
+                };
+
+                let value = match value {
+                    Some(value) => value,
+                    None => match parse_literal(next_token!(tokens), tokens)? {
+                        Some(value) => value,
+                        None => return Err(Error::Systax("Invaild data")),
+                    },
+                };
+
+                ColumnData::Column(column.to_lowercase(), operator, value)
+            }
+            token => match parse_literal(token, tokens)? {
+                Some(v) => ColumnData::Value(v),
+                None => return Err(Error::Systax("Invaild data")),
+            },
         };
 
         tokens.next_if(|x| x.is_token(&Token::Comma));
@@ -570,7 +922,7 @@ pub fn parse_update(tokens: &mut TokenIter<'_>) -> Result<Statement, Error> {
 }
 
 pub fn parse_drop_table(tokens: &mut TokenIter<'_>) -> Result<Statement, Error> {
-    match next_token!(tokens).get_identifer() {
+    match next_token!(tokens).get_name() {
         Some(ident) => {
             if !next_token!(tokens).is_token(&Token::SemiComma) {
                 return Err(Error::Systax("Expexted ';' after table name."));
@@ -582,6 +934,58 @@ pub fn parse_drop_table(tokens: &mut TokenIter<'_>) -> Result<Statement, Error>
     }
 }
 
+pub fn parse_drop_index(tokens: &mut TokenIter<'_>) -> Result<Statement, Error> {
+    match next_token!(tokens).get_name() {
+        Some(name) => {
+            if !next_token!(tokens).is_token(&Token::SemiComma) {
+                return Err(Error::Systax("Expexted ';' after index name."));
+            }
+
+            Ok(Statement::DropIndex { name })
+        }
+        None => Err(Error::Systax("Expected index identifer.")),
+    }
+}
+
+pub fn parse_analyze(tokens: &mut TokenIter<'_>) -> Result<Statement, Error> {
+    let table = match next_token!(tokens) {
+        Token::SemiComma => return Ok(Statement::Analyze { table: None }),
+        token => token
+            .get_name()
+            .ok_or(Error::Systax("Expected table identifer."))?,
+    };
+
+    if !next_token!(tokens).is_token(&Token::SemiComma) {
+        return Err(Error::Systax("Expexted ';' after table name."));
+    }
+
+    Ok(Statement::Analyze { table: Some(table) })
+}
+
+pub fn parse_pragma(tokens: &mut TokenIter<'_>) -> Result<Statement, Error> {
+    let name = match next_token!(tokens).get_identifer() {
+        Some(ident) => ident,
+        None => return Err(Error::Systax("Expected pragma name.")),
+    };
+
+    let value = if tokens.next_if(|x| x.is_token(&Token::Equal)).is_some() {
+        match next_token!(tokens) {
+            Token::Ident(v) => Some(v.to_lowercase()),
+            Token::Number(v) => Some(v.to_owned()),
+            Token::String(v) => Some(v.to_owned()),
+            _ => return Err(Error::Systax("Invaild pragma value.")),
+        }
+    } else {
+        None
+    };
+
+    if !next_token!(tokens).is_token(&Token::SemiComma) {
+        return Err(Error::Systax("Expected ';'"));
+    }
+
+    Ok(Statement::Pragma { name, value })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -601,6 +1005,80 @@ mod tests {
         }
     }
 
+    #[test]
+    fn parse_pragma_statement() {
+        let query = crate::sql!("PRAGMA synchronous = NORMAL;");
+        match interpect(query) {
+            Ok(value) => assert_eq!(
+                value,
+                Statement::Pragma {
+                    name: "synchronous".into(),
+                    value: Some("normal".into())
+                }
+            ),
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    #[test]
+    fn parse_analyze_statement() {
+        let query = crate::sql!("ANALYZE;");
+        assert_eq!(
+            interpect(query).expect("Failed to parse"),
+            Statement::Analyze { table: None }
+        );
+
+        let query = crate::sql!("ANALYZE users;");
+        assert_eq!(
+            interpect(query).expect("Failed to parse"),
+            Statement::Analyze {
+                table: Some("users".into())
+            }
+        );
+    }
+
+    #[test]
+    fn parse_explain_statement() {
+        let query = crate::sql!("EXPLAIN QUERY PLAN DROP TABLE users;");
+        assert_eq!(
+            interpect(query).expect("Failed to parse"),
+            Statement::Explain(Box::new(Statement::DropTable {
+                table: "users".into()
+            }))
+        );
+
+        let query = crate::sql!("EXPLAIN DROP TABLE users;");
+        assert!(interpect(query).is_err());
+    }
+
+    #[test]
+    fn parse_create_index_statement() {
+        let query = crate::sql!("CREATE UNIQUE INDEX users_name ON users (name, age);");
+        match interpect(query) {
+            Ok(value) => assert_eq!(
+                value,
+                Statement::CreateIndex {
+                    name: "users_name".into(),
+                    table: "users".into(),
+                    columns: vec!["name".into(), "age".into()],
+                    unique: true,
+                }
+            ),
+            Err(e) => panic!("{}", e),
+        }
+
+        let query = crate::sql!("DROP INDEX users_name;");
+        match interpect(query) {
+            Ok(value) => assert_eq!(
+                value,
+                Statement::DropIndex {
+                    name: "users_name".into()
+                }
+            ),
+            Err(e) => panic!("{}", e),
+        }
+    }
+
     #[test]
     fn parse_expr_value() {
         let query = crate::sql!("NOT column = 1 AND id > 2;");
@@ -613,6 +1091,188 @@ mod tests {
         }
     }
 
+    #[test]
+    fn parse_boolean_predicate() {
+        let query = crate::sql!("NOT active AND admin = TRUE OR flag;");
+        let mut iter = query.iter().peekable();
+
+        assert_eq!(
+            parse_expr(&mut iter).expect("Failed to parse"),
+            vec![
+                Condition::NOT,
+                Condition::E("active".into(), "true".into()),
+                Condition::AND,
+                Condition::E("admin".into(), "true".into()),
+                Condition::OR,
+                Condition::E("flag".into(), "true".into()),
+            ]
+        );
+
+        let query = crate::sql!("INSERT INTO users VALUES (1,false);");
+        match interpect(query).expect("Failed to parse") {
+            Statement::Insert { data, .. } => assert_eq!(
+                data,
+                vec![
+                    ColumnData::Value("1".into()),
+                    ColumnData::Value("false".into())
+                ]
+            ),
+            value => panic!("Expected insert statement, got {:?}", value),
+        }
+    }
+
+    #[test]
+    fn parse_update_arithmetic() {
+        let query =
+            crate::sql!("UPDATE accounts SET balance = balance - 10.25, fee = fee-1, total = 3;");
+        match interpect(query).expect("Failed to parse") {
+            Statement::Update { columns, .. } => assert_eq!(
+                columns,
+                vec![
+                    (
+                        "balance".into(),
+                        ColumnData::Column("balance".into(), Arithmetic::Subtract, "10.25".into())
+                    ),
+                    (
+                        "fee".into(),
+                        ColumnData::Column("fee".into(), Arithmetic::Add, "-1".into())
+                    ),
+                    ("total".into(), ColumnData::Value("3".into())),
+                ]
+            ),
+            value => panic!("Expected update statement, got {:?}", value),
+        }
+
+        assert!(interpect(crate::sql!("UPDATE accounts SET balance = balance 1;")).is_err());
+    }
+
+    #[test]
+    fn parse_cast() {
+        let query = crate::sql!(
+            "SELECT (id, CAST(age AS string)) FROM t WHERE CAST(score AS decimal(5, 1)) > CAST(\"12.75abc\" AS real);"
+        );
+        match interpect(query).expect("Failed to parse") {
+            Statement::Select {
+                columns, target, ..
+            } => {
+                let cast = |column: &str, data_type| ColumnExpr::Cast {
+                    column: column.into(),
+                    data_type,
+                };
+                assert_eq!(columns, vec!["id".into(), cast("age", DataType::String)]);
+                assert_eq!(
+                    target,
+                    Some(vec![Condition::GT(
+                        cast(
+                            "score",
+                            DataType::Decimal {
+                                precision: 5,
+                                scale: 1
+                            }
+                        ),
+                        "12.75".into()
+                    )])
+                );
+            }
+            value => panic!("Expected select statement, got {:?}", value),
+        }
+
+        let query = crate::sql!("INSERT INTO t VALUES (CAST(12.9 AS int), CAST(\"hi\" AS blob));");
+        match interpect(query).expect("Failed to parse") {
+            Statement::Insert { data, .. } => assert_eq!(
+                data,
+                vec![
+                    ColumnData::Value("12".into()),
+                    ColumnData::Value("6869".into())
+                ]
+            ),
+            value => panic!("Expected insert statement, got {:?}", value),
+        }
+
+        assert!(interpect(crate::sql!("SELECT * FROM t WHERE CAST(age int) = 1;")).is_err());
+        assert!(interpect(crate::sql!("INSERT INTO t VALUES (CAST(1 AS money));")).is_err());
+    }
+
+    #[test]
+    fn parse_json_calls() {
+        let query = crate::sql!(
+            "SELECT (id, json_extract(attrs, '$.a.b')) FROM t WHERE json_array_length(attrs) > 1 AND attrs = json('{ \"a\": 2 }');"
+        );
+        let call = |function: &str, args: &[&str]| ColumnExpr::Call {
+            function: function.into(),
+            column: "attrs".into(),
+            args: args.iter().map(|x| x.to_string()).collect(),
+        };
+
+        match interpect(query).expect("Failed to parse") {
+            Statement::Select {
+                columns, target, ..
+            } => {
+                assert_eq!(columns, vec!["id".into(), call("json_extract", &["$.a.b"])]);
+                assert_eq!(
+                    target,
+                    Some(vec![
+                        Condition::GT(call("json_array_length", &[]), "1".into()),
+                        Condition::AND,
+                        // a document written as a literal is evaluated right away.
+                        Condition::E("attrs".into(), r#"{"a":2}"#.into()),
+                    ])
+                );
+            }
+            value => panic!("Expected select statement, got {:?}", value),
+        }
+
+        let query = crate::sql!("UPDATE t SET attrs = json_set(attrs, '$.a', 1);");
+        match interpect(query).expect("Failed to parse") {
+            Statement::Update { columns, .. } => assert_eq!(
+                columns,
+                vec![(
+                    "attrs".into(),
+                    ColumnData::Call(call("json_set", &["$.a", "1"]))
+                )]
+            ),
+            value => panic!("Expected update statement, got {:?}", value),
+        }
+    }
+
+    #[test]
+    fn parse_date_functions() {
+        let query = crate::sql!(
+            "at BETWEEN date(\"2024-01-31 13:45\", \"+1 month\") AND datetime(1706708700, \"start of year\");"
+        );
+        let mut iter = query.iter().peekable();
+
+        assert_eq!(
+            parse_expr(&mut iter).expect("Failed to parse"),
+            vec![Condition::BETWEEN(
+                "at".into(),
+                "2024-02-29".into(),
+                "2024-01-01T00:00:00Z".into()
+            )]
+        );
+
+        let query =
+            crate::sql!("INSERT INTO events VALUES (1,strftime(\"%Y\", \"2024-01-31\"),null);");
+        match interpect(query).expect("Failed to parse") {
+            Statement::Insert { data, .. } => assert_eq!(
+                data,
+                vec![
+                    ColumnData::Value("1".into()),
+                    ColumnData::Value("2024".into()),
+                    ColumnData::Null
+                ]
+            ),
+            value => panic!("Expected insert statement, got {:?}", value),
+        }
+
+        assert!(interpect(crate::sql!("INSERT INTO events VALUES (now(1));")).is_err());
+        assert!(interpect(crate::sql!(
+            "INSERT INTO events VALUES (date(\"2024-13-01\"));"
+        ))
+        .is_err());
+        assert!(interpect(crate::sql!("INSERT INTO events VALUES (age());")).is_err());
+    }
+
     #[test]
     fn where_expr_test() {
         let delete_query = crate::sql!("DELETE FROM test WHERE id=1;");
@@ -635,9 +1295,59 @@ mod tests {
         }
     }
 
+    #[test]
+    fn create_table_with_type_aliases() {
+        let query =
+            crate::sql!("CREATE TABLE test (id INTEGER PRIMARY KEY, name VARCHAR(255), bio TEXT);");
+
+        match interpect(query).expect("Failed to parse") {
+            Statement::Create { cols, .. } => {
+                let types: Vec<DataType> = cols.iter().map(|x| x.data_type).collect();
+                assert_eq!(
+                    types,
+                    vec![DataType::Int, DataType::String, DataType::String]
+                );
+            }
+            value => panic!("Expected create statement, got {:?}", value),
+        }
+
+        let query = crate::sql!("CREATE TABLE test (id int, price money);");
+        assert!(matches!(interpect(query), Err(Error::UnknownType(name)) if name == "money"));
+
+        let query = crate::sql!("CREATE TABLE test (id int(4));");
+        assert!(matches!(interpect(query), Err(Error::UnknownType(name)) if name == "int(4)"));
+    }
+
+    #[test]
+    fn create_table_with_composite_primary_key() {
+        let query = crate::sql!(
+            "CREATE TABLE test (tenant_id uint, id uint, name string, PRIMARY KEY (tenant_id, id DESC));"
+        );
+
+        match interpect(query).expect("Failed to parse") {
+            Statement::Create {
+                primary_key, cols, ..
+            } => {
+                assert_eq!(primary_key, vec![0, 1]);
+                assert!(!cols[0].nullable && !cols[1].nullable && cols[2].nullable);
+                assert_eq!(cols[0].ordering, Ordering::Asc);
+                assert_eq!(cols[1].ordering, Ordering::Desc);
+            }
+            value => panic!("Expected create statement, got {:?}", value),
+        }
+
+        let query = crate::sql!(
+            "CREATE TABLE test (id uint PRIMARY KEY, name string, PRIMARY KEY (name));"
+        );
+        assert!(interpect(query).is_err());
+
+        let query = crate::sql!("CREATE TABLE test (id uint, PRIMARY KEY (other));");
+        assert!(interpect(query).is_err());
+    }
+
     #[test]
     fn test_column_constarint_primary_key() {
-        let primary_key = vec![Token::Ident("PRIMARY".into()), Token::Ident("KEY".into())];
+        let primary_key = [Token::Ident("PRIMARY".into()), Token::Ident("KEY".into())];
         let mut primary_iter = primary_key.iter().peekable();
 
         match parse_column_constraint(&mut primary_iter) {
@@ -650,7 +1360,7 @@ mod tests {
 
     #[test]
     fn test_column_constarint_primary_key_autoint() {
-        let primary_key = vec![
+        let primary_key = [
             Token::Ident("PRIMARY".into()),
             Token::Ident("KEY".into()),
             Token::Ident("AUTOINCREMENT".into()),
@@ -667,7 +1377,7 @@ mod tests {
 
     #[test]
     fn test_column_constarint_primary_key_desc() {
-        let primary_key = vec![
+        let primary_key = [
             Token::Ident("PRIMARY".into()),
             Token::Ident("KEY".into()),
             Token::Ident("DESC".into()),
@@ -684,7 +1394,7 @@ mod tests {
 
     #[test]
     fn test_column_constarint_not_null() {
-        let primary_key = vec![Token::Ident("NOT".into()), Token::Ident("NULL".into())];
+        let primary_key = [Token::Ident("NOT".into()), Token::Ident("NULL".into())];
         let mut primary_iter = primary_key.iter().peekable();
 
         match parse_column_constraint(&mut primary_iter) {
@@ -697,7 +1407,7 @@ mod tests {
 
     #[test]
     fn test_column_constarint_unique() {
-        let primary_key = vec![Token::Ident("UNIQUE".into())];
+        let primary_key = [Token::Ident("UNIQUE".into())];
         let mut primary_iter = primary_key.iter().peekable();
 
         match parse_column_constraint(&mut primary_iter) {
@@ -708,6 +1418,23 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_column_constarint_default() {
+        let query = crate::sql!("DEFAULT -1 DEFAULT uuid_v7() DEFAULT nope() DEFAULT now(1)");
+        let mut iter = query.iter().peekable();
+
+        assert_eq!(
+            parse_column_constraint(&mut iter).expect("Failed to parse"),
+            ColumnConstraint::Default(DefaultValue::Value("-1".into()))
+        );
+        assert_eq!(
+            parse_column_constraint(&mut iter).expect("Failed to parse"),
+            ColumnConstraint::Default(DefaultValue::Call("uuid_v7".into(), vec![]))
+        );
+        assert!(parse_column_constraint(&mut iter).is_err());
+        assert!(parse_column_constraint(&mut iter).is_err());
+    }
+
     #[test]
     fn test_interpect() {
         let tokens = vec![
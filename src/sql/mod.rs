@@ -1,10 +1,13 @@
+use std::fmt::Display;
+
 use serde::{Deserialize, Serialize};
 
-use crate::engine::structure::Value;
+use crate::engine::{data_type::DataType, structure::Value};
 
-use self::interperter::ColumnData;
+use self::{error::Error, interperter::ColumnData};
 
 pub mod error;
+pub mod function;
 pub mod interperter;
 pub mod tokenizer;
 
@@ -33,30 +36,110 @@ impl Default for Ordering {
     }
 }
 
-#[derive(Debug, PartialEq)]
+/// Operator of an arithmetic expression.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Arithmetic {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+}
+
+/// What is read from a row, a column or a function called on one, `json_extract(attrs, "$.a")`.
+#[derive(Debug, PartialEq, Clone)]
+pub enum ColumnExpr {
+    Column(String),
+    /// A function with a column as its first argument and literals after it.
+    Call {
+        function: String,
+        column: String,
+        args: Vec<String>,
+    },
+    /// The column converted to another type, `CAST(age AS string)`.
+    Cast {
+        column: String,
+        data_type: DataType,
+    },
+}
+
+impl From<&str> for ColumnExpr {
+    fn from(value: &str) -> Self {
+        ColumnExpr::Column(value.to_owned())
+    }
+}
+
+impl From<String> for ColumnExpr {
+    fn from(value: String) -> Self {
+        ColumnExpr::Column(value)
+    }
+}
+
+impl Display for ColumnExpr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ColumnExpr::Column(name) => write!(f, "{}", name),
+            ColumnExpr::Call {
+                function,
+                column,
+                args,
+            } => {
+                write!(f, "{}({}", function, column)?;
+                for arg in args {
+                    write!(f, ", \"{}\"", arg)?;
+                }
+                write!(f, ")")
+            }
+            ColumnExpr::Cast { column, data_type } => {
+                write!(f, "cast({} as {})", column, data_type)
+            }
+        }
+    }
+}
+
+// the variants are named after the SQL operators, like the ones of `Operation`.
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, PartialEq, Clone)]
 pub enum Condition {
-    E(String, String),
-    GT(String, String),
-    LT(String, String),
-    GTE(String, String),
-    LTE(String, String),
-    NE(String, String),
+    E(ColumnExpr, String),
+    GT(ColumnExpr, String),
+    LT(ColumnExpr, String),
+    GTE(ColumnExpr, String),
+    LTE(ColumnExpr, String),
+    NE(ColumnExpr, String),
     NOT,
-    BETWEEN(String, String, String),
-    LIKE(String, String),
+    BETWEEN(ColumnExpr, String, String),
+    LIKE(ColumnExpr, String),
     AND,
     OR,
 }
 
+/// Value of a column an insert leaves out.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Eq)]
+pub enum DefaultValue {
+    Value(String),
+    /// A function called for every row that is inserted, `uuid_v7()`.
+    Call(String, Vec<String>),
+}
+
+impl DefaultValue {
+    /// The literal the value of the column is parsed from.
+    pub fn evaluate(&self) -> Result<String, Error> {
+        match self {
+            DefaultValue::Value(value) => Ok(value.to_owned()),
+            DefaultValue::Call(name, args) => function::call(name, args),
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Eq)]
 pub struct ColumnDef {
     pub name: String,
     pub nullable: bool,
-    pub data_type: u8,
+    pub data_type: DataType,
     pub unique: bool,
     pub autoincrement: bool,
     pub ordering: Ordering,
-    pub default_value: Option<String>,
+    pub default_value: Option<DefaultValue>,
 }
 
 impl ColumnDef {
@@ -64,10 +147,10 @@ impl ColumnDef {
         name: String,
         nullable: bool,
         unique: bool,
-        data_type: u8,
+        data_type: DataType,
         autoincrement: bool,
         ordering: Ordering,
-        default_value: Option<String>,
+        default_value: Option<DefaultValue>,
     ) -> Self {
         Self {
             unique,
@@ -95,11 +178,12 @@ pub enum Statement {
     },
     Select {
         table: String,
-        columns: Vec<String>,
+        columns: Vec<ColumnExpr>,
         target: Option<Vec<Condition>>,
     },
     Create {
-        primary_key: usize,
+        /// Columns of the primary key, more then one for a composite key.
+        primary_key: Vec<usize>,
         table: String,
         cols: Vec<ColumnDef>,
     },
@@ -115,4 +199,48 @@ pub enum Statement {
     DropTable {
         table: String,
     },
+    /// CREATE [UNIQUE] INDEX {NAME} ON {TABLE} (COLUMN-NAME(,)...)
+    CreateIndex {
+        name: String,
+        table: String,
+        columns: Vec<String>,
+        unique: bool,
+    },
+    DropIndex {
+        name: String,
+    },
+    /// PRAGMA {NAME} (= VALUE)?
+    Pragma {
+        name: String,
+        value: Option<String>,
+    },
+    /// ANALYZE {TABLE}?
+    Analyze {
+        table: Option<String>,
+    },
+    /// EXPLAIN QUERY PLAN {STATEMENT}
+    Explain(Box<Statement>),
+}
+
+impl Statement {
+    /// Names of tables and indexes are not case sensitive, like in sqlite. They are lower cased
+    /// here, before the statement is run, so the locks, files and catalog all use the same name.
+    pub fn fold_names(&mut self) {
+        match self {
+            Statement::Insert { table, .. }
+            | Statement::Select { table, .. }
+            | Statement::Create { table, .. }
+            | Statement::Delete { table, .. }
+            | Statement::Update { table, .. }
+            | Statement::DropTable { table }
+            | Statement::Analyze { table: Some(table) } => *table = table.to_lowercase(),
+            Statement::CreateIndex { name, table, .. } => {
+                *name = name.to_lowercase();
+                *table = table.to_lowercase();
+            }
+            Statement::DropIndex { name } => *name = name.to_lowercase(),
+            Statement::Explain(statement) => statement.fold_names(),
+            Statement::Pragma { .. } | Statement::Analyze { table: None } => {}
+        }
+    }
 }
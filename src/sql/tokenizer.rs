@@ -15,6 +15,9 @@ pub enum Token {
     LeftPren,
     Period,
     Star,
+    Plus,
+    Minus,
+    Slash,
     Equal,
     GreaterThan,
     LessThan,
@@ -23,6 +26,10 @@ pub enum Token {
     NotEqual,
     String(String),
     Number(String),
+    /// The keywords `TRUE` and `FALSE`.
+    Boolean(bool),
+    /// The hex digits of a blob literal, `X'DEADBEEF'`.
+    Blob(String),
     Ident(String),
     EOL,
 }
@@ -45,6 +52,14 @@ impl Token {
             _ => None,
         }
     }
+
+    /// Name of a table or index as it is written, `Statement::fold_names` lower cases it.
+    pub fn get_name(&self) -> Option<String> {
+        match self {
+            Self::Ident(value) => Some(value.to_owned()),
+            _ => None,
+        }
+    }
 }
 
 impl From<&str> for Token {
@@ -53,6 +68,9 @@ impl From<&str> for Token {
             "(" => Token::LeftPren,
             ")" => Token::RightPren,
             "*" => Token::Star,
+            "+" => Token::Plus,
+            "-" => Token::Minus,
+            "/" => Token::Slash,
             ";" => Token::SemiComma,
             "," => Token::Comma,
             "." => Token::Period,
@@ -62,6 +80,8 @@ impl From<&str> for Token {
             ">=" => Self::GreaterThanOrEqual,
             "<=" => Self::LessThanOrEqual,
             "!=" => Self::NotEqual,
+            e if e.eq_ignore_ascii_case("true") => Self::Boolean(true),
+            e if e.eq_ignore_ascii_case("false") => Self::Boolean(false),
             _ => Token::Ident(value.into()),
         }
     }
@@ -80,6 +100,9 @@ impl From<char> for Token {
             '(' => Self::LeftPren,
             ',' => Self::Comma,
             '*' => Self::Star,
+            '+' => Self::Plus,
+            '-' => Self::Minus,
+            '/' => Self::Slash,
             ';' => Self::SemiComma,
             '=' => Self::Equal,
             '>' => Self::GreaterThan,
@@ -89,6 +112,18 @@ impl From<char> for Token {
     }
 }
 
+fn take_digits(input: &mut std::iter::Peekable<std::str::Chars<'_>>, value: &mut String) {
+    while let Some(item) = input.peek() {
+        if !item.is_numeric() {
+            break;
+        }
+
+        if let Some(c) = input.next() {
+            value.push(c);
+        }
+    }
+}
+
 pub fn tokenizer(buffer: &String) -> Result<Vec<Token>, Error> {
     let mut input = buffer.chars().peekable();
 
@@ -97,30 +132,61 @@ pub fn tokenizer(buffer: &String) -> Result<Vec<Token>, Error> {
         match char {
             e if e.is_control() => continue,
             e if e.is_whitespace() => continue,
-            e if e.is_numeric() => {
+            // a '-' followed by a digit is the sign of a number, `balance-10` is `balance + -10`.
+            e if e.is_numeric() || (e == '-' && input.peek().is_some_and(|x| x.is_numeric())) => {
                 let mut value = String::default();
 
                 value.push(e);
+                take_digits(&mut input, &mut value);
 
-                while let Some(item) = input.peek() {
-                    if !item.is_numeric() {
-                        break;
-                    }
+                // a fraction, only when a digit follows the '.'.
+                let mut ahead = input.clone();
+                if ahead.next() == Some('.') && ahead.peek().is_some_and(|x| x.is_ascii_digit()) {
+                    value.push(input.next().unwrap_or('.'));
+                    take_digits(&mut input, &mut value);
+                }
 
-                    if let Some(c) = input.next() {
-                        value.push(c);
+                // an exponent, 1e10, 2.5E-3.
+                let mut ahead = input.clone();
+                if ahead.next().is_some_and(|x| x == 'e' || x == 'E') {
+                    if ahead.peek().is_some_and(|x| x == &'-' || x == &'+') {
+                        ahead.next();
+                    }
+                    if ahead.peek().is_some_and(|x| x.is_ascii_digit()) {
+                        while input.peek().is_some_and(|x| !x.is_ascii_digit()) {
+                            value.extend(input.next());
+                        }
+                        take_digits(&mut input, &mut value);
                     }
                 }
 
                 tokens.push(Token::Number(value))
             }
+            'x' | 'X' if input.peek() == Some(&'\'') => {
+                input.next();
+
+                let mut value = String::default();
+                loop {
+                    match input.next() {
+                        Some('\'') => break,
+                        Some(c) => value.push(c),
+                        None => {
+                            return Err(Error::UnknownChar(
+                                "Blob literal is missing its closing '".into(),
+                            ))
+                        }
+                    }
+                }
+
+                tokens.push(Token::Blob(value))
+            }
             'a'..='z' | 'A'..='Z' | '_' => {
                 let mut value = String::default();
 
                 value.push(char);
 
                 while let Some(item) = input.peek() {
-                    if !item.is_alphabetic() {
+                    if !(item.is_alphanumeric() || item == &'_') {
                         break;
                     }
                     if let Some(c) = input.next() {
@@ -145,6 +211,27 @@ pub fn tokenizer(buffer: &String) -> Result<Vec<Token>, Error> {
 
                 tokens.push(Token::String(value))
             }
+            // a string in single quotes can hold double quotes, `'{"a": 1}'`. A quote in it is written twice.
+            '\'' => {
+                let mut value = String::default();
+                loop {
+                    match input.next() {
+                        Some('\'') if input.peek() == Some(&'\'') => {
+                            input.next();
+                            value.push('\'');
+                        }
+                        Some('\'') => break,
+                        Some(c) => value.push(c),
+                        None => {
+                            return Err(Error::UnknownChar(
+                                "String is missing its closing '".into(),
+                            ))
+                        }
+                    }
+                }
+
+                tokens.push(Token::String(value))
+            }
             '>' => match input.peek().expect("Failed to peek") {
                 '=' => {
                     input.next();
@@ -163,7 +250,7 @@ pub fn tokenizer(buffer: &String) -> Result<Vec<Token>, Error> {
                 '=' => tokens.push(token!("!=")),
                 e => return Err(Error::UnknownChar(format!("Was expecting '=' not '{}'", e))),
             },
-            '(' | ')' | '.' | '*' | ',' | ';' | '=' => tokens.push(token!(char)),
+            '(' | ')' | '.' | '*' | ',' | ';' | '=' | '+' | '-' | '/' => tokens.push(token!(char)),
             _ => {
                 return Err(Error::UnknownChar(format!(
                     ": Unknown char: {}",
@@ -186,4 +273,118 @@ mod tests {
 
         println!("{:#?}", tokens);
     }
+
+    #[test]
+    fn test_identifier_with_underscore() {
+        let input = "PRAGMA cache_stats2;".to_string();
+
+        match super::tokenizer(&input) {
+            Ok(tokens) => assert_eq!(
+                tokens,
+                vec![
+                    super::Token::Ident("PRAGMA".into()),
+                    super::Token::Ident("cache_stats2".into()),
+                    super::Token::SemiComma
+                ]
+            ),
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    #[test]
+    fn test_negative_number() {
+        let input = "VALUES (-12,3);".to_string();
+
+        match super::tokenizer(&input) {
+            Ok(tokens) => assert_eq!(
+                tokens,
+                vec![
+                    super::Token::Ident("VALUES".into()),
+                    super::Token::LeftPren,
+                    super::Token::Number("-12".into()),
+                    super::Token::Comma,
+                    super::Token::Number("3".into()),
+                    super::Token::RightPren,
+                    super::Token::SemiComma
+                ]
+            ),
+            Err(e) => panic!("{}", e),
+        }
+
+        assert_eq!(
+            super::tokenizer(&"- 1".to_string()).ok(),
+            Some(vec![super::Token::Minus, super::Token::Number("1".into())])
+        );
+    }
+
+    #[test]
+    fn test_real_number() {
+        let input = "(1.5,-2.25e10,3E-2,4e);".to_string();
+
+        match super::tokenizer(&input) {
+            Ok(tokens) => assert_eq!(
+                tokens,
+                vec![
+                    super::Token::LeftPren,
+                    super::Token::Number("1.5".into()),
+                    super::Token::Comma,
+                    super::Token::Number("-2.25e10".into()),
+                    super::Token::Comma,
+                    super::Token::Number("3E-2".into()),
+                    super::Token::Comma,
+                    super::Token::Number("4".into()),
+                    super::Token::Ident("e".into()),
+                    super::Token::RightPren,
+                    super::Token::SemiComma
+                ]
+            ),
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    #[test]
+    fn test_blob_literal() {
+        let input = "(X'DEADbeef',x'',xid);".to_string();
+
+        match super::tokenizer(&input) {
+            Ok(tokens) => assert_eq!(
+                tokens,
+                vec![
+                    super::Token::LeftPren,
+                    super::Token::Blob("DEADbeef".into()),
+                    super::Token::Comma,
+                    super::Token::Blob("".into()),
+                    super::Token::Comma,
+                    super::Token::Ident("xid".into()),
+                    super::Token::RightPren,
+                    super::Token::SemiComma
+                ]
+            ),
+            Err(e) => panic!("{}", e),
+        }
+
+        assert!(super::tokenizer(&"X'00".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_single_quoted_string() {
+        let input = r#"('{"a": "it''s"}','');"#.to_string();
+
+        match super::tokenizer(&input) {
+            Ok(tokens) => assert_eq!(
+                tokens,
+                vec![
+                    super::Token::LeftPren,
+                    super::Token::String(r#"{"a": "it's"}"#.into()),
+                    super::Token::Comma,
+                    super::Token::String("".into()),
+                    super::Token::RightPren,
+                    super::Token::SemiComma
+                ]
+            ),
+            Err(e) => panic!("{}", e),
+        }
+
+        assert!(super::tokenizer(&"'abc".to_string()).is_err());
+    }
 }